@@ -280,6 +280,12 @@ impl Layer for EditorLayer {
 
         // Render egui
         {
+            let timestamps = context
+                .world
+                .lock()
+                .unwrap()
+                .get_resource::<GpuProfiler>()
+                .and_then(|profiler| profiler.begin_pass("egui render pass"));
             let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("egui render pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -293,7 +299,9 @@ impl Layer for EditorLayer {
                 })],
                 depth_stencil_attachment: None,
                 occlusion_query_set: None,
-                timestamp_writes: None,
+                timestamp_writes: timestamps
+                    .as_ref()
+                    .map(GpuPassTimestamps::render_pass_writes),
             });
 
             // egui requires a 'static lifetime for the render pass
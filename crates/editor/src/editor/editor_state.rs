@@ -6,6 +6,8 @@ use crate::inspector::{ComponentInspector, create_component_inspector};
 #[derive(Default)]
 pub struct EditorState {
     pub selected_entity: Option<(Entity, Tag)>,
+    /// Instance of the selected `InstancedLodMesh` picked in the viewport
+    pub selected_instance: Option<u32>,
     pub component_inspector: ComponentInspector,
 }
 
@@ -13,16 +15,19 @@ impl EditorState {
     pub fn new() -> Self {
         Self {
             selected_entity: None,
+            selected_instance: None,
             component_inspector: create_component_inspector(),
         }
     }
 
     pub fn select_entity(&mut self, entity: Entity, tag: Tag) {
         self.selected_entity = Some((entity, tag));
+        self.selected_instance = None;
     }
 
     pub fn deselect_entity(&mut self) {
         self.selected_entity = None;
+        self.selected_instance = None;
     }
 
     pub fn is_entity_selected(&self, entity: Entity) -> bool {
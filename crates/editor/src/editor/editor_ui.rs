@@ -29,6 +29,17 @@ pub fn draw_ui(
                         editor_state.select_entity(entity, tag.clone());
                     }
                 });
+
+            ui.separator();
+            ui.heading("Render Settings");
+            draw_msaa_settings(ui, &mut world);
+            draw_environment_settings(ui, &mut world);
+            draw_wireframe_settings(ui, &mut world);
+            draw_debug_view_settings(ui, &mut world);
+
+            ui.separator();
+            ui.heading("Profiler");
+            draw_profiler(ui, &mut world);
         });
 
     // Get viewport size from world
@@ -43,6 +54,9 @@ pub fn draw_ui(
             ui.separator();
             if let Some((entity, tag)) = &editor_state.selected_entity {
                 ui.label(format!("{}", tag.label));
+                if let Some(instance) = editor_state.selected_instance {
+                    ui.label(format!("Instance {}", instance));
+                }
                 ui.separator();
 
                 // Use the component inspector from editor state
@@ -117,10 +131,18 @@ pub fn draw_ui(
             if let Some(texture_id) = viewport_texture_id {
                 // Use the actual texture size for 1:1 pixel mapping
                 let size = [viewport_size.width as f32, viewport_size.height as f32];
-                ui.add(
+                let response = ui.add(
                     egui::Image::new(egui::load::SizedTexture::new(texture_id, size))
-                        .fit_to_exact_size(egui::vec2(size[0], size[1])),
+                        .fit_to_exact_size(egui::vec2(size[0], size[1]))
+                        .sense(egui::Sense::click()),
                 );
+
+                if response.clicked()
+                    && let Some(pointer) = response.interact_pointer_pos()
+                {
+                    let position = (pointer - response.rect.min) / response.rect.size();
+                    pick_entity(&mut world, editor_state, [position.x, position.y]);
+                }
             } else {
                 // Paint a placeholder background for the viewport area
                 ui.painter()
@@ -139,3 +161,197 @@ pub fn draw_ui(
     //         ui.label(format!("Frame Time: {} ms", dt));
     //     });
 }
+
+/// Select the entity under a normalized point of the window image, or nothing
+///
+/// Casts through the camera shown on top at that point, the one with the highest `order` whose
+/// viewport contains it.
+fn pick_entity(world: &mut World, editor_state: &mut EditorState, position: [f32; 2]) {
+    let origin = world
+        .get_resource::<RenderOrigin>()
+        .copied()
+        .unwrap_or_default();
+    let ray = world
+        .query_filtered::<(&Camera, &Transform, &GpuCamera), With<GpuRenderTarget>>()
+        .iter(world)
+        .filter_map(|(camera, transform, gpu_camera)| {
+            let local = camera.viewport.local_position(position)?;
+            Some((camera, transform, gpu_camera, local))
+        })
+        .max_by_key(|(camera, ..)| camera.order)
+        .and_then(|(camera, transform, gpu_camera, local)| {
+            Ray::from_camera(camera, transform, &origin, gpu_camera.aspect, local)
+        });
+    let Some(ray) = ray else {
+        return;
+    };
+
+    let Some(hit) = pick(world, &ray) else {
+        editor_state.deselect_entity();
+        return;
+    };
+
+    // Untagged entities still get selected, under their id
+    let tag = world
+        .get::<Tag>(hit.entity)
+        .cloned()
+        .unwrap_or_else(|| Tag {
+            label: format!("{}", hit.entity),
+        });
+    editor_state.select_entity(hit.entity, tag);
+    editor_state.selected_instance = hit.instance;
+}
+
+/// Sample count picker, only the counts the device can render with are offered
+fn draw_msaa_settings(ui: &mut egui::Ui, world: &mut World) {
+    let Some(msaa) = world.get_resource::<MsaaSettings>().copied() else {
+        return;
+    };
+    let supported_features = world
+        .get_resource::<SupportedFeatures>()
+        .copied()
+        .unwrap_or_default();
+
+    let current = msaa.sample_count(&supported_features);
+    let mut sample_count = current;
+    ui.horizontal(|ui| {
+        ui.label("MSAA:");
+        egui::ComboBox::from_id_salt("msaa_sample_count_combo")
+            .selected_text(format!("{}x", sample_count))
+            .show_ui(ui, |ui| {
+                for count in MsaaSettings::SAMPLE_COUNTS {
+                    if supported_features.supports_sample_count(count) {
+                        ui.selectable_value(&mut sample_count, count, format!("{}x", count));
+                    }
+                }
+            });
+    });
+
+    // Only touch the resource on change, writing it rebuilds every camera target
+    if sample_count != current {
+        world.resource_mut::<MsaaSettings>().sample_count = sample_count;
+    }
+}
+
+/// Skybox and ambient light of the environment map
+fn draw_environment_settings(ui: &mut egui::Ui, world: &mut World) {
+    let Some(mut settings) = world.get_resource::<EnvironmentLighting>().copied() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        ui.label("Skybox Intensity:");
+        ui.add(
+            egui::DragValue::new(&mut settings.skybox_intensity)
+                .speed(0.01)
+                .range(0.0..=10.0),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Ambient Intensity:");
+        ui.add(
+            egui::DragValue::new(&mut settings.intensity)
+                .speed(0.01)
+                .range(0.0..=10.0),
+        );
+    });
+
+    if settings != *world.resource::<EnvironmentLighting>() {
+        *world.resource_mut::<EnvironmentLighting>() = settings;
+    }
+}
+
+/// Wireframe overlay over every mesh, for inspecting tessellation
+fn draw_wireframe_settings(ui: &mut egui::Ui, world: &mut World) {
+    let Some(mut settings) = world.get_resource::<WireframeSettings>().copied() else {
+        return;
+    };
+
+    ui.checkbox(&mut settings.overlay, "Wireframe Overlay");
+    ui.horizontal(|ui| {
+        ui.label("Wireframe Width:");
+        ui.add(
+            egui::DragValue::new(&mut settings.width)
+                .speed(0.05)
+                .range(0.5..=8.0),
+        );
+        ui.color_edit_button_rgba_unmultiplied(&mut settings.color);
+    });
+
+    // Only touch the resource on change, toggling the overlay rebuilds the wireframe meshes
+    if settings != *world.resource::<WireframeSettings>() {
+        *world.resource_mut::<WireframeSettings>() = settings;
+    }
+}
+
+/// Visualization replacing the lit output of every camera
+fn draw_debug_view_settings(ui: &mut egui::Ui, world: &mut World) {
+    let Some(current) = world.get_resource::<DebugView>().copied() else {
+        return;
+    };
+
+    let mut debug_view = current;
+    ui.horizontal(|ui| {
+        ui.label("Debug View:");
+        egui::ComboBox::from_id_salt("debug_view_combo")
+            .selected_text(debug_view.label())
+            .show_ui(ui, |ui| {
+                for view in DebugView::ALL {
+                    ui.selectable_value(&mut debug_view, view, view.label());
+                }
+            });
+    });
+
+    if debug_view != current {
+        *world.resource_mut::<DebugView>() = debug_view;
+    }
+}
+
+/// Recording toggle, the slowest scopes of the latest profiled frame and trace export
+fn draw_profiler(ui: &mut egui::Ui, world: &mut World) {
+    let Some(mut profiler) = world.get_resource_mut::<Profiler>() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut profiler.enabled, "Record");
+        if ui.button("Clear").clicked() {
+            profiler.clear();
+        }
+    });
+
+    if ui.button("Export Chrome Trace...").clicked()
+        && let Some(path) = rfd::FileDialog::new()
+            .add_filter("Chrome Trace", &["json"])
+            .set_file_name("trace.json")
+            .save_file()
+    {
+        match profiler.write_chrome_trace(&path) {
+            Ok(()) => log::info!(
+                "Wrote trace of {} frames to {:?}",
+                profiler.frames().count(),
+                path
+            ),
+            Err(e) => log::error!("Failed to write trace: {}", e),
+        }
+    }
+
+    for track in ProfileTrack::ALL {
+        let Some(frame) = profiler.latest(track) else {
+            continue;
+        };
+        let mut scopes: Vec<_> = frame.track(track).collect();
+        scopes.sort_by_key(|scope| std::cmp::Reverse(scope.duration));
+
+        egui::CollapsingHeader::new(track.label())
+            .id_salt(("profiler_track", track.label()))
+            .show(ui, |ui| {
+                for scope in scopes.iter().take(20) {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{:.3} ms", scope.duration.as_secs_f64() * 1000.0));
+                        ui.label(scope.short_name());
+                    });
+                }
+            });
+    }
+}
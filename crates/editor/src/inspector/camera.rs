@@ -8,35 +8,90 @@ impl Inspectable for Camera {
     fn inspect(&mut self, ui: &mut egui::Ui, _world: &World) {
         ui.checkbox(&mut self.is_main, "Is Main Camera");
 
+        draw_projection(ui, &mut self.projection);
+
         ui.horizontal(|ui| {
-            ui.label("Target:");
-            ui.add(
-                egui::DragValue::new(&mut self.target.x)
-                    .prefix("x: ")
-                    .speed(0.1),
-            );
-            ui.add(
-                egui::DragValue::new(&mut self.target.y)
-                    .prefix("y: ")
-                    .speed(0.1),
-            );
-            ui.add(
-                egui::DragValue::new(&mut self.target.z)
-                    .prefix("z: ")
-                    .speed(0.1),
-            );
+            ui.label("Depth:");
+            egui::ComboBox::from_id_salt("camera_depth_mode_combo")
+                .selected_text(self.depth_mode.label())
+                .show_ui(ui, |ui| {
+                    for depth_mode in DepthMode::ALL {
+                        ui.selectable_value(&mut self.depth_mode, depth_mode, depth_mode.label());
+                    }
+                });
         });
 
         ui.horizontal(|ui| {
-            ui.label("FOV Y:");
-            ui.add(egui::DragValue::new(&mut self.fovy).speed(0.01));
+            ui.label("Order:");
+            ui.add(egui::DragValue::new(&mut self.order));
         });
 
-        ui.horizontal(|ui| {
-            ui.label("Near:");
-            ui.add(egui::DragValue::new(&mut self.znear).speed(0.01));
-            ui.label("Far:");
-            ui.add(egui::DragValue::new(&mut self.zfar).speed(0.1));
+        ui.collapsing("Clear", |ui| {
+            let mut skybox = self.clear == ClearMode::Skybox;
+            if ui.checkbox(&mut skybox, "Skybox").changed() {
+                self.clear = if skybox {
+                    ClearMode::Skybox
+                } else {
+                    ClearMode::Color(ClearMode::DEFAULT_COLOR)
+                };
+            }
+
+            if let ClearMode::Color(color) = &mut self.clear {
+                ui.horizontal(|ui| {
+                    ui.label("Color:");
+                    ui.color_edit_button_rgba_unmultiplied(color);
+                });
+            }
+        });
+
+        ui.collapsing("Viewport", |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut self.viewport.x)
+                        .prefix("x: ")
+                        .speed(0.01)
+                        .range(0.0..=1.0),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.viewport.y)
+                        .prefix("y: ")
+                        .speed(0.01)
+                        .range(0.0..=1.0),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut self.viewport.width)
+                        .prefix("w: ")
+                        .speed(0.01)
+                        .range(0.0..=1.0),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.viewport.height)
+                        .prefix("h: ")
+                        .speed(0.01)
+                        .range(0.0..=1.0),
+                );
+            });
+
+            let mut fixed = matches!(self.resolution, RenderResolution::Fixed { .. });
+            if ui.checkbox(&mut fixed, "Fixed Resolution").changed() {
+                self.resolution = if fixed {
+                    RenderResolution::Fixed {
+                        width: 1280,
+                        height: 720,
+                    }
+                } else {
+                    RenderResolution::Viewport
+                };
+            }
+
+            if let RenderResolution::Fixed { width, height } = &mut self.resolution {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(width).prefix("w: ").range(1..=8192));
+                    ui.add(egui::DragValue::new(height).prefix("h: ").range(1..=8192));
+                });
+            }
         });
 
         ui.collapsing("Depth of Field", |ui| {
@@ -68,3 +123,85 @@ impl Inspectable for Camera {
         });
     }
 }
+
+fn draw_projection(ui: &mut egui::Ui, projection: &mut Projection) {
+    let (znear, zfar) = projection.depth_range();
+    let kind = match projection {
+        Projection::Perspective { .. } => "Perspective",
+        Projection::Orthographic { .. } => "Orthographic",
+        Projection::Custom(_) => "Custom",
+    };
+
+    ui.horizontal(|ui| {
+        ui.label("Projection:");
+        egui::ComboBox::from_id_salt("camera_projection_combo")
+            .selected_text(kind)
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(kind == "Perspective", "Perspective")
+                    .clicked()
+                {
+                    *projection = Projection::Perspective {
+                        fovy: std::f32::consts::FRAC_PI_3,
+                        znear,
+                        zfar,
+                    };
+                }
+                if ui
+                    .selectable_label(kind == "Orthographic", "Orthographic")
+                    .clicked()
+                {
+                    *projection = Projection::Orthographic {
+                        scaling: OrthographicScaling::FixedVertical(10.0),
+                        znear,
+                        zfar,
+                    };
+                }
+            });
+    });
+
+    match projection {
+        Projection::Perspective { fovy, znear, zfar } => {
+            ui.horizontal(|ui| {
+                ui.label("FOV Y:");
+                ui.add(egui::DragValue::new(fovy).speed(0.01));
+            });
+            draw_depth_range(ui, znear, zfar);
+        }
+        Projection::Orthographic {
+            scaling,
+            znear,
+            zfar,
+        } => {
+            ui.horizontal(|ui| match scaling {
+                OrthographicScaling::FixedVertical(height) => {
+                    ui.label("Height:");
+                    ui.add(egui::DragValue::new(height).speed(0.1));
+                }
+                OrthographicScaling::FixedHorizontal(width) => {
+                    ui.label("Width:");
+                    ui.add(egui::DragValue::new(width).speed(0.1));
+                }
+                OrthographicScaling::Fixed { width, height } => {
+                    ui.label("Size:");
+                    ui.add(egui::DragValue::new(width).prefix("w: ").speed(0.1));
+                    ui.add(egui::DragValue::new(height).prefix("h: ").speed(0.1));
+                }
+            });
+            draw_depth_range(ui, znear, zfar);
+        }
+        // Set from code, only shown
+        Projection::Custom(_) => {
+            ui.label(format!("Near: {:.3} Far: {:.3}", znear, zfar));
+        }
+    }
+}
+
+fn draw_depth_range(ui: &mut egui::Ui, znear: &mut f32, zfar: &mut f32) {
+    ui.horizontal(|ui| {
+        ui.label("Near:");
+        ui.add(egui::DragValue::new(znear).speed(0.01));
+        ui.label("Far:");
+        ui.add(egui::DragValue::new(zfar).speed(0.1));
+    });
+}
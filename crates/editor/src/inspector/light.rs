@@ -6,7 +6,82 @@ crate::register_inspectable!(Light, "Light");
 
 impl Inspectable for Light {
     fn inspect(&mut self, ui: &mut egui::Ui, _world: &World) {
-        ui.label("Note: Position is controlled by Transform component");
+        ui.label("Note: Position and direction are controlled by Transform component");
+
+        ui.horizontal(|ui| {
+            ui.label("Kind:");
+            egui::ComboBox::from_id_salt("light_kind_combo")
+                .selected_text(match self.kind {
+                    LightKind::Directional => "Directional",
+                    LightKind::Point { .. } => "Point",
+                    LightKind::Spot { .. } => "Spot",
+                })
+                .show_ui(ui, |ui| {
+                    let range = match self.kind {
+                        LightKind::Point { range } | LightKind::Spot { range, .. } => range,
+                        LightKind::Directional => 10.0,
+                    };
+
+                    if ui
+                        .selectable_label(
+                            matches!(self.kind, LightKind::Directional),
+                            "Directional",
+                        )
+                        .clicked()
+                    {
+                        self.kind = LightKind::Directional;
+                    }
+                    if ui
+                        .selectable_label(matches!(self.kind, LightKind::Point { .. }), "Point")
+                        .clicked()
+                    {
+                        self.kind = LightKind::Point { range };
+                    }
+                    if ui
+                        .selectable_label(matches!(self.kind, LightKind::Spot { .. }), "Spot")
+                        .clicked()
+                        && !matches!(self.kind, LightKind::Spot { .. })
+                    {
+                        self.kind = LightKind::Spot {
+                            range,
+                            inner_angle: 0.3,
+                            outer_angle: 0.5,
+                        };
+                    }
+                });
+        });
+
+        match &mut self.kind {
+            LightKind::Directional => {}
+            LightKind::Point { range } => {
+                ui.horizontal(|ui| {
+                    ui.label("Range:");
+                    ui.add(egui::DragValue::new(range).speed(0.1).range(0.01..=10000.0));
+                });
+            }
+            LightKind::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+            } => {
+                ui.horizontal(|ui| {
+                    ui.label("Range:");
+                    ui.add(egui::DragValue::new(range).speed(0.1).range(0.01..=10000.0));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Inner Angle:");
+                    ui.drag_angle(inner_angle);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Outer Angle:");
+                    ui.drag_angle(outer_angle);
+                });
+
+                // Keep the cone valid
+                *outer_angle = outer_angle.clamp(0.0, std::f32::consts::FRAC_PI_2);
+                *inner_angle = inner_angle.clamp(0.0, *outer_angle);
+            }
+        }
 
         ui.horizontal(|ui| {
             ui.label("Intensity:");
@@ -0,0 +1,47 @@
+use crate::prelude::*;
+use trialogue_engine::prelude::*;
+
+// Auto-register for inspection
+crate::register_inspectable!(LookAt, "Look At");
+
+impl Inspectable for LookAt {
+    fn inspect(&mut self, ui: &mut egui::Ui, _world: &World) {
+        ui.horizontal(|ui| {
+            ui.label("Target:");
+            ui.add(
+                egui::DragValue::new(&mut self.target.x)
+                    .prefix("x: ")
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.target.y)
+                    .prefix("y: ")
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.target.z)
+                    .prefix("z: ")
+                    .speed(0.1),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Up:");
+            ui.add(
+                egui::DragValue::new(&mut self.up.x)
+                    .prefix("x: ")
+                    .speed(0.01),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.up.y)
+                    .prefix("y: ")
+                    .speed(0.01),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.up.z)
+                    .prefix("z: ")
+                    .speed(0.01),
+            );
+        });
+    }
+}
@@ -31,17 +31,12 @@ impl Inspectable for Material {
                         "Fill",
                     );
 
-                    // Only show Line mode if supported
-                    if supported_features
-                        .map(|f| f.polygon_mode_line)
-                        .unwrap_or(false)
-                    {
-                        ui.selectable_value(
-                            &mut self.render_mode.polygon_mode,
-                            wgpu::PolygonMode::Line,
-                            "Line",
-                        );
-                    }
+                    // Without `POLYGON_MODE_LINE` the wireframe pass draws Line materials
+                    ui.selectable_value(
+                        &mut self.render_mode.polygon_mode,
+                        wgpu::PolygonMode::Line,
+                        "Line",
+                    );
 
                     // Only show Point mode if supported
                     if supported_features
@@ -57,17 +52,58 @@ impl Inspectable for Material {
                 });
         });
 
+        ui.horizontal(|ui| {
+            ui.label("Blend:");
+            egui::ComboBox::from_id_salt("blend_mode_combo")
+                .selected_text(format!("{:?}", self.render_mode.blend))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.render_mode.blend, BlendMode::Opaque, "Opaque");
+                    ui.selectable_value(&mut self.render_mode.blend, BlendMode::Alpha, "Alpha");
+                    ui.selectable_value(
+                        &mut self.render_mode.blend,
+                        BlendMode::Premultiplied,
+                        "Premultiplied",
+                    );
+                    ui.selectable_value(
+                        &mut self.render_mode.blend,
+                        BlendMode::Additive,
+                        "Additive",
+                    );
+                });
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Cull Mode:");
+            egui::ComboBox::from_id_salt("cull_mode_combo")
+                .selected_text(match self.render_mode.cull_mode {
+                    Some(face) => format!("{:?}", face),
+                    None => "None".to_string(),
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.render_mode.cull_mode,
+                        Some(wgpu::Face::Back),
+                        "Back",
+                    );
+                    ui.selectable_value(
+                        &mut self.render_mode.cull_mode,
+                        Some(wgpu::Face::Front),
+                        "Front",
+                    );
+                    ui.selectable_value(&mut self.render_mode.cull_mode, None, "None");
+                });
+        });
+
+        ui.checkbox(&mut self.render_mode.depth_test, "Depth Test");
+        ui.checkbox(&mut self.render_mode.depth_write, "Depth Write");
+        ui.checkbox(&mut self.render_mode.alpha_to_coverage, "Alpha to Coverage");
+
         // Reset to Fill if current mode is not supported
-        if let Some(features) = supported_features {
-            match self.render_mode.polygon_mode {
-                wgpu::PolygonMode::Line if !features.polygon_mode_line => {
-                    self.render_mode.polygon_mode = wgpu::PolygonMode::Fill;
-                }
-                wgpu::PolygonMode::Point if !features.polygon_mode_point => {
-                    self.render_mode.polygon_mode = wgpu::PolygonMode::Fill;
-                }
-                _ => {}
-            }
+        if let Some(features) = supported_features
+            && self.render_mode.polygon_mode == wgpu::PolygonMode::Point
+            && !features.polygon_mode_point
+        {
+            self.render_mode.polygon_mode = wgpu::PolygonMode::Fill;
         }
     }
 }
@@ -1,9 +1,12 @@
 mod camera;
 mod environment_map;
 mod light;
+mod look_at;
 mod material;
 mod mesh;
+mod post_process;
 mod sphere;
+mod tonemapping;
 mod transform;
 
 use crate::prelude::*;
@@ -0,0 +1,125 @@
+use crate::prelude::*;
+use trialogue_engine::prelude::*;
+
+// Auto-register for inspection
+crate::register_inspectable!(PostProcessStack, "Post Processing");
+
+/// Effects that can be added from the inspector, the others need assets or shaders
+fn addable_effects() -> [PostEffect; 5] {
+    [
+        PostEffect::Bloom(Bloom::default()),
+        PostEffect::Fxaa(Fxaa::default()),
+        PostEffect::Vignette(Vignette::default()),
+        PostEffect::ChromaticAberration(ChromaticAberration::default()),
+        PostEffect::FilmGrain(FilmGrain::default()),
+    ]
+}
+
+impl Inspectable for PostProcessStack {
+    fn inspect(&mut self, ui: &mut egui::Ui, _world: &World) {
+        let mut move_up = None;
+        let mut remove = None;
+        let count = self.effects.len();
+
+        for (index, effect) in self.effects.iter_mut().enumerate() {
+            ui.push_id(index, |ui| {
+                ui.group(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong(effect.name());
+                        ui.label(format!("({:?})", effect.stage()));
+                        if ui.add_enabled(index > 0, egui::Button::new("^")).clicked() {
+                            move_up = Some(index);
+                        }
+                        if ui
+                            .add_enabled(index + 1 < count, egui::Button::new("v"))
+                            .clicked()
+                        {
+                            move_up = Some(index + 1);
+                        }
+                        if ui.button("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    inspect_effect(effect, ui);
+                });
+            });
+        }
+
+        if let Some(index) = move_up {
+            self.effects.swap(index - 1, index);
+        }
+        if let Some(index) = remove {
+            self.effects.remove(index);
+        }
+
+        egui::ComboBox::from_id_salt("add_post_effect_combo")
+            .selected_text("Add Effect")
+            .show_ui(ui, |ui| {
+                for effect in addable_effects() {
+                    if ui.selectable_label(false, effect.name()).clicked() {
+                        self.effects.push(effect);
+                    }
+                }
+            });
+    }
+}
+
+fn inspect_effect(effect: &mut PostEffect, ui: &mut egui::Ui) {
+    match effect {
+        PostEffect::Bloom(bloom) => {
+            drag_value(ui, "Threshold:", &mut bloom.threshold, 0.0..=100.0);
+            drag_value(ui, "Knee:", &mut bloom.knee, 0.0..=1.0);
+            drag_value(ui, "Intensity:", &mut bloom.intensity, 0.0..=10.0);
+        }
+        PostEffect::Fxaa(fxaa) => {
+            drag_value(ui, "Edge Threshold:", &mut fxaa.edge_threshold, 0.0..=1.0);
+            drag_value(
+                ui,
+                "Edge Threshold Min:",
+                &mut fxaa.edge_threshold_min,
+                0.0..=1.0,
+            );
+            drag_value(
+                ui,
+                "Subpixel Quality:",
+                &mut fxaa.subpixel_quality,
+                0.0..=1.0,
+            );
+        }
+        PostEffect::Vignette(vignette) => {
+            drag_value(ui, "Intensity:", &mut vignette.intensity, 0.0..=1.0);
+            drag_value(ui, "Radius:", &mut vignette.radius, 0.0..=1.0);
+            drag_value(ui, "Smoothness:", &mut vignette.smoothness, 0.0..=1.0);
+        }
+        PostEffect::ColorGrading(grading) => {
+            drag_value(ui, "Strength:", &mut grading.strength, 0.0..=1.0);
+        }
+        PostEffect::ChromaticAberration(aberration) => {
+            drag_value(ui, "Intensity:", &mut aberration.intensity, 0.0..=0.1);
+        }
+        PostEffect::FilmGrain(grain) => {
+            drag_value(ui, "Intensity:", &mut grain.intensity, 0.0..=1.0);
+        }
+        PostEffect::Custom(custom) => {
+            ui.label(format!("Shader: {}", custom.shader));
+            ui.horizontal(|ui| {
+                ui.label("Values:");
+                for value in custom.values.iter_mut() {
+                    ui.add(egui::DragValue::new(value).speed(0.01));
+                }
+            });
+        }
+    }
+}
+
+fn drag_value(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut f32,
+    range: std::ops::RangeInclusive<f32>,
+) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(value).speed(0.005).range(range));
+    });
+}
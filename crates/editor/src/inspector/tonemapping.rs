@@ -0,0 +1,80 @@
+use crate::prelude::*;
+use trialogue_engine::prelude::*;
+
+// Auto-register for inspection
+crate::register_inspectable!(Tonemapping, "Tonemapping");
+
+impl Inspectable for Tonemapping {
+    fn inspect(&mut self, ui: &mut egui::Ui, _world: &World) {
+        ui.horizontal(|ui| {
+            ui.label("Operator:");
+            egui::ComboBox::from_id_salt("tonemap_operator_combo")
+                .selected_text(format!("{:?}", self.operator))
+                .show_ui(ui, |ui| {
+                    for operator in TonemapOperator::ALL {
+                        ui.selectable_value(
+                            &mut self.operator,
+                            operator,
+                            format!("{:?}", operator),
+                        );
+                    }
+                });
+        });
+
+        let mut auto = matches!(self.exposure, Exposure::Auto(_));
+        if ui.checkbox(&mut auto, "Auto Exposure").changed() {
+            self.exposure = if auto {
+                Exposure::Auto(AutoExposure::default())
+            } else {
+                Exposure::default()
+            };
+        }
+
+        match &mut self.exposure {
+            Exposure::Manual { ev } => {
+                ui.horizontal(|ui| {
+                    ui.label("Exposure (EV):");
+                    ui.add(egui::DragValue::new(ev).speed(0.05).range(-16.0..=16.0));
+                });
+            }
+            Exposure::Auto(auto) => {
+                ui.horizontal(|ui| {
+                    ui.label("Compensation (EV):");
+                    ui.add(
+                        egui::DragValue::new(&mut auto.compensation)
+                            .speed(0.05)
+                            .range(-16.0..=16.0),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Log Luminance:");
+                    ui.add(
+                        egui::DragValue::new(&mut auto.min_log_luminance)
+                            .prefix("min: ")
+                            .speed(0.1),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut auto.max_log_luminance)
+                            .prefix("max: ")
+                            .speed(0.1),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Adaptation Speed:");
+                    ui.add(
+                        egui::DragValue::new(&mut auto.speed_brighten)
+                            .prefix("brighten: ")
+                            .speed(0.05)
+                            .range(0.0..=100.0),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut auto.speed_darken)
+                            .prefix("darken: ")
+                            .speed(0.05)
+                            .range(0.0..=100.0),
+                    );
+                });
+            }
+        }
+    }
+}
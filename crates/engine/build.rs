@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
 fn main() {
     // Auto-discover modules and inject prelude imports
     let config = build_utils::AutoModConfig::new("src")
@@ -9,24 +13,65 @@ fn main() {
     // Tell cargo to rerun this build script if source files change
     println!("cargo:rerun-if-changed=src");
 
-    // Tell cargo to rerun this build script if shaders change
-    println!("cargo:rerun-if-changed=src/layers/renderer/shader.wgsl");
-    println!("cargo:rerun-if-changed=src/layers/raytracer/raytracer.wgsl");
+    // Validate every shader in the crate and collect the layouts of their structs
+    let mut shaders = Vec::new();
+    find_shaders(Path::new("src"), &mut shaders);
+    shaders.sort();
+
+    let mut layouts = String::new();
+    let mut module_names: HashMap<String, PathBuf> = HashMap::new();
+
+    for path in &shaders {
+        // Tell cargo to rerun this build script if this shader changes
+        println!("cargo:rerun-if-changed={}", path.display());
+
+        let module = validate_shader(path);
+
+        let module_name = path.file_stem().unwrap().to_string_lossy().to_string();
+        if let Some(existing) = module_names.insert(module_name.clone(), path.clone()) {
+            panic!(
+                "Shader files {} and {} share the name '{}' - shader file names must be unique",
+                existing.display(),
+                path.display(),
+                module_name
+            );
+        }
+
+        generate_layout_module(&mut layouts, &module_name, path, &module);
+    }
 
-    // Validate all shader files at compile time
-    validate_shader("src/layers/renderer/shader.wgsl");
-    validate_shader("src/layers/raytracer/raytracer.wgsl");
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("wgsl_layouts.rs"), layouts)
+        .expect("Failed to write generated WGSL layouts");
 }
 
-fn validate_shader(path: &str) {
+fn find_shaders(dir: &Path, shaders: &mut Vec<PathBuf>) {
+    let entries = std::fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("Failed to read directory {}: {}", dir.display(), e));
+
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            find_shaders(&path, shaders);
+        } else if path.extension().is_some_and(|ext| ext == "wgsl") {
+            shaders.push(path);
+        }
+    }
+}
+
+fn validate_shader(path: &Path) -> naga::Module {
     let shader_source = std::fs::read_to_string(path)
-        .unwrap_or_else(|e| panic!("Failed to read shader file {}: {}", path, e));
+        .unwrap_or_else(|e| panic!("Failed to read shader file {}: {}", path.display(), e));
 
     // Parse the WGSL shader
     let module = match naga::front::wgsl::parse_str(&shader_source) {
         Ok(module) => module,
         Err(e) => {
-            panic!("Shader parsing failed for {}:\n{:?}", path, e);
+            panic!(
+                "Shader parsing failed for {}:\n{}",
+                path.display(),
+                e.emit_to_string(&shader_source)
+            );
         }
     };
 
@@ -38,10 +83,97 @@ fn validate_shader(path: &str) {
 
     match validator.validate(&module) {
         Ok(_module_info) => {
-            println!("cargo:warning=✓ Shader validated: {}", path);
+            println!("cargo:warning=✓ Shader validated: {}", path.display());
         }
         Err(e) => {
-            panic!("Shader validation failed for {}:\n{:?}", path, e);
+            panic!(
+                "Shader validation failed for {}:\n{}",
+                path.display(),
+                e.emit_to_string(&shader_source)
+            );
+        }
+    }
+
+    module
+}
+
+/// Emit a module with size, alignment and per-member offset constants for every struct
+/// declared in the shader. Members bound to a `@location` also get a `_LOCATION` constant
+/// so vertex layouts can be checked against the shader inputs.
+fn generate_layout_module(out: &mut String, module_name: &str, path: &Path, module: &naga::Module) {
+    let mut layouter = naga::proc::Layouter::default();
+    layouter
+        .update(module.to_ctx())
+        .unwrap_or_else(|e| panic!("Failed to compute layouts for {}: {:?}", path.display(), e));
+
+    writeln!(
+        out,
+        "/// Layouts of the structs declared in `{}`",
+        path.display()
+    )
+    .unwrap();
+    writeln!(out, "pub mod {} {{", module_name).unwrap();
+
+    for (handle, ty) in module.types.iter() {
+        let (Some(name), naga::TypeInner::Struct { members, span }) = (&ty.name, &ty.inner) else {
+            continue;
+        };
+
+        writeln!(out, "    pub mod {} {{", to_snake_case(name)).unwrap();
+        writeln!(out, "        pub const SIZE: usize = {};", span).unwrap();
+        writeln!(
+            out,
+            "        pub const ALIGN: usize = {};",
+            layouter[handle].alignment
+        )
+        .unwrap();
+
+        for member in members {
+            let Some(member_name) = &member.name else {
+                continue;
+            };
+            let member_name = member_name.to_uppercase();
+
+            writeln!(
+                out,
+                "        pub const {}_OFFSET: usize = {};",
+                member_name, member.offset
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "        pub const {}_SIZE: usize = {};",
+                member_name, layouter[member.ty].size
+            )
+            .unwrap();
+
+            if let Some(naga::Binding::Location { location, .. }) = member.binding {
+                writeln!(
+                    out,
+                    "        pub const {}_LOCATION: u32 = {};",
+                    member_name, location
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(out, "    }}").unwrap();
+    }
+
+    writeln!(out, "}}").unwrap();
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                result.push('_');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
         }
     }
+    result
 }
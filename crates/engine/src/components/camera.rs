@@ -3,12 +3,348 @@ use crate::prelude::*;
 #[derive(Component, Clone, PartialEq)]
 pub struct Camera {
     pub is_main: bool,
-    pub target: Point3<f32>,
-    pub fovy: f32,
-    pub znear: f32,
-    pub zfar: f32,
+    /// View to clip space projection, shared by the raster renderer and the raytracer
+    pub projection: Projection,
+    /// How view depth is stored in the depth buffer, shadow maps follow it
+    pub depth_mode: DepthMode,
     pub aperture: f32,
     pub focus_distance: f32,
+    /// What the camera's target shows where no geometry is drawn
+    pub clear: ClearMode,
+    /// Area of the window the camera is shown in
+    pub viewport: Viewport,
+    /// Cameras render and are shown in ascending order, later ones cover earlier ones
+    pub order: i32,
+    /// Size of the camera's render targets
+    pub resolution: RenderResolution,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            is_main: false,
+            projection: Projection::default(),
+            depth_mode: DepthMode::default(),
+            aperture: 0.0,
+            focus_distance: 10.0,
+            clear: ClearMode::default(),
+            viewport: Viewport::default(),
+            order: 0,
+            resolution: RenderResolution::default(),
+        }
+    }
+}
+
+/// How a camera maps view space onto its target
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Vertical field of view in radians, width follows the target's aspect ratio
+    Perspective { fovy: f32, znear: f32, zfar: f32 },
+    /// Parallel projection, e.g. for top-down views
+    Orthographic {
+        scaling: OrthographicScaling,
+        znear: f32,
+        zfar: f32,
+    },
+    /// View to wgpu clip space matrix, used as is
+    Custom(Matrix4<f32>),
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective {
+            fovy: std::f32::consts::FRAC_PI_3,
+            znear: 0.1,
+            zfar: 1000.0,
+        }
+    }
+}
+
+/// World size of the area an orthographic camera shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrthographicScaling {
+    /// Fixed height, width follows the target's aspect ratio
+    FixedVertical(f32),
+    /// Fixed width, height follows the target's aspect ratio
+    FixedHorizontal(f32),
+    /// Fixed width and height, stretched to the target
+    Fixed { width: f32, height: f32 },
+}
+
+impl OrthographicScaling {
+    /// Width and height of the view for a target of `aspect`
+    pub fn size(&self, aspect: f32) -> (f32, f32) {
+        match *self {
+            OrthographicScaling::FixedVertical(height) => (height * aspect, height),
+            OrthographicScaling::FixedHorizontal(width) => (width, width / aspect),
+            OrthographicScaling::Fixed { width, height } => (width, height),
+        }
+    }
+}
+
+impl Projection {
+    /// View to wgpu clip space matrix for a target of `aspect`
+    pub fn matrix(&self, aspect: f32) -> Matrix4<f32> {
+        match *self {
+            Projection::Perspective { fovy, znear, zfar } => {
+                OPENGL_TO_WGPU * Perspective3::new(aspect, fovy, znear, zfar).to_homogeneous()
+            }
+            Projection::Orthographic {
+                scaling,
+                znear,
+                zfar,
+            } => {
+                let (width, height) = scaling.size(aspect);
+                let (half_width, half_height) = (width * 0.5, height * 0.5);
+                OPENGL_TO_WGPU
+                    * nalgebra::Orthographic3::new(
+                        -half_width,
+                        half_width,
+                        -half_height,
+                        half_height,
+                        znear,
+                        zfar,
+                    )
+                    .to_homogeneous()
+            }
+            Projection::Custom(matrix) => matrix,
+        }
+    }
+
+    /// View distances of the near and far planes
+    ///
+    /// Read back from the matrix for custom projections.
+    pub fn depth_range(&self) -> (f32, f32) {
+        match *self {
+            Projection::Perspective { znear, zfar, .. }
+            | Projection::Orthographic { znear, zfar, .. } => (znear, zfar),
+            Projection::Custom(matrix) => {
+                let inverse = matrix.try_inverse().unwrap_or_else(Matrix4::identity);
+                // View space looks down -Z
+                let distance = |depth| -inverse.transform_point(&Point3::new(0.0, 0.0, depth)).z;
+                (distance(0.0), distance(1.0))
+            }
+        }
+    }
+}
+
+/// How a camera's depth buffer stores view depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DepthMode {
+    /// 0 at the near plane up to 1 at the far plane, compared with `Less`
+    #[default]
+    Standard,
+    /// 1 at the near plane down to 0 at an infinitely far perspective far plane, compared
+    /// with `Greater`
+    ///
+    /// Float precision grows towards 0, which cancels the perspective divide bunching depth
+    /// up near the camera, so distant geometry stops z-fighting at any far distance.
+    ReverseZ,
+}
+
+impl DepthMode {
+    pub const ALL: [DepthMode; 2] = [DepthMode::Standard, DepthMode::ReverseZ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DepthMode::Standard => "Standard",
+            DepthMode::ReverseZ => "Reverse-Z",
+        }
+    }
+
+    pub fn is_reversed(&self) -> bool {
+        *self == DepthMode::ReverseZ
+    }
+
+    /// Depth of the far plane, which depth targets are cleared to
+    pub fn far_depth(&self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReverseZ => 0.0,
+        }
+    }
+
+    /// `compare` as written for standard depth, e.g. `Less` becomes `Greater` in reverse-Z
+    pub fn compare(&self, compare: wgpu::CompareFunction) -> wgpu::CompareFunction {
+        use wgpu::CompareFunction::*;
+
+        match (self, compare) {
+            (DepthMode::ReverseZ, Less) => Greater,
+            (DepthMode::ReverseZ, LessEqual) => GreaterEqual,
+            (DepthMode::ReverseZ, Greater) => Less,
+            (DepthMode::ReverseZ, GreaterEqual) => LessEqual,
+            _ => compare,
+        }
+    }
+
+    /// `bias` as written for standard depth, reverse-Z pushes the other way
+    pub fn bias(&self, bias: wgpu::DepthBiasState) -> wgpu::DepthBiasState {
+        match self {
+            DepthMode::Standard => bias,
+            DepthMode::ReverseZ => wgpu::DepthBiasState {
+                constant: -bias.constant,
+                slope_scale: -bias.slope_scale,
+                clamp: -bias.clamp,
+            },
+        }
+    }
+
+    /// Clip space transform turning a standard projection into one of this mode
+    pub fn clip_transform(&self) -> Matrix4<f32> {
+        match self {
+            DepthMode::Standard => Matrix4::identity(),
+            DepthMode::ReverseZ => REVERSE_Z,
+        }
+    }
+}
+
+/// Background of a camera's render
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ClearMode {
+    /// Linear RGBA colour
+    Color([f32; 4]),
+    /// The scene's environment map, see `EnvironmentLighting`
+    ///
+    /// Clears to `ClearMode::DEFAULT_COLOR` while there is no environment map.
+    #[default]
+    Skybox,
+}
+
+impl ClearMode {
+    pub const DEFAULT_COLOR: [f32; 4] = [0.1, 0.1, 0.3, 1.0];
+
+    /// Colour the target is cleared to before drawing
+    pub fn color(&self) -> wgpu::Color {
+        let [r, g, b, a] = match self {
+            ClearMode::Color(color) => *color,
+            ClearMode::Skybox => Self::DEFAULT_COLOR,
+        };
+        wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: a as f64,
+        }
+    }
+}
+
+/// Normalized rectangle of the window, origin at the top left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+impl Viewport {
+    /// Pixel origin and size of the rectangle in a window, at least one pixel in size
+    pub fn pixel_rect(&self, window_size: &WindowSize) -> (u32, u32, u32, u32) {
+        let scale = |value: f32, size: u32| (value.clamp(0.0, 1.0) * size as f32).round() as u32;
+        let x = scale(self.x, window_size.width);
+        let y = scale(self.y, window_size.height);
+        let width = scale(self.x + self.width, window_size.width).saturating_sub(x);
+        let height = scale(self.y + self.height, window_size.height).saturating_sub(y);
+        (x, y, width.max(1), height.max(1))
+    }
+
+    /// Normalized position inside the rectangle of a normalized window position, `None` if it
+    /// lies outside
+    pub fn local_position(&self, position: [f32; 2]) -> Option<[f32; 2]> {
+        let x = (position[0] - self.x) / self.width;
+        let y = (position[1] - self.y) / self.height;
+        ((0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)).then_some([x, y])
+    }
+}
+
+/// Size of a camera's render targets
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RenderResolution {
+    /// The pixel size of the camera's viewport in the window
+    #[default]
+    Viewport,
+    /// A fixed size, independent of the window
+    Fixed { width: u32, height: u32 },
+}
+
+impl Camera {
+    /// Size of the camera's render targets for a window of `window_size`
+    pub fn target_size(&self, window_size: &WindowSize) -> (u32, u32) {
+        match self.resolution {
+            RenderResolution::Viewport => {
+                let (_, _, width, height) = self.viewport.pixel_rect(window_size);
+                (width, height)
+            }
+            RenderResolution::Fixed { width, height } => (width.max(1), height.max(1)),
+        }
+    }
+
+    /// World to view space matrix for a camera placed at `transform`
+    ///
+    /// Cameras look along their local -Z axis with +Y up, scale is ignored.
+    pub fn view_matrix(&self, transform: &Transform) -> Matrix4<f32> {
+        self.view_matrix_relative(transform, &Point3::origin())
+    }
+
+    /// View matrix for a world with `origin` moved to zero, see `Transform::model_matrix_relative`
+    pub fn view_matrix_relative(
+        &self,
+        transform: &Transform,
+        origin: &Point3<f64>,
+    ) -> Matrix4<f32> {
+        let position: Vector3<f32> = (transform.position - origin).cast();
+        Isometry3::from_parts(position.into(), transform.rotation)
+            .inverse()
+            .to_homogeneous()
+    }
+
+    /// View to wgpu clip space matrix of the raster renderer, in the camera's `DepthMode`
+    ///
+    /// Reverse-Z perspective projections have no far plane, use `Projection::matrix` where
+    /// a finite frustum is needed.
+    pub fn projection_matrix(&self, aspect: f32) -> Matrix4<f32> {
+        match (self.depth_mode, self.projection) {
+            (DepthMode::ReverseZ, Projection::Perspective { fovy, znear, .. }) => {
+                let focal = 1.0 / (fovy * 0.5).tan();
+                // Clip depth is znear and w the view distance, so depth is 1 at the near plane
+                #[rustfmt::skip]
+                let matrix = Matrix4::new(
+                    focal / aspect, 0.0, 0.0, 0.0,
+                    0.0, focal, 0.0, 0.0,
+                    0.0, 0.0, 0.0, znear,
+                    0.0, 0.0, -1.0, 0.0,
+                );
+                matrix
+            }
+            (depth_mode, projection) => depth_mode.clip_transform() * projection.matrix(aspect),
+        }
+    }
+
+    /// World to wgpu clip space matrix
+    pub fn view_projection(&self, transform: &Transform, aspect: f32) -> Matrix4<f32> {
+        self.projection_matrix(aspect) * self.view_matrix(transform)
+    }
+
+    /// `RenderOrigin` relative to wgpu clip space matrix, as uploaded to the camera uniform
+    pub fn view_projection_relative(
+        &self,
+        transform: &Transform,
+        origin: &RenderOrigin,
+        aspect: f32,
+    ) -> Matrix4<f32> {
+        self.projection_matrix(aspect) * self.view_matrix_relative(transform, &origin.position)
+    }
 }
 
 #[derive(Component)]
@@ -24,117 +360,124 @@ pub struct CameraUniform {
     view_projection: Matrix4<f32>,
 }
 
+// Keep the camera uniform in sync with the `CameraUniform` struct of the raster shaders
+const _: () = {
+    use crate::shader::layouts::{gizmos, shader, shader_instanced, wireframe};
+    use std::mem::{offset_of, size_of};
+
+    assert!(size_of::<CameraUniform>() == shader::camera_uniform::SIZE);
+    assert!(offset_of!(CameraUniform, view_projection) == shader::camera_uniform::VIEW_PROJ_OFFSET);
+    assert!(size_of::<CameraUniform>() == shader_instanced::camera_uniform::SIZE);
+    assert!(
+        offset_of!(CameraUniform, view_projection)
+            == shader_instanced::camera_uniform::VIEW_PROJ_OFFSET
+    );
+    assert!(size_of::<CameraUniform>() == gizmos::camera_uniform::SIZE);
+    assert!(size_of::<CameraUniform>() == wireframe::camera_uniform::SIZE);
+};
+
 #[derive(Component)]
 pub struct RenderTarget {}
 
+/// Display ready output of a camera, tonemapped from its `GpuHdrTarget`
 #[derive(Component)]
 pub struct GpuRenderTarget {
     pub texture: wgpu::Texture,
 }
 
+/// Format of the linear HDR textures cameras render into
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Linear HDR colour a camera renders into before tonemapping
 #[derive(Component)]
-pub struct GpuDepthTexture {
+pub struct GpuHdrTarget {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
+    /// Multisampled colour the scene passes draw into and resolve into `texture`, `None`
+    /// without MSAA
+    pub multisampled: Option<MultisampledTexture>,
 }
 
-#[derive(Component)]
-pub struct GpuShadowMap {
+pub struct MultisampledTexture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
-    pub bind_group: wgpu::BindGroup,
-    pub light_buffer: wgpu::Buffer,
-    pub light_dir_buffer: wgpu::Buffer,
-    pub light_properties_buffer: wgpu::Buffer,
-    pub shadow_uniform_bind_group: wgpu::BindGroup,
-    pub light_dir: Vector3<f32>, // Store for comparison
-    pub light_intensity: f32,
-    pub light_color: [f32; 3],
 }
 
-// Helper constant for coordinate system conversion
-#[rustfmt::skip]
-const OPENGL_TO_WGPU: Matrix4<f32> = Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.0,
-    0.0, 0.0, 0.5, 1.0,
-);
+impl GpuHdrTarget {
+    /// Texture the scene passes draw into, pipelines are built for its sample count
+    pub fn draw_texture(&self) -> &wgpu::Texture {
+        match &self.multisampled {
+            Some(multisampled) => &multisampled.texture,
+            None => &self.texture,
+        }
+    }
 
-// GPU Component trait implementations
-impl GpuComponent for Camera {
-    type UserComponent = Camera;
-    type GpuVariant = GpuCamera;
+    /// Colour attachment of the scene passes and the view it resolves into, if multisampled
+    pub fn attachment(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.multisampled {
+            Some(multisampled) => (&multisampled.view, Some(&self.view)),
+            None => (&self.view, None),
+        }
+    }
 }
 
-impl GpuInitialize for Camera {
-    type Dependencies = (Transform,);
-
-    fn initialize(
-        user: &Self::UserComponent,
-        dependencies: Option<&Self::Dependencies>,
-        device: &wgpu::Device,
-        _queue: &wgpu::Queue,
-        context: &GpuContext,
-    ) -> Self::GpuVariant {
-        use wgpu::util::DeviceExt;
-
-        let transform = &dependencies.expect("Camera requires Transform component").0;
-
-        // Compute view matrix from transform
-        let up = transform.rotation * Vector3::y_axis();
-        let view = Isometry3::look_at_rh(&transform.position, &user.target, &up).to_homogeneous();
-
-        // Compute projection matrix (initial aspect ratio 1.0, will be updated)
-        let proj = OPENGL_TO_WGPU
-            * Perspective3::new(1.0, user.fovy, user.znear, user.zfar).to_homogeneous();
-
-        let matrix = proj * view;
-
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[matrix]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+#[derive(Component)]
+pub struct GpuDepthTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &context.camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-            label: Some("camera_bind_group"),
-        });
+/// Cascaded shadow map of a camera, one texture array layer per cascade
+#[derive(Component)]
+pub struct GpuShadowMap {
+    pub texture: wgpu::Texture,
+    /// Array view over every cascade, sampled by the main pass
+    pub view: wgpu::TextureView,
+    /// Single layer views rendered to by the shadow pass
+    pub cascade_views: Vec<wgpu::TextureView>,
+    pub sampler: wgpu::Sampler,
+    /// `Camera::depth_mode` the maps are rendered and compared in
+    pub depth_mode: DepthMode,
+    pub bind_group: wgpu::BindGroup,
+    /// Main pass bind group for `NotShadowReceiver` entities, its cascade count is zero
+    pub unshadowed_bind_group: wgpu::BindGroup,
+    /// `ShadowCascades` uniform read by the main pass
+    pub cascades_buffer: wgpu::Buffer,
+    /// Zeroed `ShadowCascades` uniform behind `unshadowed_bind_group`
+    pub unshadowed_cascades_buffer: wgpu::Buffer,
+    /// `EnvironmentUniform` of the camera, bound by both main pass bind groups
+    pub environment_buffer: wgpu::Buffer,
+    /// Light space matrix of each cascade, read by the shadow pass
+    pub cascade_buffers: Vec<wgpu::Buffer>,
+    pub cascade_bind_groups: Vec<wgpu::BindGroup>,
+}
 
-        GpuCamera {
-            buffer,
-            bind_group,
-            aspect: 1.0,
+impl GpuShadowMap {
+    /// Main pass bind group for an entity, depending on whether it receives shadows
+    pub fn main_pass_bind_group(&self, receives_shadows: bool) -> &wgpu::BindGroup {
+        if receives_shadows {
+            &self.bind_group
+        } else {
+            &self.unshadowed_bind_group
         }
     }
 }
 
-impl GpuUpdate for Camera {
-    fn update(
-        user: &Self::UserComponent,
-        gpu: &mut Self::GpuVariant,
-        dependencies: Option<&(Transform,)>,
-        _device: &wgpu::Device,
-        queue: &wgpu::Queue,
-    ) {
-        let transform = &dependencies.expect("Camera requires Transform component").0;
-
-        // Compute view matrix
-        let up = transform.rotation * Vector3::y_axis();
-        let view = Isometry3::look_at_rh(&transform.position, &user.target, &up).to_homogeneous();
-
-        // Compute projection matrix using current aspect ratio
-        let proj = OPENGL_TO_WGPU
-            * Perspective3::new(gpu.aspect, user.fovy, user.znear, user.zfar).to_homogeneous();
-
-        let matrix = proj * view;
+// Helper constant for coordinate system conversion, remaps OpenGL's -1..1 depth to 0..1
+// `Matrix4::new` takes its arguments row by row
+#[rustfmt::skip]
+pub(crate) const OPENGL_TO_WGPU: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+);
 
-        queue.write_buffer(&gpu.buffer, 0, bytemuck::cast_slice(&[matrix]));
-    }
-}
+// Flips 0..1 clip space depth, z' = w - z
+#[rustfmt::skip]
+const REVERSE_Z: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 1.0,
+    0.0, 0.0, 0.0, 1.0,
+);
@@ -0,0 +1,77 @@
+use crate::prelude::*;
+
+/// Replaces the lit output of the raster shaders with a visualization, for every camera
+///
+/// Selected in the shaders through their `DEBUG_VIEW` override constant, so each view gets its
+/// own pipelines. Shaders without the constant keep rendering lit. While a view is active the
+/// skybox and the HDR post effects are skipped and tonemapping uses a fixed exposure, so the
+/// colours stay comparable between frames.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DebugView {
+    /// Regular lit output
+    #[default]
+    Lit,
+    /// World space normals mapped to RGB
+    Normals,
+    /// Texture coordinates in red and green
+    Uvs,
+    /// Fractional part of the world position, one colour cycle per unit
+    WorldPosition,
+    /// Linear view depth, compressed so near and far detail stay visible
+    Depth,
+    /// Shadow factor of the shadow casting light, white is fully lit
+    Shadow,
+    /// Every fragment adds heat, depth testing is off so hidden surfaces count too
+    Overdraw,
+    /// A random colour per object, per instance for instanced meshes
+    EntityColor,
+    /// Quadtree depth of instanced LOD chunks, other meshes are grey
+    LodDepth,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 9] = [
+        DebugView::Lit,
+        DebugView::Normals,
+        DebugView::Uvs,
+        DebugView::WorldPosition,
+        DebugView::Depth,
+        DebugView::Shadow,
+        DebugView::Overdraw,
+        DebugView::EntityColor,
+        DebugView::LodDepth,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DebugView::Lit => "Lit",
+            DebugView::Normals => "Normals",
+            DebugView::Uvs => "UVs",
+            DebugView::WorldPosition => "World Position",
+            DebugView::Depth => "Depth",
+            DebugView::Shadow => "Shadow",
+            DebugView::Overdraw => "Overdraw",
+            DebugView::EntityColor => "Entity Colour",
+            DebugView::LodDepth => "LOD Depth",
+        }
+    }
+
+    /// Value of the `DEBUG_VIEW` override constant of the raster shaders
+    pub fn shader_index(&self) -> u32 {
+        match self {
+            DebugView::Lit => 0,
+            DebugView::Normals => 1,
+            DebugView::Uvs => 2,
+            DebugView::WorldPosition => 3,
+            DebugView::Depth => 4,
+            DebugView::Shadow => 5,
+            DebugView::Overdraw => 6,
+            DebugView::EntityColor => 7,
+            DebugView::LodDepth => 8,
+        }
+    }
+
+    pub fn is_lit(&self) -> bool {
+        *self == DebugView::Lit
+    }
+}
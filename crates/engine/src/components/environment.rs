@@ -0,0 +1,97 @@
+use crate::prelude::*;
+
+/// How the raster renderer uses the scene's `EnvironmentMap`
+///
+/// The environment is convolved into image based lighting whenever it changes, materials pick up
+/// its diffuse and specular ambient light and the skybox pass draws it behind the geometry of
+/// cameras cleared with `ClearMode::Skybox`.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct EnvironmentLighting {
+    /// Multiplier of the skybox colour
+    pub skybox_intensity: f32,
+    /// Multiplier of the ambient light materials receive from the environment
+    pub intensity: f32,
+}
+
+impl Default for EnvironmentLighting {
+    fn default() -> Self {
+        Self {
+            skybox_intensity: 1.0,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// Per camera environment data, read by the raster shaders and the skybox pass
+#[derive(ShaderType, Debug, Clone, Copy)]
+pub struct EnvironmentUniform {
+    /// Clip space to world direction, the camera's view projection without its translation
+    pub sky_inverse_view_projection: Matrix4<f32>,
+    pub camera_position: Vector3<f32>,
+    pub intensity: f32,
+    pub skybox_intensity: f32,
+    /// Mip levels of the prefiltered specular map, roughness 1.0 samples the last one
+    pub specular_mips: f32,
+}
+
+// Keep the uniform layout in sync with the `EnvironmentUniform` struct of the raster shaders
+const _: () = {
+    use crate::shader::layouts::{shader, shader_instanced, skybox};
+    use encase::ShaderSize;
+
+    assert!(EnvironmentUniform::SHADER_SIZE.get() == shader::environment_uniform::SIZE as u64);
+    assert!(
+        EnvironmentUniform::SHADER_SIZE.get() == shader_instanced::environment_uniform::SIZE as u64
+    );
+    assert!(EnvironmentUniform::SHADER_SIZE.get() == skybox::environment_uniform::SIZE as u64);
+};
+
+/// Image based lighting baked from the scene's `EnvironmentMap`
+///
+/// Bound next to the shadow map and lights of every camera. Without an environment map the
+/// cubemaps are black, so materials receive no ambient light and no skybox is drawn.
+#[derive(Resource)]
+pub struct GpuEnvironmentLighting {
+    /// The environment itself, drawn by the skybox pass
+    pub environment_view: wgpu::TextureView,
+    /// Cosine weighted convolution of the environment, indexed by surface normal
+    pub irradiance_view: wgpu::TextureView,
+    /// GGX prefiltered environment, one mip per roughness step
+    pub specular_view: wgpu::TextureView,
+    pub specular_mips: u32,
+    /// Split sum scale and bias of the specular BRDF, by view angle and roughness
+    pub brdf_lut_view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    /// `EnvironmentMap::bytes_hash` of the baked environment, `None` for the black fallback
+    pub source_hash: Option<u64>,
+}
+
+impl GpuEnvironmentLighting {
+    /// Whether an environment map has been baked, as opposed to the black fallback
+    pub fn has_environment(&self) -> bool {
+        self.source_hash.is_some()
+    }
+
+    pub fn uniform(
+        &self,
+        settings: &EnvironmentLighting,
+        camera: &Camera,
+        transform: &Transform,
+        origin: &RenderOrigin,
+        aspect: f32,
+    ) -> EnvironmentUniform {
+        // Only the direction matters for the sky, so it stays centred on the camera
+        let view_projection = camera.projection_matrix(aspect)
+            * camera.view_matrix_relative(transform, &transform.position);
+
+        EnvironmentUniform {
+            sky_inverse_view_projection: view_projection
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity),
+            camera_position: origin.relative(&transform.position).coords,
+            intensity: settings.intensity,
+            skybox_intensity: settings.skybox_intensity,
+            specular_mips: self.specular_mips as f32,
+        }
+    }
+}
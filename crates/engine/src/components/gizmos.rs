@@ -0,0 +1,241 @@
+use crate::prelude::*;
+
+use bevy_ecs::system::SystemParam;
+
+/// Linear RGBA colour of a gizmo
+pub type GizmoColor = [f32; 4];
+
+/// One world space line segment queued for drawing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GizmoLine {
+    pub start: Point3<f32>,
+    pub end: Point3<f32>,
+    pub color: GizmoColor,
+    /// Seconds the line stays visible after this frame, 0.0 draws it once
+    pub duration: f32,
+    /// Hidden behind scene geometry, otherwise drawn on top of everything
+    pub depth_test: bool,
+}
+
+/// Lines queued by `Gizmos`, drawn by the raster renderer into every camera
+#[derive(Resource, Default)]
+pub struct GizmoBuffer {
+    pub lines: Vec<GizmoLine>,
+}
+
+impl GizmoBuffer {
+    /// Lines to draw this frame, keeping the ones whose duration outlasts `delta_time`
+    pub fn advance(&mut self, delta_time: f32) -> Vec<GizmoLine> {
+        let lines = self.lines.clone();
+        self.lines.retain_mut(|line| {
+            line.duration -= delta_time;
+            line.duration > 0.0
+        });
+        lines
+    }
+}
+
+/// Duration and depth testing of queued gizmos
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GizmoStyle {
+    pub duration: f32,
+    pub depth_test: bool,
+}
+
+impl Default for GizmoStyle {
+    fn default() -> Self {
+        Self {
+            duration: 0.0,
+            depth_test: true,
+        }
+    }
+}
+
+/// Immediate mode debug drawing in world space
+///
+/// Shapes are queued as lines and shown for one frame unless drawn inside `with_style`:
+///
+/// ```ignore
+/// fn debug_system(mut gizmos: Gizmos) {
+///     gizmos.sphere(Point3::origin(), 1.0, [1.0, 0.0, 0.0, 1.0]);
+///     gizmos.with_style(GizmoStyle { duration: 2.0, depth_test: false }, |gizmos| {
+///         gizmos.ray(Point3::origin(), Vector3::y() * 5.0, [0.0, 1.0, 0.0, 1.0]);
+///     });
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct Gizmos<'w, 's> {
+    buffer: ResMut<'w, GizmoBuffer>,
+    style: Local<'s, GizmoStyle>,
+}
+
+impl Gizmos<'_, '_> {
+    const CIRCLE_SEGMENTS: usize = 32;
+
+    /// Queue the shapes drawn by `draw` with `style`
+    pub fn with_style(&mut self, style: GizmoStyle, draw: impl FnOnce(&mut Self)) {
+        let previous = std::mem::replace(&mut *self.style, style);
+        draw(self);
+        *self.style = previous;
+    }
+
+    pub fn line(&mut self, start: Point3<f32>, end: Point3<f32>, color: GizmoColor) {
+        self.buffer.lines.push(GizmoLine {
+            start,
+            end,
+            color,
+            duration: self.style.duration,
+            depth_test: self.style.depth_test,
+        });
+    }
+
+    /// Line from `origin` along `direction`, its length is that of `direction`
+    pub fn ray(&mut self, origin: Point3<f32>, direction: Vector3<f32>, color: GizmoColor) {
+        self.line(origin, origin + direction, color);
+    }
+
+    /// The twelve edges of a box
+    pub fn aabb(&mut self, aabb: &Aabb, color: GizmoColor) {
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i: u32| {
+            Point3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            )
+        });
+        self.box_edges(&corners, color);
+    }
+
+    /// Three great circles around the axes
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: GizmoColor) {
+        for normal in [Vector3::x(), Vector3::y(), Vector3::z()] {
+            self.circle(center, normal, radius, color);
+        }
+    }
+
+    /// Circle in the plane through `center` facing `normal`
+    pub fn circle(
+        &mut self,
+        center: Point3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+        color: GizmoColor,
+    ) {
+        let (u, v) = plane_basis(&normal);
+        let point = |i: usize| {
+            let angle = i as f32 / Self::CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for i in 0..Self::CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    /// Edges of the volume a view projection maps to wgpu clip space, e.g. a camera's
+    /// `Camera::view_projection` or a shadow cascade's light space matrix
+    ///
+    /// Reverse-Z cameras have no far plane, draw them with their `Projection::matrix`.
+    pub fn frustum(&mut self, view_projection: &Matrix4<f32>, color: GizmoColor) {
+        let Some(clip_to_world) = view_projection.try_inverse() else {
+            return;
+        };
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i: u32| {
+            clip_to_world.transform_point(&Point3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { 0.0 } else { 1.0 },
+            ))
+        });
+        self.box_edges(&corners, color);
+    }
+
+    /// Local X, Y and Z axes of a transform in red, green and blue
+    pub fn axes(&mut self, transform: &Transform, length: f32) {
+        let axes = [
+            (Vector3::x(), [1.0, 0.0, 0.0, 1.0]),
+            (Vector3::y(), [0.0, 1.0, 0.0, 1.0]),
+            (Vector3::z(), [0.0, 0.0, 1.0, 1.0]),
+        ];
+        for (axis, color) in axes {
+            let direction = transform.rotation * axis * length;
+            self.ray(transform.position.cast(), direction, color);
+        }
+    }
+
+    /// Square grid of `cells` by `cells` cells of `spacing` size in the plane through `center`
+    /// facing `normal`
+    pub fn grid(
+        &mut self,
+        center: Point3<f32>,
+        normal: Vector3<f32>,
+        cells: u32,
+        spacing: f32,
+        color: GizmoColor,
+    ) {
+        let (u, v) = plane_basis(&normal);
+        let half_size = cells as f32 * spacing * 0.5;
+        for i in 0..=cells {
+            let offset = i as f32 * spacing - half_size;
+            self.line(
+                center + u * offset - v * half_size,
+                center + u * offset + v * half_size,
+                color,
+            );
+            self.line(
+                center + v * offset - u * half_size,
+                center + v * offset + u * half_size,
+                color,
+            );
+        }
+    }
+
+    /// Edges between corners indexed by their x, y and z bits
+    fn box_edges(&mut self, corners: &[Point3<f32>; 8], color: GizmoColor) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+}
+
+/// Two unit vectors spanning the plane facing `normal`
+fn plane_basis(normal: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let normal = normal.normalize();
+    let helper = if normal.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    let u = normal.cross(&helper).normalize();
+    (u, normal.cross(&u))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(duration: f32) -> GizmoLine {
+        GizmoLine {
+            start: Point3::origin(),
+            end: Point3::new(1.0, 0.0, 0.0),
+            color: [1.0; 4],
+            duration,
+            depth_test: true,
+        }
+    }
+
+    #[test]
+    fn lines_stay_for_their_duration() {
+        let mut buffer = GizmoBuffer {
+            lines: vec![line(0.0), line(0.25)],
+        };
+
+        assert_eq!(buffer.advance(0.1).len(), 2);
+        assert_eq!(buffer.advance(0.1).len(), 1);
+        assert_eq!(buffer.advance(0.1).len(), 1);
+        assert!(buffer.advance(0.1).is_empty());
+    }
+}
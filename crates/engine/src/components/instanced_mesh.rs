@@ -26,10 +26,51 @@ impl InstancedLodMesh {
         self.chunks.iter().filter(|c| c.visible).collect()
     }
 
+    /// World-space center of the bounds of the visible chunks of an entity placed at
+    /// `transform`, `None` without visible chunks
+    pub fn bounds_center(&self, bounds: &Aabb, transform: &Transform) -> Option<Point3<f64>> {
+        // Relative to the entity, so only the offsets of the chunks are in single precision
+        let model = transform.model_matrix_relative(&transform.position);
+        let chunk_bounds = self
+            .visible_chunks()
+            .into_iter()
+            .map(|chunk| bounds.transformed(&(model * chunk.transform)))
+            .reduce(|a, b| a.union(&b))?;
+
+        Some(transform.position + chunk_bounds.center().coords.cast())
+    }
+
     /// Mark as needing GPU update
     pub fn mark_dirty(&mut self) {
         self.dirty = true;
     }
+
+    /// Instance data and bounding spheres of the visible chunks of an entity placed at
+    /// `transform`, relative to `origin`
+    fn instances(
+        &self,
+        transform: &Transform,
+        origin: &RenderOrigin,
+    ) -> (Vec<InstanceData>, Vec<InstanceBounds>) {
+        let local_bounds = Aabb::from_vertices(&self.base_mesh.vertices);
+        // The entity's position is subtracted from the origin in double precision, the chunks
+        // only add their small offsets within the entity
+        let model = transform.model_matrix_relative(&origin.position);
+
+        self.visible_chunks()
+            .into_iter()
+            .map(|chunk| {
+                let transform = model * chunk.transform;
+                let bounds = match local_bounds {
+                    Some(aabb) => InstanceBounds::from_aabb(&aabb.transformed(&transform)),
+                    None => InstanceBounds::unbounded(origin.relative(&chunk.center)),
+                };
+                let mut instance = InstanceData::from_matrix(&transform);
+                instance.lod_depth = chunk.depth;
+                (instance, bounds)
+            })
+            .unzip()
+    }
 }
 
 /// Represents one chunk in the LOD quadtree
@@ -40,8 +81,9 @@ pub struct LodChunk {
     /// Current depth in the quadtree
     pub depth: u32,
     /// World-space center point (for distance calculations)
-    pub center: Point3<f32>,
-    /// World transform for this instance
+    pub center: Point3<f64>,
+    /// Transform of this instance relative to the entity's `Transform`, or to the world
+    /// without one
     pub transform: Matrix4<f32>,
     /// Whether this chunk should be rendered
     pub visible: bool,
@@ -50,7 +92,12 @@ pub struct LodChunk {
 }
 
 impl LodChunk {
-    pub fn new(bounds: (f32, f32, f32, f32), depth: u32, center: Point3<f32>, transform: Matrix4<f32>) -> Self {
+    pub fn new(
+        bounds: (f32, f32, f32, f32),
+        depth: u32,
+        center: Point3<f64>,
+        transform: Matrix4<f32>,
+    ) -> Self {
         Self {
             bounds,
             depth,
@@ -67,17 +114,60 @@ impl LodChunk {
 pub struct GpuInstancedLodMesh {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    /// Every visible chunk, drawn by the shadow pass and culled per camera for the main pass
     pub instance_buffer: wgpu::Buffer,
+    /// Render origin relative bounding sphere of each instance, read by the culling pass
+    pub bounds_buffer: wgpu::Buffer,
     pub instance_count: u32,
     pub index_count: u32,
+    /// Bumped whenever the instance buffers are recreated, so bind groups using them are rebuilt
+    pub generation: u32,
+}
+
+/// Render origin relative bounding sphere of an instance, as laid out in the culling shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceBounds {
+    pub center: [f32; 3],
+    pub radius: f32,
 }
 
+impl InstanceBounds {
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        Self {
+            center: aabb.center().into(),
+            radius: aabb.half_extents().norm(),
+        }
+    }
+
+    /// Bounds that are never culled, for meshes without vertices
+    pub fn unbounded(center: Point3<f32>) -> Self {
+        Self {
+            center: center.into(),
+            radius: f32::INFINITY,
+        }
+    }
+}
+
+// Keep the bounds layout in sync with the `InstanceBounds` struct of the culling shader
+const _: () = {
+    use crate::shader::layouts::instance_cull::instance_bounds;
+    use std::mem::{offset_of, size_of};
+
+    assert!(size_of::<InstanceBounds>() == instance_bounds::SIZE);
+    assert!(offset_of!(InstanceBounds, center) == instance_bounds::CENTER_OFFSET);
+    assert!(offset_of!(InstanceBounds, radius) == instance_bounds::RADIUS_OFFSET);
+};
+
 /// Per-instance data sent to GPU
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceData {
     /// 4x4 transform matrix (stored as 4 vec4s for alignment)
     pub model_matrix: [[f32; 4]; 4],
+    /// Quadtree depth of the chunk, for the `DebugView::LodDepth` view
+    pub lod_depth: u32,
+    pub _padding: [u32; 3],
 }
 
 impl InstanceData {
@@ -92,42 +182,113 @@ impl InstanceData {
                 [m.m13, m.m23, m.m33, m.m43],
                 [m.m14, m.m24, m.m34, m.m44],
             ],
+            lod_depth: 0,
+            _padding: [0; 3],
         }
     }
 
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        // Model matrix (4 vec4s)
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Uint32,
+    ];
+
     /// Vertex buffer layout for instance data
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
         wgpu::VertexBufferLayout {
             array_stride: mem::size_of::<InstanceData>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &[
-                // Model matrix (4 vec4s)
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 3,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
-                    shader_location: 4,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
-                    shader_location: 5,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
-                    shader_location: 6,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-            ],
+            attributes: &Self::ATTRIBS,
         }
     }
 }
 
+// Keep the instance layout in sync with the `InstanceInput` struct of the instanced shaders
+const _: () = {
+    use crate::shader::layouts::{instance_cull, shader_instanced, shadow_instanced};
+    use crate::shader::vertex_attribute_matches;
+
+    let a = &InstanceData::ATTRIBS;
+    let column = std::mem::size_of::<[f32; 4]>();
+
+    // The culling pass copies the instances unchanged
+    assert!(std::mem::size_of::<InstanceData>() == instance_cull::instance_data::SIZE);
+
+    {
+        use shader_instanced::instance_input;
+        assert!(std::mem::size_of::<InstanceData>() == instance_input::SIZE);
+        assert!(vertex_attribute_matches(
+            &a[0],
+            0,
+            instance_input::MODEL_MATRIX_0_LOCATION,
+            instance_input::MODEL_MATRIX_0_SIZE
+        ));
+        assert!(vertex_attribute_matches(
+            &a[1],
+            column,
+            instance_input::MODEL_MATRIX_1_LOCATION,
+            instance_input::MODEL_MATRIX_1_SIZE
+        ));
+        assert!(vertex_attribute_matches(
+            &a[2],
+            column * 2,
+            instance_input::MODEL_MATRIX_2_LOCATION,
+            instance_input::MODEL_MATRIX_2_SIZE
+        ));
+        assert!(vertex_attribute_matches(
+            &a[3],
+            column * 3,
+            instance_input::MODEL_MATRIX_3_LOCATION,
+            instance_input::MODEL_MATRIX_3_SIZE
+        ));
+        assert!(vertex_attribute_matches(
+            &a[4],
+            column * 4,
+            instance_input::LOD_DEPTH_LOCATION,
+            instance_input::LOD_DEPTH_SIZE
+        ));
+    }
+
+    {
+        use shadow_instanced::instance_input;
+        assert!(std::mem::size_of::<InstanceData>() == instance_input::SIZE);
+        assert!(vertex_attribute_matches(
+            &a[0],
+            0,
+            instance_input::MODEL_MATRIX_0_LOCATION,
+            instance_input::MODEL_MATRIX_0_SIZE
+        ));
+        assert!(vertex_attribute_matches(
+            &a[1],
+            column,
+            instance_input::MODEL_MATRIX_1_LOCATION,
+            instance_input::MODEL_MATRIX_1_SIZE
+        ));
+        assert!(vertex_attribute_matches(
+            &a[2],
+            column * 2,
+            instance_input::MODEL_MATRIX_2_LOCATION,
+            instance_input::MODEL_MATRIX_2_SIZE
+        ));
+        assert!(vertex_attribute_matches(
+            &a[3],
+            column * 3,
+            instance_input::MODEL_MATRIX_3_LOCATION,
+            instance_input::MODEL_MATRIX_3_SIZE
+        ));
+        assert!(vertex_attribute_matches(
+            &a[4],
+            column * 4,
+            instance_input::LOD_DEPTH_LOCATION,
+            instance_input::LOD_DEPTH_SIZE
+        ));
+    }
+};
+
 // GPU Component trait implementations
 impl GpuComponent for InstancedLodMesh {
     type UserComponent = InstancedLodMesh;
@@ -159,65 +320,47 @@ impl GpuInitialize for InstancedLodMesh {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        // Create instance buffer with visible chunks
-        let visible_chunks = user.visible_chunks();
-        let instance_data: Vec<InstanceData> = visible_chunks
-            .iter()
-            .map(|chunk| InstanceData::from_matrix(&chunk.transform))
-            .collect();
-
-        let instance_buffer = if instance_data.is_empty() {
-            // Create empty buffer
-            device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("Instance Buffer"),
-                size: std::mem::size_of::<InstanceData>() as u64,
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            })
-        } else {
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            })
-        };
+        // Create instance and bounds buffers with visible chunks, `update_instanced_meshes`
+        // moves them to the entity's transform and the render origin once this is inserted
+        let (instance_data, bounds) =
+            user.instances(&Transform::default(), &RenderOrigin::default());
 
         let result = GpuInstancedLodMesh {
             vertex_buffer,
             index_buffer,
-            instance_buffer,
-            instance_count: visible_chunks.len() as u32,
+            instance_buffer: create_instance_buffer(device, "Instance Buffer", &instance_data),
+            bounds_buffer: create_instance_buffer(device, "Instance Bounds Buffer", &bounds),
+            instance_count: instance_data.len() as u32,
             index_count: user.base_mesh.indices.len() as u32,
+            generation: 0,
         };
-        
-        log::info!("Initialized GpuInstancedLodMesh: {} instances, {} indices, {} vertices",
-            result.instance_count, result.index_count, user.base_mesh.vertices.len());
-        
+
+        log::info!(
+            "Initialized GpuInstancedLodMesh: {} instances, {} indices, {} vertices",
+            result.instance_count,
+            result.index_count,
+            user.base_mesh.vertices.len()
+        );
+
         result
     }
 }
 
-impl GpuUpdate for InstancedLodMesh {
-    fn update(
-        user: &Self::UserComponent,
-        gpu: &mut Self::GpuVariant,
-        _dependencies: Option<&()>,
+impl GpuInstancedLodMesh {
+    /// Rewrite the instances of the visible chunks of an entity placed at `transform`,
+    /// relative to `origin`
+    pub fn update(
+        &mut self,
+        user: &InstancedLodMesh,
+        transform: &Transform,
+        origin: &RenderOrigin,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) {
-        // Only update if marked dirty
-        if !user.dirty {
-            return;
-        }
-
-        // Rebuild instance buffer with current visible chunks
-        let visible_chunks = user.visible_chunks();
-        let instance_data: Vec<InstanceData> = visible_chunks
-            .iter()
-            .map(|chunk| InstanceData::from_matrix(&chunk.transform))
-            .collect();
+        // Rebuild instance buffers with current visible chunks
+        let (instance_data, bounds) = user.instances(transform, origin);
 
-        gpu.instance_count = visible_chunks.len() as u32;
+        self.instance_count = instance_data.len() as u32;
 
         if instance_data.is_empty() {
             return;
@@ -225,21 +368,55 @@ impl GpuUpdate for InstancedLodMesh {
 
         // Check if we need to recreate buffer (size changed significantly)
         let needed_size = (instance_data.len() * std::mem::size_of::<InstanceData>()) as u64;
-        let current_size = gpu.instance_buffer.size();
+        let current_size = self.instance_buffer.size();
 
         if needed_size > current_size {
-            // Recreate larger buffer
-            use wgpu::util::DeviceExt;
-            gpu.instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            });
+            // Recreate larger buffers
+            self.instance_buffer =
+                create_instance_buffer(device, "Instance Buffer", &instance_data);
+            self.bounds_buffer = create_instance_buffer(device, "Instance Bounds Buffer", &bounds);
+            self.generation = self.generation.wrapping_add(1);
         } else {
-            // Update existing buffer
-            queue.write_buffer(&gpu.instance_buffer, 0, bytemuck::cast_slice(&instance_data));
+            // Update existing buffers
+            queue.write_buffer(
+                &self.instance_buffer,
+                0,
+                bytemuck::cast_slice(&instance_data),
+            );
+            queue.write_buffer(&self.bounds_buffer, 0, bytemuck::cast_slice(&bounds));
         }
 
-        log::debug!("Updated instance buffer with {} instances", gpu.instance_count);
+        log::debug!(
+            "Updated instance buffer with {} instances",
+            self.instance_count
+        );
+    }
+}
+
+/// Per-instance buffer readable as vertex input and by the culling pass
+fn create_instance_buffer<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    label: &str,
+    contents: &[T],
+) -> wgpu::Buffer {
+    use wgpu::util::DeviceExt;
+
+    let usage =
+        wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+
+    if contents.is_empty() {
+        // Create empty buffer
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: std::mem::size_of::<T>() as u64,
+            usage,
+            mapped_at_creation: false,
+        })
+    } else {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(contents),
+            usage,
+        })
     }
 }
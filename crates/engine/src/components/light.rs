@@ -0,0 +1,171 @@
+use crate::prelude::*;
+
+/// Maximum number of lights uploaded to the rasterizer each frame
+pub const MAX_LIGHTS: usize = 256;
+
+/// What kind of light source a `Light` is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// Infinitely far away light shining along the Transform's forward (-Z) axis
+    Directional,
+    /// Light radiating in all directions from the Transform's position, fading out at `range`
+    Point { range: f32 },
+    /// Cone of light along the Transform's forward (-Z) axis
+    /// `inner_angle` and `outer_angle` are half-angles in radians, the light fades between them
+    Spot {
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl LightKind {
+    /// Index of the kind in the shader's `LightData.kind`
+    pub fn shader_index(&self) -> u32 {
+        match self {
+            LightKind::Directional => 0,
+            LightKind::Point { .. } => 1,
+            LightKind::Spot { .. } => 2,
+        }
+    }
+}
+
+/// User-facing component for spawning lights
+/// Position and orientation are taken from the Transform component
+#[derive(Component, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub intensity: f32,
+    pub color: [f32; 3],
+}
+
+impl Light {
+    pub fn directional(intensity: f32, color: [f32; 3]) -> Self {
+        Self {
+            kind: LightKind::Directional,
+            intensity,
+            color,
+        }
+    }
+
+    pub fn point(intensity: f32, color: [f32; 3], range: f32) -> Self {
+        Self {
+            kind: LightKind::Point { range },
+            intensity,
+            color,
+        }
+    }
+
+    pub fn spot(
+        intensity: f32,
+        color: [f32; 3],
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+            },
+            intensity,
+            color,
+        }
+    }
+
+    /// Direction the light shines in, the Transform's forward (-Z) axis
+    pub fn direction(transform: &Transform) -> Vector3<f32> {
+        transform.rotation * -Vector3::z()
+    }
+
+    /// Pack this light into the layout the raster shaders read
+    pub fn to_light_data(&self, transform: &Transform, origin: &RenderOrigin) -> LightData {
+        let (range, spot_scale, spot_offset) = match self.kind {
+            LightKind::Directional => (0.0, 0.0, 0.0),
+            LightKind::Point { range } => (range, 0.0, 0.0),
+            LightKind::Spot {
+                range,
+                inner_angle,
+                outer_angle,
+            } => {
+                // Remap cos(angle) so the cone fades from 1 at the inner angle to 0 at the outer
+                let cos_inner = inner_angle.cos();
+                let cos_outer = outer_angle.cos();
+                let scale = 1.0 / (cos_inner - cos_outer).max(0.001);
+                (range, scale, -cos_outer * scale)
+            }
+        };
+
+        LightData {
+            position: origin.relative(&transform.position).coords,
+            range,
+            direction: Self::direction(transform),
+            kind: self.kind.shader_index(),
+            color: Vector3::from_row_slice(&self.color),
+            intensity: self.intensity,
+            spot_scale,
+            spot_offset,
+        }
+    }
+}
+
+/// A single light as laid out in the `LightData` struct of the raster shaders
+#[derive(ShaderType, Clone, Copy)]
+pub struct LightData {
+    pub position: Vector3<f32>,
+    pub range: f32,
+    pub direction: Vector3<f32>,
+    pub kind: u32,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    pub spot_scale: f32,
+    pub spot_offset: f32,
+}
+
+/// Every light in the scene as laid out in the `LightBuffer` struct of the raster shaders
+#[derive(ShaderType)]
+pub struct LightBuffer {
+    pub count: u32,
+    /// Index of the light that samples the shadow map, `u32::MAX` if none does
+    pub shadow_light: u32,
+    #[shader(size(runtime))]
+    pub lights: Vec<LightData>,
+}
+
+// Keep the light layout in sync with the `LightData` struct of the raster shaders
+const _: () = {
+    use crate::shader::layouts::{shader, shader_instanced};
+    use encase::ShaderSize;
+
+    assert!(LightData::SHADER_SIZE.get() == shader::light_data::SIZE as u64);
+    assert!(LightData::SHADER_SIZE.get() == shader_instanced::light_data::SIZE as u64);
+};
+
+/// Storage buffer holding the `LightBuffer` for all raster shaders, sized for `MAX_LIGHTS`
+#[derive(Resource)]
+pub struct GpuLights {
+    pub buffer: wgpu::Buffer,
+    /// Direction of the light that casts shadows this frame, if any
+    pub shadow_direction: Option<Vector3<f32>>,
+}
+
+impl GpuLights {
+    pub fn new(device: &wgpu::Device) -> Self {
+        use encase::ShaderSize;
+
+        let size = LightBuffer::min_size().get() + LightData::SHADER_SIZE.get() * MAX_LIGHTS as u64;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lights Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            shadow_direction: None,
+        }
+    }
+}
@@ -6,6 +6,8 @@ pub enum Shader {
     Standard,
     Instanced,
     Raytracer,
+    /// User written post effect, see `CustomPostEffect`
+    PostEffect(String),
 }
 
 impl Display for Shader {
@@ -14,30 +16,170 @@ impl Display for Shader {
             Shader::Standard => write!(f, "standard"),
             Shader::Instanced => write!(f, "instanced"),
             Shader::Raytracer => write!(f, "raytracer"),
+            Shader::PostEffect(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// How a material's output is combined with what is already in the render target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    /// Overwrite the target
+    #[default]
+    Opaque,
+    /// Classic `src * a + dst * (1 - a)` blending
+    Alpha,
+    /// Blending for colours already multiplied by their alpha
+    Premultiplied,
+    /// Add the source on top of the target
+    Additive,
+}
+
+impl BlendMode {
+    pub fn blend_state(&self) -> wgpu::BlendState {
+        match self {
+            BlendMode::Opaque => wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
         }
     }
 }
 
 /// Rendering mode configuration for materials
+///
+/// Every field is part of the pipeline cache key, so each distinct combination
+/// gets its own render pipeline the first time it is drawn.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RenderMode {
     pub polygon_mode: wgpu::PolygonMode,
-    // Future render mode properties can be added here:
-    // pub cull_mode: Option<wgpu::Face>,
-    // pub depth_test: bool,
-    // etc.
+    pub cull_mode: Option<wgpu::Face>,
+    pub blend: BlendMode,
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub topology: wgpu::PrimitiveTopology,
+    /// Turn the fragment alpha into MSAA coverage for order-independent cut-outs
+    pub alpha_to_coverage: bool,
 }
 
 impl RenderMode {
     pub fn filled() -> Self {
         Self {
             polygon_mode: wgpu::PolygonMode::Fill,
+            cull_mode: Some(wgpu::Face::Back),
+            blend: BlendMode::Opaque,
+            depth_test: true,
+            depth_write: true,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            alpha_to_coverage: false,
+        }
+    }
+
+    /// Alpha blended, depth tested but not written - drawn back-to-front in the transparent pass
+    pub fn transparent() -> Self {
+        Self {
+            blend: BlendMode::Alpha,
+            depth_write: false,
+            ..Self::filled()
         }
     }
 
     pub fn wireframe() -> Self {
         Self {
             polygon_mode: wgpu::PolygonMode::Line,
+            ..Self::filled()
+        }
+    }
+
+    pub fn with_polygon_mode(mut self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_depth(mut self, depth_test: bool, depth_write: bool) -> Self {
+        self.depth_test = depth_test;
+        self.depth_write = depth_write;
+        self
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_alpha_to_coverage(mut self, alpha_to_coverage: bool) -> Self {
+        self.alpha_to_coverage = alpha_to_coverage;
+        self
+    }
+
+    /// Value of the `ALPHA_MODE` override constant of the raster shaders at `sample_count`
+    ///
+    /// Opaque materials write an alpha of 1 whatever their texture holds. Alpha-to-coverage
+    /// needs MSAA, single-sampled cut-outs discard fragments below the cutoff instead.
+    pub fn alpha_mode_index(&self, sample_count: u32) -> u32 {
+        match (self.is_transparent(), self.alpha_to_coverage) {
+            (true, _) => 1,
+            (false, true) if sample_count > 1 => 1,
+            (false, true) => 2,
+            (false, false) => 0,
+        }
+    }
+
+    /// Whether this mode is drawn in the sorted transparent pass instead of the opaque pass
+    pub fn is_transparent(&self) -> bool {
+        self.blend != BlendMode::Opaque
+    }
+
+    /// Whether this mode cuts out the parts where its texture's alpha is below the cutoff, which
+    /// the shadow pass discards too
+    pub fn is_cutout(&self) -> bool {
+        self.alpha_to_coverage && !self.is_transparent()
+    }
+
+    pub fn primitive_state(&self) -> wgpu::PrimitiveState {
+        wgpu::PrimitiveState {
+            topology: self.topology,
+            strip_index_format: self.topology.is_strip().then(index_format),
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: self.cull_mode,
+            polygon_mode: self.polygon_mode,
+            unclipped_depth: false,
+            conservative: false,
+        }
+    }
+
+    pub fn depth_stencil_state(&self, format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: self.depth_write,
+            depth_compare: if self.depth_test {
+                wgpu::CompareFunction::Less
+            } else {
+                wgpu::CompareFunction::Always
+            },
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
         }
     }
 }
@@ -78,7 +220,44 @@ impl Material {
 
     /// Enable wireframe mode
     pub fn wireframe(mut self) -> Self {
-        self.render_mode = RenderMode::wireframe();
+        self.render_mode.polygon_mode = wgpu::PolygonMode::Line;
+        self
+    }
+
+    /// Set how this material blends with the render target
+    pub fn with_blend_mode(mut self, blend: BlendMode) -> Self {
+        self.render_mode.blend = blend;
+        self
+    }
+
+    /// Alpha blend this material in the transparent pass
+    pub fn transparent(mut self) -> Self {
+        self.render_mode.blend = BlendMode::Alpha;
+        self.render_mode.depth_write = false;
+        self
+    }
+
+    /// Render as an opaque cut-out using alpha-to-coverage
+    pub fn alpha_to_coverage(mut self) -> Self {
+        self.render_mode.alpha_to_coverage = true;
+        self
+    }
+
+    /// Set which faces are culled, `None` renders both sides
+    pub fn with_cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.render_mode.cull_mode = cull_mode;
+        self
+    }
+
+    /// Render both front and back faces
+    pub fn double_sided(self) -> Self {
+        self.with_cull_mode(None)
+    }
+
+    /// Set whether this material tests against and writes to the depth buffer
+    pub fn with_depth(mut self, depth_test: bool, depth_write: bool) -> Self {
+        self.render_mode.depth_test = depth_test;
+        self.render_mode.depth_write = depth_write;
         self
     }
 }
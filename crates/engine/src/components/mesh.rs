@@ -11,6 +11,20 @@ pub struct GpuMesh {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub index_count: u32,
+    /// Hash of the geometry, meshes with the same id are drawn together
+    pub content_id: u64,
+}
+
+impl Mesh {
+    /// Hash identifying identical geometry across entities
+    pub fn content_id(&self) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        bytemuck::cast_slice::<_, u8>(&self.vertices).hash(&mut hasher);
+        self.indices.hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
 #[repr(C)]
@@ -36,6 +50,51 @@ impl Vertex {
     }
 }
 
+// Keep the vertex layout in sync with the `VertexInput` struct of the raster shaders
+const _: () = {
+    use crate::shader::layouts::{shader, shader_instanced};
+    use crate::shader::vertex_attribute_matches;
+    use std::mem::offset_of;
+
+    let a = &Vertex::ATTRIBS;
+    assert!(vertex_attribute_matches(
+        &a[0],
+        offset_of!(Vertex, position),
+        shader::vertex_input::POSITION_LOCATION,
+        shader::vertex_input::POSITION_SIZE
+    ));
+    assert!(vertex_attribute_matches(
+        &a[1],
+        offset_of!(Vertex, uv),
+        shader::vertex_input::UV_LOCATION,
+        shader::vertex_input::UV_SIZE
+    ));
+    assert!(vertex_attribute_matches(
+        &a[2],
+        offset_of!(Vertex, normal),
+        shader::vertex_input::NORMAL_LOCATION,
+        shader::vertex_input::NORMAL_SIZE
+    ));
+    assert!(vertex_attribute_matches(
+        &a[0],
+        offset_of!(Vertex, position),
+        shader_instanced::vertex_input::POSITION_LOCATION,
+        shader_instanced::vertex_input::POSITION_SIZE
+    ));
+    assert!(vertex_attribute_matches(
+        &a[1],
+        offset_of!(Vertex, uv),
+        shader_instanced::vertex_input::UV_LOCATION,
+        shader_instanced::vertex_input::UV_SIZE
+    ));
+    assert!(vertex_attribute_matches(
+        &a[2],
+        offset_of!(Vertex, normal),
+        shader_instanced::vertex_input::NORMAL_LOCATION,
+        shader_instanced::vertex_input::NORMAL_SIZE
+    ));
+};
+
 pub type Index = u16;
 
 pub fn index_format() -> wgpu::IndexFormat {
@@ -76,6 +135,7 @@ impl GpuInitialize for Mesh {
             vertex_buffer,
             index_buffer,
             index_count: user.indices.len() as u32,
+            content_id: user.content_id(),
         }
     }
 }
@@ -107,5 +167,6 @@ impl GpuUpdate for Mesh {
         gpu.vertex_buffer = vertex_buffer;
         gpu.index_buffer = index_buffer;
         gpu.index_count = user.indices.len() as u32;
+        gpu.content_id = user.content_id();
     }
 }
@@ -1,19 +1,45 @@
 mod camera;
+mod debug_view;
+mod environment;
+mod gizmos;
 mod instanced_mesh;
 mod label;
+mod light;
 mod material;
 mod mesh;
+mod msaa;
+mod picking;
+mod post_process;
+mod present;
+mod profiler;
 mod raytracer;
 mod resources;
+mod shadow;
 mod texture;
+mod tonemapping;
 mod transform;
+mod visibility;
+mod wireframe;
 
 pub use camera::*;
+pub use debug_view::*;
+pub use environment::*;
+pub use gizmos::*;
 pub use instanced_mesh::*;
 pub use label::*;
+pub use light::*;
 pub use material::*;
 pub use mesh::*;
+pub use msaa::*;
+pub use picking::*;
+pub use post_process::*;
+pub use present::*;
+pub use profiler::*;
 pub use raytracer::*;
 pub use resources::*;
+pub use shadow::*;
 pub use texture::*;
+pub use tonemapping::*;
 pub use transform::*;
+pub use visibility::*;
+pub use wireframe::*;
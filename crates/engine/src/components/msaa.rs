@@ -0,0 +1,30 @@
+use crate::prelude::*;
+
+/// Multisample anti-aliasing of the raster passes of every camera
+///
+/// Changing this recreates the colour and depth targets of all cameras, pipelines for the new
+/// sample count are built on first use.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct MsaaSettings {
+    /// Samples per pixel, 1 turns MSAA off
+    pub sample_count: u32,
+}
+
+impl Default for MsaaSettings {
+    fn default() -> Self {
+        Self { sample_count: 4 }
+    }
+}
+
+impl MsaaSettings {
+    pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+    /// Highest count the device supports that doesn't exceed the requested one
+    pub fn sample_count(&self, supported: &SupportedFeatures) -> u32 {
+        Self::SAMPLE_COUNTS
+            .into_iter()
+            .filter(|&count| count <= self.sample_count && supported.supports_sample_count(count))
+            .max()
+            .unwrap_or(1)
+    }
+}
@@ -0,0 +1,231 @@
+use crate::prelude::*;
+
+/// Half-line, in world space or relative to a `RenderOrigin`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    /// Normalized
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    /// Create a new ray with a normalized direction
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// Ray from a camera through a point of its target, relative to `origin` like `pick` expects
+    ///
+    /// `position` is normalized with the origin at the top left, like `Viewport`. Starts on the
+    /// near plane, so orthographic cameras work too. `None` if the projection can't be inverted.
+    pub fn from_camera(
+        camera: &Camera,
+        transform: &Transform,
+        origin: &RenderOrigin,
+        aspect: f32,
+        position: [f32; 2],
+    ) -> Option<Self> {
+        // The finite projection, reverse-Z cameras have their far plane at infinity
+        let view_projection = camera.projection.matrix(aspect)
+            * camera.view_matrix_relative(transform, &origin.position);
+        let inverse = view_projection.try_inverse()?;
+        let x = position[0] * 2.0 - 1.0;
+        let y = 1.0 - position[1] * 2.0;
+
+        // wgpu clip space depth runs from 0 at the near plane to 1 at the far plane
+        let near = inverse.transform_point(&Point3::new(x, y, 0.0));
+        let far = inverse.transform_point(&Point3::new(x, y, 1.0));
+        let direction = far - near;
+
+        (direction.norm_squared() > 0.0).then(|| Self::new(near, direction))
+    }
+
+    /// Get a point along the ray at distance t
+    pub fn point_at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction * t
+    }
+
+    /// Distance to where the ray enters the box, zero if it starts inside
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = f32::INFINITY;
+
+        // Slab test, an axis parallel to the ray divides into +-infinity and compares fine
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+
+        (near <= far).then_some(near)
+    }
+
+    /// Distance to the triangle, either face counts
+    pub fn intersect_triangle(
+        &self,
+        a: &Point3<f32>,
+        b: &Point3<f32>,
+        c: &Point3<f32>,
+    ) -> Option<f32> {
+        // Möller-Trumbore, the direction doesn't have to be normalized
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(&edge2);
+        let determinant = edge1.dot(&p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let inverse = 1.0 / determinant;
+        let s = self.origin - a;
+        let u = s.dot(&p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(&edge1);
+        let v = self.direction.dot(&q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(&q) * inverse;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Closest triangle of `mesh` placed at `model`, in world distance along this ray
+    fn intersect_mesh(&self, mesh: &Mesh, bounds: &Aabb, model: &Matrix4<f32>) -> Option<f32> {
+        self.intersect_aabb(&bounds.transformed(model))?;
+
+        // Test in mesh space, the unnormalized direction keeps distances in world units
+        let inverse = model.try_inverse()?;
+        let local = Ray {
+            origin: inverse.transform_point(&self.origin),
+            direction: inverse.transform_vector(&self.direction),
+        };
+
+        let position = |index: u16| Point3::from(mesh.vertices[index as usize].position);
+        mesh.indices
+            .chunks_exact(3)
+            .filter_map(|triangle| {
+                local.intersect_triangle(
+                    &position(triangle[0]),
+                    &position(triangle[1]),
+                    &position(triangle[2]),
+                )
+            })
+            .min_by(f32::total_cmp)
+    }
+}
+
+/// Closest entity a ray hits, see `pick`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickHit {
+    pub entity: Entity,
+    /// Index of the hit instance of an `InstancedLodMesh`, in the order of its visible chunks
+    pub instance: Option<u32>,
+    /// Distance along the ray
+    pub distance: f32,
+    /// World position of the hit
+    pub point: Point3<f64>,
+}
+
+/// Find the closest `Mesh` or `InstancedLodMesh` triangle along a ray relative to the
+/// `RenderOrigin`
+///
+/// Runs on the CPU against the meshes' `Aabb` first and their triangles after, in the same
+/// origin relative space the renderer draws in, so the result matches what was drawn without
+/// reading anything back from the GPU.
+pub fn pick(world: &mut World, ray: &Ray) -> Option<PickHit> {
+    let origin = world
+        .get_resource::<RenderOrigin>()
+        .copied()
+        .unwrap_or_default();
+
+    let mut closest: Option<PickHit> = None;
+    let mut hit = |entity: Entity, instance: Option<u32>, distance: f32| {
+        if closest.is_none_or(|closest| distance < closest.distance) {
+            closest = Some(PickHit {
+                entity,
+                instance,
+                distance,
+                point: origin.position + ray.point_at(distance).coords.cast(),
+            });
+        }
+    };
+
+    let mut mesh_query = world.query::<(Entity, &Mesh, &Transform, &Aabb)>();
+    for (entity, mesh, transform, bounds) in mesh_query.iter(world) {
+        let model = transform.model_matrix_relative(&origin.position);
+        if let Some(distance) = ray.intersect_mesh(mesh, bounds, &model) {
+            hit(entity, None, distance);
+        }
+    }
+
+    let mut instanced_query =
+        world.query::<(Entity, &InstancedLodMesh, Option<&Transform>, &Aabb)>();
+    for (entity, instanced_mesh, transform, bounds) in instanced_query.iter(world) {
+        // Chunks without a `Transform` are placed in the world
+        let model = transform
+            .cloned()
+            .unwrap_or_default()
+            .model_matrix_relative(&origin.position);
+        for (index, chunk) in instanced_mesh.visible_chunks().into_iter().enumerate() {
+            let mesh = &instanced_mesh.base_mesh;
+            if let Some(distance) = ray.intersect_mesh(mesh, bounds, &(model * chunk.transform)) {
+                hit(entity, Some(index as u32), distance);
+            }
+        }
+    }
+
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn center_ray_follows_the_camera() {
+        let camera = Camera::default();
+        let transform = Transform {
+            position: Point3::new(0.0, 0.0, 1.0e7 + 5.0),
+            ..Default::default()
+        };
+        let origin = RenderOrigin {
+            position: Point3::new(0.0, 0.0, 1.0e7),
+            ..Default::default()
+        };
+
+        let ray = Ray::from_camera(&camera, &transform, &origin, 1.5, [0.5, 0.5]).unwrap();
+
+        assert!((ray.direction - -Vector3::z()).norm() < 1e-4);
+        assert!((ray.origin.z - 4.9).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ray_hits_box_and_triangle() {
+        let ray = Ray::new(Point3::new(0.25, 0.25, 5.0), -Vector3::z());
+        let aabb = Aabb {
+            min: Point3::new(-1.0, -1.0, -1.0),
+            max: Point3::new(1.0, 1.0, 1.0),
+        };
+        assert_eq!(ray.intersect_aabb(&aabb), Some(4.0));
+
+        let a = Point3::new(0.0, 0.0, 0.0);
+        let b = Point3::new(1.0, 0.0, 0.0);
+        let c = Point3::new(0.0, 1.0, 0.0);
+        assert_eq!(ray.intersect_triangle(&a, &b, &c), Some(5.0));
+        // Either winding
+        assert_eq!(ray.intersect_triangle(&a, &c, &b), Some(5.0));
+
+        let miss = Ray::new(Point3::new(2.0, 2.0, 5.0), -Vector3::z());
+        assert_eq!(miss.intersect_aabb(&aabb), None);
+        assert_eq!(miss.intersect_triangle(&a, &b, &c), None);
+    }
+}
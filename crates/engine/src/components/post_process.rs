@@ -0,0 +1,273 @@
+use crate::prelude::*;
+
+use nalgebra::{Vector2, Vector4};
+use std::sync::Arc;
+
+/// Point in a camera's frame where a post effect runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PostProcessStage {
+    /// On the linear HDR image, before tonemapping
+    Hdr,
+    /// On the tonemapped display image
+    #[default]
+    Ldr,
+}
+
+/// Bright pass blurred over a mip chain and added back onto the HDR image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    /// Luminance above which pixels start to bloom
+    pub threshold: f32,
+    /// Width of the soft transition around the threshold, relative to it
+    pub knee: f32,
+    /// Scale of the blurred light added back onto the image
+    pub intensity: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.15,
+        }
+    }
+}
+
+/// Fast approximate anti-aliasing of luminance edges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fxaa {
+    /// Local contrast, relative to the brightest neighbour, needed to treat a pixel as an edge
+    pub edge_threshold: f32,
+    /// Absolute contrast below which dark pixels are never treated as edges
+    pub edge_threshold_min: f32,
+    /// Amount of sub-pixel aliasing removed, softens the image at 1.0
+    pub subpixel_quality: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Self {
+            edge_threshold: 0.125,
+            edge_threshold_min: 0.0312,
+            subpixel_quality: 0.75,
+        }
+    }
+}
+
+/// Darkens the corners of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    /// Darkening at the corners, 1.0 turns them black
+    pub intensity: f32,
+    /// Distance from the centre where darkening starts, 1.0 being the corners
+    pub radius: f32,
+    /// Width of the falloff past `radius`
+    pub smoothness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.35,
+            radius: 0.5,
+            smoothness: 0.5,
+        }
+    }
+}
+
+/// 3D colour lookup table
+///
+/// Loaded from an image of `size` square slices laid side by side, `size * size` texels wide
+/// and `size` texels high. Red increases along each slice, green down it and blue from one
+/// slice to the next. Inputs and outputs are sRGB encoded, as exported by grading tools.
+#[derive(Clone)]
+pub struct ColorLut {
+    pub bytes: Arc<Vec<u8>>,
+}
+
+impl ColorLut {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes: Arc::new(bytes),
+        }
+    }
+}
+
+// Compared by identity, tables are uploaded once per shared image and inspecting a stack
+// doesn't compare whole images every frame
+impl PartialEq for ColorLut {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.bytes, &other.bytes)
+    }
+}
+
+impl std::fmt::Debug for ColorLut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ColorLut")
+            .field("bytes", &self.bytes.len())
+            .finish()
+    }
+}
+
+/// Colour grading through a lookup table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorGrading {
+    pub lut: ColorLut,
+    /// Blend between the original colour at 0.0 and the graded colour at 1.0
+    pub strength: f32,
+}
+
+/// Splits the colour channels apart towards the edges of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChromaticAberration {
+    /// Offset of the red and blue channels at the corners, as a fraction of the image size
+    pub intensity: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self { intensity: 0.004 }
+    }
+}
+
+/// Animated noise over the image, strongest in the midtones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilmGrain {
+    pub intensity: f32,
+}
+
+impl Default for FilmGrain {
+    fn default() -> Self {
+        Self { intensity: 0.04 }
+    }
+}
+
+/// User written fullscreen effect
+///
+/// `shader` must be a `Shader::PostEffect` registered with the application, hot reloaded like
+/// any other shader. It declares `vertex` and `fragment` entry points and binds
+///
+/// ```wgsl
+/// @group(0) @binding(0) var source_texture: texture_2d<f32>;
+/// @group(0) @binding(1) var source_sampler: sampler;
+/// @group(0) @binding(2) var<uniform> params: PostEffectParams;
+/// ```
+///
+/// with `PostEffectParams` laid out like the Rust struct of the same name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomPostEffect {
+    pub shader: Shader,
+    pub stage: PostProcessStage,
+    /// Passed through as `PostEffectParams::values`
+    pub values: Vector4<f32>,
+}
+
+/// A single fullscreen effect of a `PostProcessStack`
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostEffect {
+    Bloom(Bloom),
+    Fxaa(Fxaa),
+    Vignette(Vignette),
+    ColorGrading(ColorGrading),
+    ChromaticAberration(ChromaticAberration),
+    FilmGrain(FilmGrain),
+    Custom(CustomPostEffect),
+}
+
+impl PostEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostEffect::Bloom(_) => "Bloom",
+            PostEffect::Fxaa(_) => "FXAA",
+            PostEffect::Vignette(_) => "Vignette",
+            PostEffect::ColorGrading(_) => "Color Grading",
+            PostEffect::ChromaticAberration(_) => "Chromatic Aberration",
+            PostEffect::FilmGrain(_) => "Film Grain",
+            PostEffect::Custom(_) => "Custom",
+        }
+    }
+
+    /// Bloom needs the unclamped HDR image, the other built-in effects work on display colour
+    pub fn stage(&self) -> PostProcessStage {
+        match self {
+            PostEffect::Bloom(_) => PostProcessStage::Hdr,
+            PostEffect::Custom(custom) => custom.stage,
+            _ => PostProcessStage::Ldr,
+        }
+    }
+
+    /// Effect specific settings packed into `PostEffectParams::values`
+    pub fn values(&self) -> Vector4<f32> {
+        match self {
+            PostEffect::Bloom(bloom) => {
+                Vector4::new(bloom.threshold, bloom.knee, bloom.intensity, 0.0)
+            }
+            PostEffect::Fxaa(fxaa) => Vector4::new(
+                fxaa.edge_threshold,
+                fxaa.edge_threshold_min,
+                fxaa.subpixel_quality,
+                0.0,
+            ),
+            PostEffect::Vignette(vignette) => Vector4::new(
+                vignette.intensity,
+                vignette.radius,
+                vignette.smoothness,
+                0.0,
+            ),
+            PostEffect::ColorGrading(grading) => Vector4::new(grading.strength, 0.0, 0.0, 0.0),
+            PostEffect::ChromaticAberration(aberration) => {
+                Vector4::new(aberration.intensity, 0.0, 0.0, 0.0)
+            }
+            PostEffect::FilmGrain(grain) => Vector4::new(grain.intensity, 0.0, 0.0, 0.0),
+            PostEffect::Custom(custom) => custom.values,
+        }
+    }
+}
+
+/// Ordered fullscreen effects applied to a camera's render
+///
+/// Effects run in list order within their stage, HDR effects before tonemapping and the
+/// rest after it, each reading the output of the previous one.
+#[derive(Component, Debug, Clone, PartialEq, Default)]
+pub struct PostProcessStack {
+    pub effects: Vec<PostEffect>,
+}
+
+impl PostProcessStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, effect: PostEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Effects that run at `stage`, in order
+    pub fn stage(&self, stage: PostProcessStage) -> impl Iterator<Item = &PostEffect> {
+        self.effects
+            .iter()
+            .filter(move |effect| effect.stage() == stage)
+    }
+}
+
+/// Per-pass uniform of post effects, as laid out in the `PostEffectParams` struct of their shaders
+#[derive(ShaderType, Clone, Copy)]
+pub struct PostEffectParams {
+    /// Size of one texel of the image being written
+    pub texel_size: Vector2<f32>,
+    /// Seconds since the first frame
+    pub time: f32,
+    pub frame: u32,
+    pub values: Vector4<f32>,
+}
+
+// Keep the params layout in sync with the `PostEffectParams` struct of the post effect shaders
+const _: () = {
+    use crate::shader::layouts::{bloom, post_process};
+    use encase::ShaderSize;
+
+    assert!(PostEffectParams::SHADER_SIZE.get() == post_process::post_effect_params::SIZE as u64);
+    assert!(PostEffectParams::SHADER_SIZE.get() == bloom::post_effect_params::SIZE as u64);
+};
@@ -0,0 +1,64 @@
+use crate::prelude::*;
+
+/// How the window layer fits camera render targets into their viewports
+///
+/// Targets sized by their viewport are shown as is, fixed resolution targets are scaled.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Default)]
+pub struct PresentSettings {
+    pub filter: PresentFilter,
+    pub scaling: PresentScaling,
+}
+
+/// Sampling of a target shown at a different size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PresentFilter {
+    /// Hard pixel edges, for pixel art and debugging low resolutions
+    Nearest,
+    #[default]
+    Linear,
+}
+
+impl PresentFilter {
+    pub const ALL: [PresentFilter; 2] = [PresentFilter::Nearest, PresentFilter::Linear];
+
+    pub fn filter_mode(&self) -> wgpu::FilterMode {
+        match self {
+            PresentFilter::Nearest => wgpu::FilterMode::Nearest,
+            PresentFilter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+/// How a target whose aspect ratio differs from its viewport's is fitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentScaling {
+    /// Fill the viewport, distorting the image
+    Stretch,
+    /// Keep the aspect ratio, with black bars above and below or on the sides
+    #[default]
+    Fit,
+}
+
+impl PresentScaling {
+    pub const ALL: [PresentScaling; 2] = [PresentScaling::Stretch, PresentScaling::Fit];
+
+    /// Pixel rectangle `x, y, width, height` a target of `source` size is drawn to in `viewport`
+    pub fn fit(&self, source: (u32, u32), viewport: (u32, u32, u32, u32)) -> [f32; 4] {
+        let [x, y, width, height] =
+            [viewport.0, viewport.1, viewport.2, viewport.3].map(|v| v as f32);
+        match self {
+            PresentScaling::Stretch => [x, y, width, height],
+            PresentScaling::Fit => {
+                let scale = (width / source.0 as f32).min(height / source.1 as f32);
+                let (fitted_width, fitted_height) =
+                    (source.0 as f32 * scale, source.1 as f32 * scale);
+                [
+                    x + (width - fitted_width) * 0.5,
+                    y + (height - fitted_height) * 0.5,
+                    fitted_width,
+                    fitted_height,
+                ]
+            }
+        }
+    }
+}
@@ -0,0 +1,625 @@
+use crate::prelude::*;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What a profiled scope measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProfileTrack {
+    /// CPU time of a layer's frame
+    Layer,
+    /// CPU time of one ECS system run
+    System,
+    /// GPU time of a render or compute pass
+    Gpu,
+}
+
+impl ProfileTrack {
+    pub const ALL: [ProfileTrack; 3] =
+        [ProfileTrack::Layer, ProfileTrack::System, ProfileTrack::Gpu];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProfileTrack::Layer => "Layer",
+            ProfileTrack::System => "System",
+            ProfileTrack::Gpu => "GPU",
+        }
+    }
+}
+
+/// One timed span of a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileScope {
+    pub name: String,
+    pub track: ProfileTrack,
+    /// Index of the thread the scope ran on, 0 for the GPU
+    pub thread: u64,
+    /// Offset from the start of the frame
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+impl ProfileScope {
+    /// Name without module paths, `gpu_update_system<Mesh>` for systems
+    pub fn short_name(&self) -> String {
+        let mut short = String::with_capacity(self.name.len());
+        let mut chars = self.name.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == ':' && chars.peek() == Some(&':') {
+                chars.next();
+                while short.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                    short.pop();
+                }
+            } else {
+                short.push(c);
+            }
+        }
+        short
+    }
+}
+
+/// Timings of one profiled frame
+#[derive(Debug, Clone, Default)]
+pub struct FrameProfile {
+    pub frame: u64,
+    /// Offset from the creation of the profiler
+    pub start: Duration,
+    /// CPU time from the first to the end of the last layer
+    pub duration: Duration,
+    pub scopes: Vec<ProfileScope>,
+}
+
+impl FrameProfile {
+    pub fn track(&self, track: ProfileTrack) -> impl Iterator<Item = &ProfileScope> {
+        self.scopes.iter().filter(move |scope| scope.track == track)
+    }
+}
+
+/// CPU time per layer and ECS system, and GPU time per pass when the adapter supports
+/// timestamp queries, see `GpuProfiler`
+///
+/// Nothing is recorded until `enabled` is set. GPU timings are read back asynchronously and
+/// join their frame a few frames later.
+#[derive(Resource)]
+pub struct Profiler {
+    pub enabled: bool,
+    epoch: Instant,
+    frame: u64,
+    frame_start: Instant,
+    layers: Vec<ProfileScope>,
+    /// Most recent profiled frames, oldest first
+    frames: VecDeque<FrameProfile>,
+    systems: Arc<SystemRecorder>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    /// Profiled frames kept for `frames` and `chrome_trace`
+    pub const HISTORY: usize = 300;
+
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            enabled: false,
+            epoch: now,
+            frame: 0,
+            frame_start: now,
+            layers: Vec::new(),
+            frames: VecDeque::new(),
+            systems: Arc::new(SystemRecorder::default()),
+        }
+    }
+
+    /// `tracing` subscriber timing ECS systems into this profiler
+    ///
+    /// Systems only emit spans with the `profiling` feature, which makes
+    /// `ApplicationBuilder::build` install this as the global subscriber.
+    pub fn system_subscriber(&self) -> impl tracing::Subscriber + Send + Sync + 'static {
+        SystemSpans(self.systems.clone())
+    }
+
+    /// Index of the current frame, counted whether profiled or not
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = &FrameProfile> {
+        self.frames.iter()
+    }
+
+    /// Newest profiled frame with scopes of `track`
+    pub fn latest(&self, track: ProfileTrack) -> Option<&FrameProfile> {
+        self.frames
+            .iter()
+            .rev()
+            .find(|frame| frame.scopes.iter().any(|scope| scope.track == track))
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Start timing a frame, returns whether it is profiled
+    pub fn begin_frame(&mut self) -> bool {
+        self.frame_start = Instant::now();
+        self.layers.clear();
+        self.systems
+            .recording
+            .store(self.enabled, Ordering::Relaxed);
+        self.enabled
+    }
+
+    pub fn record_layer(&mut self, name: &str, start: Instant, duration: Duration) {
+        self.layers.push(ProfileScope {
+            name: name.to_string(),
+            track: ProfileTrack::Layer,
+            thread: thread_index(),
+            start: start.saturating_duration_since(self.frame_start),
+            duration,
+        });
+    }
+
+    /// Finish the frame, collecting the systems that ran during it
+    pub fn end_frame(&mut self) {
+        let systems = self.systems.take();
+        let frame = self.frame;
+        self.frame += 1;
+        if !self.enabled {
+            return;
+        }
+
+        let mut scopes = std::mem::take(&mut self.layers);
+        scopes.extend(systems.into_iter().map(|span| ProfileScope {
+            name: span.name,
+            track: ProfileTrack::System,
+            thread: span.thread,
+            start: span.start.saturating_duration_since(self.frame_start),
+            duration: span.end.saturating_duration_since(span.start),
+        }));
+
+        self.frames.push_back(FrameProfile {
+            frame,
+            start: self.frame_start.duration_since(self.epoch),
+            duration: self.frame_start.elapsed(),
+            scopes,
+        });
+        while self.frames.len() > Self::HISTORY {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Attach GPU timings read back for an earlier frame
+    pub fn add_gpu_scopes(&mut self, frame: u64, scopes: Vec<ProfileScope>) {
+        if let Some(profile) = self
+            .frames
+            .iter_mut()
+            .find(|profile| profile.frame == frame)
+        {
+            profile.scopes.extend(scopes);
+        }
+    }
+
+    /// The kept frames in the Chrome trace event format, for `chrome://tracing` or Perfetto
+    ///
+    /// GPU passes are on their own track, starting with the CPU frame they were recorded in.
+    pub fn chrome_trace(&self) -> String {
+        let mut threads: Vec<u64> = self
+            .frames
+            .iter()
+            .flat_map(|frame| &frame.scopes)
+            .map(|scope| scope.thread)
+            .filter(|&thread| thread != 0)
+            .collect();
+        threads.sort_unstable();
+        threads.dedup();
+
+        let mut events = vec![
+            r#"{"name":"thread_name","ph":"M","pid":0,"tid":0,"args":{"name":"GPU"}}"#.to_string(),
+        ];
+        events.extend(threads.iter().map(|thread| {
+            format!(
+                r#"{{"name":"thread_name","ph":"M","pid":0,"tid":{thread},"args":{{"name":"Thread {thread}"}}}}"#
+            )
+        }));
+
+        for frame in &self.frames {
+            for scope in &frame.scopes {
+                events.push(format!(
+                    r#"{{"name":"{}","cat":"{}","ph":"X","ts":{:.3},"dur":{:.3},"pid":0,"tid":{},"args":{{"frame":{}}}}}"#,
+                    escape_json(&scope.name),
+                    scope.track.label(),
+                    (frame.start + scope.start).as_secs_f64() * 1e6,
+                    scope.duration.as_secs_f64() * 1e6,
+                    scope.thread,
+                    frame.frame,
+                ));
+            }
+        }
+
+        format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+    }
+
+    pub fn write_chrome_trace(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.chrome_trace())
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Small stable index of the current thread, starting at 1
+fn thread_index() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static INDEX: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    INDEX.with(|index| *index)
+}
+
+/// Timings of the `system` spans bevy_ecs enters around every system run
+#[derive(Default)]
+struct SystemRecorder {
+    recording: AtomicBool,
+    /// System names by span id - 1
+    names: Mutex<Vec<String>>,
+    /// Entered spans by span id and thread
+    open: Mutex<HashMap<(u64, u64), Instant>>,
+    finished: Mutex<Vec<SystemSpan>>,
+}
+
+struct SystemSpan {
+    name: String,
+    thread: u64,
+    start: Instant,
+    end: Instant,
+}
+
+impl SystemRecorder {
+    fn take(&self) -> Vec<SystemSpan> {
+        std::mem::take(&mut *self.finished.lock().unwrap())
+    }
+}
+
+/// `tracing` subscriber feeding a `SystemRecorder`, every other span and event is disabled
+struct SystemSpans(Arc<SystemRecorder>);
+
+impl tracing::Subscriber for SystemSpans {
+    fn register_callsite(
+        &self,
+        metadata: &'static tracing::Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        if self.enabled(metadata) {
+            tracing::subscriber::Interest::always()
+        } else {
+            tracing::subscriber::Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        metadata.is_span() && metadata.name() == "system"
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut name = SystemName(String::new());
+        span.record(&mut name);
+
+        let mut names = self.0.names.lock().unwrap();
+        names.push(name.0);
+        tracing::span::Id::from_u64(names.len() as u64)
+    }
+
+    fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, _event: &tracing::Event<'_>) {}
+
+    fn enter(&self, span: &tracing::span::Id) {
+        if self.0.recording.load(Ordering::Relaxed) {
+            let key = (span.into_u64(), thread_index());
+            self.0.open.lock().unwrap().insert(key, Instant::now());
+        }
+    }
+
+    fn exit(&self, span: &tracing::span::Id) {
+        let end = Instant::now();
+        let thread = thread_index();
+        let Some(start) = self
+            .0
+            .open
+            .lock()
+            .unwrap()
+            .remove(&(span.into_u64(), thread))
+        else {
+            return;
+        };
+
+        let name = self.0.names.lock().unwrap()[span.into_u64() as usize - 1].clone();
+        self.0.finished.lock().unwrap().push(SystemSpan {
+            name,
+            thread,
+            start,
+            end,
+        });
+    }
+}
+
+/// Reads the `name` field of a system span
+struct SystemName(String);
+
+impl tracing::field::Visit for SystemName {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = value.to_string();
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "name" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+/// Timestamp queries around render and compute passes, present when the adapter supports
+/// `TIMESTAMP_QUERY`
+///
+/// Passes opt in through `begin_pass`, which only hands out queries while the `Profiler` is
+/// enabled:
+///
+/// ```ignore
+/// let timestamps = world
+///     .get_resource::<GpuProfiler>()
+///     .and_then(|profiler| profiler.begin_pass("Main Pass"));
+/// encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+///     timestamp_writes: timestamps.as_ref().map(GpuPassTimestamps::render_pass_writes),
+///     ..
+/// });
+/// ```
+#[derive(Resource)]
+pub struct GpuProfiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    recording: AtomicBool,
+    /// Names of the passes timed this frame, pass `i` writes queries `2i` and `2i + 1`
+    passes: Mutex<Vec<String>>,
+    readbacks: Vec<TimestampReadback>,
+    results: Arc<Mutex<Vec<GpuFrameScopes>>>,
+}
+
+/// Frame index and pass timings read back for it
+pub type GpuFrameScopes = (u64, Vec<ProfileScope>);
+
+/// Readback of one frame's timestamps, reused once the results are read
+struct TimestampReadback {
+    buffer: wgpu::Buffer,
+    pending: Arc<AtomicBool>,
+}
+
+/// Query pair of one timed pass
+pub struct GpuPassTimestamps {
+    query_set: wgpu::QuerySet,
+    index: u32,
+}
+
+impl GpuPassTimestamps {
+    pub fn render_pass_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(self.index * 2),
+            end_of_pass_write_index: Some(self.index * 2 + 1),
+        }
+    }
+
+    pub fn compute_pass_writes(&self) -> wgpu::ComputePassTimestampWrites<'_> {
+        wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(self.index * 2),
+            end_of_pass_write_index: Some(self.index * 2 + 1),
+        }
+    }
+}
+
+impl GpuProfiler {
+    /// Passes timed per frame, later passes go untimed
+    pub const MAX_PASSES: u32 = 256;
+    /// Frames whose timestamps can be in flight at once, frames beyond are dropped
+    const READBACKS: usize = 3;
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = Self::MAX_PASSES as u64 * 2 * std::mem::size_of::<u64>() as u64;
+        let readbacks = (0..Self::READBACKS)
+            .map(|_| TimestampReadback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Readback Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                pending: Arc::new(AtomicBool::new(false)),
+            })
+            .collect();
+
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Pass Timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: Self::MAX_PASSES * 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Resolve Buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            recording: AtomicBool::new(false),
+            passes: Mutex::new(Vec::new()),
+            readbacks,
+            results: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn begin_frame(&self, recording: bool) {
+        self.recording.store(recording, Ordering::Relaxed);
+        self.passes.lock().unwrap().clear();
+    }
+
+    /// Queries for a pass named `name`, none while not profiling or when the frame is full
+    pub fn begin_pass(&self, name: &str) -> Option<GpuPassTimestamps> {
+        if !self.recording.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let mut passes = self.passes.lock().unwrap();
+        let index = passes.len() as u32;
+        if index >= Self::MAX_PASSES {
+            return None;
+        }
+        passes.push(name.to_string());
+        Some(GpuPassTimestamps {
+            query_set: self.query_set.clone(),
+            index,
+        })
+    }
+
+    /// Resolve the queries of the passes submitted this frame and read them back for `frame`
+    pub fn end_frame(&self, device: &wgpu::Device, queue: &wgpu::Queue, frame: u64) {
+        let passes = std::mem::take(&mut *self.passes.lock().unwrap());
+        if passes.is_empty() {
+            return;
+        }
+        let Some(readback) = self
+            .readbacks
+            .iter()
+            .find(|readback| !readback.pending.swap(true, Ordering::AcqRel))
+        else {
+            return;
+        };
+
+        let query_count = passes.len() as u32 * 2;
+        let size = query_count as u64 * std::mem::size_of::<u64>() as u64;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Timestamp Resolve Encoder"),
+        });
+        encoder.resolve_query_set(&self.query_set, 0..query_count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &readback.buffer, 0, size);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer = readback.buffer.clone();
+        let pending = readback.pending.clone();
+        let results = self.results.clone();
+        let period = self.period;
+        readback
+            .buffer
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    let timestamps: Vec<u64> =
+                        bytemuck::cast_slice(&buffer.slice(..size).get_mapped_range()).to_vec();
+                    buffer.unmap();
+                    let scopes = gpu_scopes(&passes, &timestamps, period);
+                    results.lock().unwrap().push((frame, scopes));
+                }
+                pending.store(false, Ordering::Release);
+            });
+    }
+
+    /// GPU timings read back since the last call, by frame
+    pub fn take_results(&self) -> Vec<GpuFrameScopes> {
+        std::mem::take(&mut *self.results.lock().unwrap())
+    }
+}
+
+/// Scopes of the passes of one frame, relative to the first timestamp written
+fn gpu_scopes(passes: &[String], timestamps: &[u64], period: f32) -> Vec<ProfileScope> {
+    let to_duration = |ticks: u64| Duration::from_nanos((ticks as f64 * period as f64) as u64);
+    let first = timestamps.iter().step_by(2).copied().min().unwrap_or(0);
+
+    passes
+        .iter()
+        .zip(timestamps.chunks_exact(2))
+        .map(|(name, pair)| ProfileScope {
+            name: name.clone(),
+            track: ProfileTrack::Gpu,
+            thread: 0,
+            start: to_duration(pair[0].saturating_sub(first)),
+            duration: to_duration(pair[1].saturating_sub(pair[0])),
+        })
+        .collect()
+}
+
+/// Finish profiling the frame of every layer, called by the application after the last layer
+pub(crate) fn end_profiled_frame(world: &mut World) {
+    let Some(frame) = world.get_resource::<Profiler>().map(Profiler::frame) else {
+        return;
+    };
+
+    let mut gpu_results = Vec::new();
+    if let (Some(gpu_profiler), Some(device), Some(queue)) = (
+        world.get_resource::<GpuProfiler>(),
+        world.get_resource::<GpuDevice>(),
+        world.get_resource::<GpuQueue>(),
+    ) {
+        gpu_profiler.end_frame(&device.0, &queue.0, frame);
+        gpu_results = gpu_profiler.take_results();
+    }
+
+    let mut profiler = world.resource_mut::<Profiler>();
+    profiler.end_frame();
+    for (frame, scopes) in gpu_results {
+        profiler.add_gpu_scopes(frame, scopes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpu_scopes_start_at_the_first_pass() {
+        let passes = ["Shadow Pass".to_string(), "Render Pass".to_string()];
+        let scopes = gpu_scopes(&passes, &[100, 150, 200, 400], 2.0);
+
+        assert_eq!(scopes[0].start, Duration::ZERO);
+        assert_eq!(scopes[0].duration, Duration::from_nanos(100));
+        assert_eq!(scopes[1].start, Duration::from_nanos(200));
+        assert_eq!(scopes[1].duration, Duration::from_nanos(400));
+    }
+
+    #[test]
+    fn short_names_drop_module_paths() {
+        let scope = ProfileScope {
+            name: "engine::systems::gpu_update_system<engine::components::Mesh>".to_string(),
+            track: ProfileTrack::System,
+            thread: 1,
+            start: Duration::ZERO,
+            duration: Duration::ZERO,
+        };
+        assert_eq!(scope.short_name(), "gpu_update_system<Mesh>");
+    }
+
+    #[test]
+    fn chrome_trace_escapes_names() {
+        assert_eq!(escape_json(r#"a "b" \c"#), r#"a \"b\" \\c"#);
+    }
+}
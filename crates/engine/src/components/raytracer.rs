@@ -9,14 +9,6 @@ pub struct Sphere {
     pub material_type: u32, // 0 = lambertian, 1 = metal, 2 = dielectric
 }
 
-/// User-facing component for spawning lights in the raytracer scene
-/// Position is taken from the Transform component
-#[derive(Component, Clone, Copy, PartialEq)]
-pub struct Light {
-    pub intensity: f32,
-    pub color: [f32; 3],
-}
-
 /// GPU-side component that holds the buffer data for the entire raytracer scene
 /// This is attached to a single entity that manages the scene
 #[derive(Component)]
@@ -34,6 +26,47 @@ pub struct EnvironmentMap {
     pub bytes: Vec<u8>,
 }
 
+impl EnvironmentMap {
+    /// Decode the image into tightly packed RGBA f32 texels, returns its width and height too
+    ///
+    /// Radiance HDR files keep their values, other formats are normalized to 0..1.
+    pub fn decode(&self) -> image::ImageResult<(u32, u32, Vec<u8>)> {
+        use image::ImageDecoder;
+
+        // Try to load as HDR first, fall back to regular image
+        if let Ok(decoder) = image::codecs::hdr::HdrDecoder::new(std::io::Cursor::new(&self.bytes))
+        {
+            let (width, height) = decoder.dimensions();
+
+            // Read raw HDR data as bytes (RGB f32)
+            let mut raw_data = vec![0u8; decoder.total_bytes() as usize];
+            decoder.read_image(&mut raw_data)?;
+
+            // Convert RGB f32 bytes to RGBA f32 bytes
+            let mut rgba_data = Vec::with_capacity(raw_data.len() / 3 * 4);
+            for rgb in raw_data.chunks_exact(12) {
+                rgba_data.extend_from_slice(rgb);
+                rgba_data.extend_from_slice(&1.0f32.to_ne_bytes());
+            }
+
+            return Ok((width, height, rgba_data));
+        }
+
+        let image = image::load_from_memory(&self.bytes)?.to_rgba32f();
+        let (width, height) = image.dimensions();
+        Ok((width, height, bytemuck::cast_slice(image.as_raw()).to_vec()))
+    }
+
+    /// Hash of the source bytes, to tell actual changes apart from mutable access
+    pub fn bytes_hash(&self) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        self.bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
 /// GPU-side component for environment map texture
 #[derive(Component)]
 pub struct GpuEnvironmentMap {
@@ -23,6 +23,9 @@ pub struct ShadowBindGroupLayout(pub wgpu::BindGroupLayout);
 #[derive(Resource)]
 pub struct ShadowUniformLayout(pub wgpu::BindGroupLayout);
 
+#[derive(Resource)]
+pub struct InstanceCullLayout(pub wgpu::BindGroupLayout);
+
 #[derive(Resource)]
 pub struct Time(pub Duration);
 
@@ -42,15 +45,9 @@ pub struct GpuAdapter(pub Option<wgpu::Adapter>);
 #[derive(Resource)]
 pub struct RaytracerComputePipeline(pub wgpu::ComputePipeline);
 
-#[derive(Resource)]
-pub struct RaytracerDisplayPipeline(pub wgpu::RenderPipeline);
-
 #[derive(Resource)]
 pub struct RaytracerBindGroupLayout(pub wgpu::BindGroupLayout);
 
-#[derive(Resource)]
-pub struct RaytracerDisplayBindGroupLayout(pub wgpu::BindGroupLayout);
-
 #[derive(Resource)]
 pub struct RaytracerOutputTexture {
     pub texture: wgpu::Texture,
@@ -60,103 +57,40 @@ pub struct RaytracerOutputTexture {
 #[derive(Resource)]
 pub struct RaytracerBindGroup(pub wgpu::BindGroup);
 
-#[derive(Resource)]
-pub struct RaytracerDisplayBindGroup(pub wgpu::BindGroup);
-
 // Raytracer scene data
 #[derive(ShaderType)]
 pub struct RaytracerCamera {
-    pub position: Vector3<f32>,
-    pub look_at: Vector3<f32>,
+    /// Clip to world space, primary rays run from the near to the far plane of each pixel
+    pub inverse_view_projection: Matrix4<f32>,
+    /// World space right and up of the camera, spanning the lens for depth of field
+    pub right: Vector3<f32>,
     pub up: Vector3<f32>,
-    pub fov: f32,
-    pub aspect_ratio: f32,
     pub aperture: f32,
     pub focus_distance: f32,
-    // Precomputed basis vectors
-    pub u: Vector3<f32>,
-    pub v: Vector3<f32>,
-    pub w: Vector3<f32>,
-    pub lower_left_corner: Vector3<f32>,
-    pub horizontal: Vector3<f32>,
-    pub vertical: Vector3<f32>,
 }
 
 impl RaytracerCamera {
-    pub fn new(
-        position: Vector3<f32>,
-        look_at: Vector3<f32>,
-        up: Vector3<f32>,
-        fov: f32,
-        aspect_ratio: f32,
-        aperture: f32,
-        focus_distance: f32,
-    ) -> Self {
-        // Compute camera basis vectors
-        let w = (position - look_at).normalize();
-        let u = up.cross(&w).normalize();
-        let v = w.cross(&u);
-
-        // Compute viewport dimensions
-        let theta = fov.to_radians();
-        let half_height = (theta / 2.0).tan();
-        let half_width = aspect_ratio * half_height;
-
-        // Compute viewport corners and spans
-        let lower_left_corner = position - half_width * u - half_height * v - w;
-        let horizontal = 2.0 * half_width * u;
-        let vertical = 2.0 * half_height * v;
+    /// Trace through the same projection the raster renderer uses for `camera`
+    pub fn new(camera: &Camera, transform: &Transform, aspect_ratio: f32) -> Self {
+        let camera_to_world = camera
+            .view_matrix(transform)
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+
+        // Rays need a finite far plane, whatever depth mode the raster renderer uses
+        let view_projection =
+            camera.projection.matrix(aspect_ratio) * camera.view_matrix(transform);
 
         Self {
-            position,
-            look_at,
-            up,
-            fov,
-            aspect_ratio,
-            aperture,
-            focus_distance,
-            u,
-            v,
-            w,
-            lower_left_corner,
-            horizontal,
-            vertical,
+            inverse_view_projection: view_projection
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity),
+            right: camera_to_world.column(0).xyz(),
+            up: camera_to_world.column(1).xyz(),
+            aperture: camera.aperture,
+            focus_distance: camera.focus_distance,
         }
     }
-
-    /// Update the camera and recompute basis vectors
-    pub fn update(
-        &mut self,
-        position: Vector3<f32>,
-        look_at: Vector3<f32>,
-        up: Vector3<f32>,
-        fov: f32,
-        aspect_ratio: f32,
-        aperture: f32,
-        focus_distance: f32,
-    ) {
-        self.position = position;
-        self.look_at = look_at;
-        self.up = up;
-        self.fov = fov;
-        self.aspect_ratio = aspect_ratio;
-        self.aperture = aperture;
-        self.focus_distance = focus_distance;
-
-        // Recompute basis vectors
-        self.w = (position - look_at).normalize();
-        self.u = up.cross(&self.w).normalize();
-        self.v = self.w.cross(&self.u);
-
-        // Recompute viewport
-        let theta = fov.to_radians();
-        let half_height = (theta / 2.0).tan();
-        let half_width = aspect_ratio * half_height;
-
-        self.lower_left_corner = position - half_width * self.u - half_height * self.v - self.w;
-        self.horizontal = 2.0 * half_width * self.u;
-        self.vertical = 2.0 * half_height * self.v;
-    }
 }
 
 #[derive(ShaderType)]
@@ -167,6 +101,15 @@ pub struct RaytracerSphere {
     pub material_type: u32,
 }
 
+// Keep the raytracer scene types in sync with the structs in raytracer.wgsl
+const _: () = {
+    use crate::shader::layouts::raytracer;
+    use encase::ShaderSize;
+
+    assert!(RaytracerCamera::SHADER_SIZE.get() == raytracer::camera::SIZE as u64);
+    assert!(RaytracerSphere::SHADER_SIZE.get() == raytracer::sphere::SIZE as u64);
+};
+
 #[derive(ShaderType)]
 pub struct RaytracerLight {
     pub position: Vector3<f32>,
@@ -190,8 +133,28 @@ pub struct RaytracerEnvironmentMap {
     pub sampler: wgpu::Sampler,
 }
 
-#[derive(Resource, Default)]
+#[derive(Resource, Default, Clone, Copy)]
 pub struct SupportedFeatures {
     pub polygon_mode_line: bool,
     pub polygon_mode_point: bool,
+    /// MSAA sample counts usable for the camera targets, each count set as the bit of the same value
+    pub msaa_sample_counts: u32,
+    /// Passes can write timestamps, see `GpuProfiler`
+    pub timestamp_query: bool,
+}
+
+impl SupportedFeatures {
+    /// Whether colour and depth targets can be created with `count` samples per pixel
+    pub fn supports_sample_count(&self, count: u32) -> bool {
+        count == 1 || (count.is_power_of_two() && self.msaa_sample_counts & count != 0)
+    }
+
+    /// Whether pipelines can rasterize with `polygon_mode`
+    pub fn supports_polygon_mode(&self, polygon_mode: wgpu::PolygonMode) -> bool {
+        match polygon_mode {
+            wgpu::PolygonMode::Fill => true,
+            wgpu::PolygonMode::Line => self.polygon_mode_line,
+            wgpu::PolygonMode::Point => self.polygon_mode_point,
+        }
+    }
 }
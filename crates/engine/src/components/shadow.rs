@@ -0,0 +1,281 @@
+use crate::prelude::*;
+
+/// Maximum number of cascades the raster shaders can sample
+pub const MAX_SHADOW_CASCADES: usize = 4;
+
+/// Configuration of the cascaded shadow maps rendered for every camera
+///
+/// Changing these recreates the shadow maps of all cameras.
+#[derive(Resource, Clone, PartialEq)]
+pub struct ShadowSettings {
+    /// Width and height of each cascade in texels
+    pub map_size: u32,
+    /// Number of cascades the view frustum is split into, at most `MAX_SHADOW_CASCADES`
+    pub cascade_count: u32,
+    /// Distance from the camera at which shadows end
+    pub max_distance: f32,
+    /// Blend between uniform (0.0) and logarithmic (1.0) cascade splits
+    pub split_lambda: f32,
+    /// Fraction of each cascade over which it fades into the next one
+    pub blend_fraction: f32,
+    /// Extra distance towards the light that is still rendered into the shadow map,
+    /// so casters outside the view frustum keep shadowing it
+    pub caster_distance: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            map_size: 2048,
+            cascade_count: 4,
+            max_distance: 2000.0,
+            split_lambda: 0.75,
+            blend_fraction: 0.1,
+            caster_distance: 1000.0,
+        }
+    }
+}
+
+impl ShadowSettings {
+    pub fn cascade_count(&self) -> usize {
+        (self.cascade_count as usize).clamp(1, MAX_SHADOW_CASCADES)
+    }
+}
+
+/// Excludes an entity from the shadow pass, so it casts no shadow
+#[derive(Component, Clone, Copy, Default)]
+pub struct NotShadowCaster;
+
+/// Draws an entity fully lit by shadow casting lights, ignoring the shadow map
+#[derive(Component, Clone, Copy, Default)]
+pub struct NotShadowReceiver;
+
+/// Per-camera cascade data as laid out in the `ShadowCascades` struct of the raster shaders
+#[derive(ShaderType, Clone, Copy)]
+pub struct ShadowCascades {
+    pub light_space_matrices: [Matrix4<f32>; MAX_SHADOW_CASCADES],
+    /// View space distance at which each cascade ends
+    pub split_depths: nalgebra::Vector4<f32>,
+    /// World space size of one shadow map texel in each cascade
+    pub texel_sizes: nalgebra::Vector4<f32>,
+    pub cascade_count: u32,
+    pub blend_fraction: f32,
+    /// Whether the shadow map is stored in `DepthMode::ReverseZ`
+    pub reverse_z: u32,
+}
+
+// Keep the cascade layout in sync with the `ShadowCascades` struct of the raster shaders
+const _: () = {
+    use crate::shader::layouts::{shader, shader_instanced};
+    use encase::ShaderSize;
+
+    assert!(ShadowCascades::SHADER_SIZE.get() == shader::shadow_cascades::SIZE as u64);
+    assert!(ShadowCascades::SHADER_SIZE.get() == shader_instanced::shadow_cascades::SIZE as u64);
+};
+
+/// Split distances of `cascade_count` cascades covering `near..far`
+///
+/// Uses the practical split scheme, blending logarithmic and uniform splits by `lambda`.
+/// Returns the far distance of each cascade.
+pub fn cascade_split_depths(near: f32, far: f32, cascade_count: usize, lambda: f32) -> Vec<f32> {
+    (1..=cascade_count)
+        .map(|i| {
+            let p = i as f32 / cascade_count as f32;
+            let log = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            lambda * log + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// World space corners of the slice of a view frustum between two view distances
+///
+/// Works for any `projection`, the corners are interpolated along the frustum's edges
+/// between its near and far planes.
+pub fn frustum_slice_corners(
+    camera_to_world: &Matrix4<f32>,
+    projection: &Matrix4<f32>,
+    near: f32,
+    far: f32,
+) -> [Point3<f32>; 8] {
+    let clip_to_view = projection.try_inverse().unwrap_or_else(Matrix4::identity);
+    let mut corners = [Point3::origin(); 8];
+
+    for (j, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .into_iter()
+        .enumerate()
+    {
+        let edge_near = clip_to_view.transform_point(&Point3::new(x, y, 0.0));
+        let edge_far = clip_to_view.transform_point(&Point3::new(x, y, 1.0));
+
+        for (i, distance) in [near, far].into_iter().enumerate() {
+            // View space looks down -Z
+            let t = (distance + edge_near.z) / (edge_near.z - edge_far.z);
+            let view_corner = edge_near + (edge_far - edge_near) * t;
+            corners[i * 4 + j] = camera_to_world.transform_point(&view_corner);
+        }
+    }
+
+    corners
+}
+
+/// Orthographic light projection tightly enclosing a frustum slice
+///
+/// The slice is bounded by a sphere so the projection doesn't change size as the camera
+/// rotates, and its center is snapped to whole texels so shadow edges don't shimmer as
+/// the camera moves. `corners` and the projection are relative to `origin`, while the texel
+/// grid stays anchored to the world so rebasing the origin doesn't move the shadow edges.
+/// Returns the light view-projection and the world size of one texel.
+pub fn fit_cascade(
+    corners: &[Point3<f32>; 8],
+    origin: &Point3<f64>,
+    light_dir: &Vector3<f32>,
+    map_size: u32,
+    caster_distance: f32,
+) -> (Matrix4<f32>, f32) {
+    let center = corners
+        .iter()
+        .fold(Vector3::zeros(), |sum, c| sum + c.coords)
+        / 8.0;
+    let radius = corners
+        .iter()
+        .map(|c| (c.coords - center).norm())
+        .fold(0.0f32, f32::max);
+    // Quantize the radius so floating point noise doesn't change the texel size
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    // Pad by a texel on each side so snapping the center never pushes the slice out
+    let texel_size = radius * 2.0 / (map_size.max(3) - 2) as f32;
+    let half_extent = radius + texel_size;
+
+    // Rotation-only light view, so snapping in light space only moves along the texel grid
+    let light_up = if light_dir.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+    let light_rotation =
+        Isometry3::look_at_rh(&Point3::origin(), &Point3::from(*light_dir), &light_up)
+            .to_homogeneous();
+
+    let center_ls = light_rotation.transform_point(&Point3::from(center));
+
+    // Snap the world light space center in double precision, then move it back relative to
+    // the origin
+    let origin_ls = light_rotation.cast::<f64>().transform_point(origin);
+    let snap = |relative: f32, offset: f64| {
+        let texel_size = texel_size as f64;
+        let world = relative as f64 + offset;
+        ((world / texel_size).floor() * texel_size - offset) as f32
+    };
+    let snapped_x = snap(center_ls.x, origin_ls.x);
+    let snapped_y = snap(center_ls.y, origin_ls.y);
+
+    // The light looks down -Z, so the slice spans -center_z +- radius in front of it
+    let light_proj = nalgebra::Orthographic3::new(
+        snapped_x - half_extent,
+        snapped_x + half_extent,
+        snapped_y - half_extent,
+        snapped_y + half_extent,
+        -center_ls.z - radius - caster_distance,
+        -center_ls.z + radius,
+    )
+    .to_homogeneous();
+
+    (OPENGL_TO_WGPU * light_proj * light_rotation, texel_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cascade_splits_cover_the_range() {
+        let splits = cascade_split_depths(0.1, 1000.0, 4, 0.75);
+        assert_eq!(splits.len(), 4);
+        assert!((splits[3] - 1000.0).abs() < 1e-3);
+        assert!(splits.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn uniform_splits_are_evenly_spaced() {
+        let splits = cascade_split_depths(0.0001, 100.0, 4, 0.0);
+        for (i, split) in splits.iter().enumerate() {
+            assert!((split - 25.0 * (i + 1) as f32).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn fitted_cascade_contains_the_slice() {
+        let camera_to_world = Matrix4::new_translation(&Vector3::new(200.0, 50.0, -30.0));
+        let projection = Projection::Perspective {
+            fovy: 1.0,
+            znear: 0.1,
+            zfar: 1000.0,
+        }
+        .matrix(16.0 / 9.0);
+        let corners = frustum_slice_corners(&camera_to_world, &projection, 1.0, 100.0);
+        let light_dir = Vector3::new(-0.3, -1.0, 0.2).normalize();
+
+        let (light_space, _) = fit_cascade(&corners, &Point3::origin(), &light_dir, 2048, 50.0);
+
+        for corner in &corners {
+            let clip = light_space.transform_point(corner);
+            assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0, "{:?}", clip);
+            assert!((0.0..=1.0).contains(&clip.z), "{:?}", clip);
+        }
+    }
+
+    #[test]
+    fn cascade_snapping_ignores_the_origin() {
+        let projection = Projection::Perspective {
+            fovy: 1.0,
+            znear: 0.1,
+            zfar: 1000.0,
+        }
+        .matrix(16.0 / 9.0);
+        let camera_position = Point3::new(5000.25, 40.0, -3000.5);
+        let light_dir = Vector3::new(-0.3, -1.0, 0.2).normalize();
+        let map_size = 2048;
+
+        // Texels of this slice are several centimeters wide, so these origins are off the grid
+        let origins = [
+            Point3::new(4096.0, 0.0, -2048.0),
+            Point3::new(4993.37, 1.9, -3007.21),
+        ];
+        let fitted = origins.map(|origin| {
+            let relative = (camera_position - origin).cast::<f32>();
+            let camera_to_world = Matrix4::new_translation(&relative);
+            let corners = frustum_slice_corners(&camera_to_world, &projection, 1.0, 100.0);
+            fit_cascade(&corners, &origin, &light_dir, map_size, 50.0)
+        });
+        assert!(fitted[0].1 > 0.05);
+
+        // The same world point lands on the same spot of the shadow map from both origins
+        let point = Point3::new(5010.0, 0.0, -3020.0);
+        let [a, b] = [0, 1].map(|i| {
+            let relative = Point3::from((point - origins[i]).cast::<f32>());
+            fitted[i].0.transform_point(&relative)
+        });
+        let texel = 2.0 / map_size as f32;
+        assert!((a.x - b.x).abs() < texel * 0.01, "{:?} {:?}", a, b);
+        assert!((a.y - b.y).abs() < texel * 0.01, "{:?} {:?}", a, b);
+    }
+
+    #[test]
+    fn orthographic_slices_keep_the_view_size() {
+        let projection = Projection::Orthographic {
+            scaling: OrthographicScaling::FixedVertical(10.0),
+            znear: 0.1,
+            zfar: 1000.0,
+        }
+        .matrix(2.0);
+        let corners = frustum_slice_corners(&Matrix4::identity(), &projection, 5.0, 50.0);
+
+        for (near, far) in corners[..4].iter().zip(&corners[4..]) {
+            assert!((near.z + 5.0).abs() < 1e-3 && (far.z + 50.0).abs() < 1e-3);
+            assert!((near.xy() - far.xy()).norm() < 1e-3);
+        }
+        assert!((corners[2].x - 10.0).abs() < 1e-3 && (corners[2].y - 5.0).abs() < 1e-3);
+    }
+}
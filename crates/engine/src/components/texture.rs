@@ -11,6 +11,19 @@ pub struct GpuTexture {
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
     pub bind_group: wgpu::BindGroup,
+    /// Hash of the image bytes, textures with the same id are drawn together
+    pub content_id: u64,
+}
+
+impl Texture {
+    /// Hash identifying identical images across entities
+    pub fn content_id(&self) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        self.bytes.hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
 // GPU Component trait implementations
@@ -102,6 +115,7 @@ impl GpuInitialize for Texture {
             view,
             sampler,
             bind_group,
+            content_id: user.content_id(),
         }
     }
 }
@@ -0,0 +1,134 @@
+use crate::prelude::*;
+
+/// Curve mapping HDR scene colour into the displayable range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TonemapOperator {
+    /// Fitted ACES filmic curve, punchy contrast
+    #[default]
+    Aces,
+    /// AgX, desaturates bright highlights towards white instead of skewing their hue
+    AgX,
+    /// Reinhard on luminance, preserves hue but looks flat
+    Reinhard,
+}
+
+impl TonemapOperator {
+    pub const ALL: [TonemapOperator; 3] = [
+        TonemapOperator::Aces,
+        TonemapOperator::AgX,
+        TonemapOperator::Reinhard,
+    ];
+
+    /// Operator index as switched on by the tonemapping shader
+    pub fn shader_index(&self) -> u32 {
+        match self {
+            TonemapOperator::Aces => 0,
+            TonemapOperator::AgX => 1,
+            TonemapOperator::Reinhard => 2,
+        }
+    }
+}
+
+/// Automatic exposure metered from a luminance histogram of the HDR image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoExposure {
+    /// Darkest luminance the histogram resolves, in log2 units
+    pub min_log_luminance: f32,
+    /// Brightest luminance the histogram resolves, in log2 units
+    pub max_log_luminance: f32,
+    /// Stops added to the metered exposure
+    pub compensation: f32,
+    /// Adaptation rate per second when the scene gets brighter
+    pub speed_brighten: f32,
+    /// Adaptation rate per second when the scene gets darker
+    pub speed_darken: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            min_log_luminance: -8.0,
+            max_log_luminance: 8.0,
+            compensation: 0.0,
+            speed_brighten: 3.0,
+            speed_darken: 1.0,
+        }
+    }
+}
+
+/// How the HDR image is scaled before tonemapping
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exposure {
+    /// Fixed exposure in stops, 0.0 leaves the scene unscaled
+    Manual {
+        ev: f32,
+    },
+    Auto(AutoExposure),
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Exposure::Manual { ev: 0.0 }
+    }
+}
+
+/// Tonemapping of a camera's HDR render into its `GpuRenderTarget`
+///
+/// Cameras without this component use the default operator and exposure.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Tonemapping {
+    pub operator: TonemapOperator,
+    pub exposure: Exposure,
+}
+
+/// Per-camera parameters of the tonemapping passes, as laid out in the tonemapping shader
+#[derive(ShaderType, Clone, Copy)]
+pub struct TonemapParams {
+    pub min_log_luminance: f32,
+    pub log_luminance_range: f32,
+    /// Stops added to the metered exposure
+    pub compensation: f32,
+    /// Linear scale applied when the exposure is manual
+    pub manual_exposure: f32,
+    /// Fraction of the way to the metered luminance covered this frame
+    pub adapt_brighten: f32,
+    pub adapt_darken: f32,
+    pub tonemap_operator: u32,
+    pub auto_exposure: u32,
+    /// Encode to sRGB in the shader, for outputs without an sRGB format
+    pub encode_srgb: u32,
+}
+
+impl TonemapParams {
+    pub fn new(
+        settings: &Tonemapping,
+        output_format: wgpu::TextureFormat,
+        delta_time: f32,
+    ) -> Self {
+        let (auto, manual_ev) = match settings.exposure {
+            Exposure::Manual { ev } => (AutoExposure::default(), Some(ev)),
+            Exposure::Auto(auto) => (auto, None),
+        };
+
+        Self {
+            min_log_luminance: auto.min_log_luminance,
+            log_luminance_range: (auto.max_log_luminance - auto.min_log_luminance)
+                .max(f32::EPSILON),
+            compensation: auto.compensation,
+            manual_exposure: manual_ev.map_or(1.0, f32::exp2),
+            adapt_brighten: 1.0 - (-auto.speed_brighten * delta_time).exp(),
+            adapt_darken: 1.0 - (-auto.speed_darken * delta_time).exp(),
+            tonemap_operator: settings.operator.shader_index(),
+            auto_exposure: manual_ev.is_none() as u32,
+            encode_srgb: !output_format.is_srgb() as u32,
+        }
+    }
+}
+
+// Keep the params layout in sync with the `TonemapParams` struct of the tonemapping shader
+const _: () = {
+    use crate::shader::layouts::tonemapping;
+    use encase::ShaderSize;
+
+    assert!(TonemapParams::SHADER_SIZE.get() == tonemapping::tonemap_params::SIZE as u64);
+};
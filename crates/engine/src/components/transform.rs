@@ -1,8 +1,11 @@
 use crate::prelude::*;
 
+use std::collections::HashMap;
+
 #[derive(Component, Clone, PartialEq)]
 pub struct Transform {
-    pub position: Point3<f32>,
+    /// World position in double precision, rendered relative to the `RenderOrigin`
+    pub position: Point3<f64>,
     pub rotation: UnitQuaternion<f32>,
     pub scale: Vector3<f32>,
 }
@@ -17,68 +20,217 @@ impl Default for Transform {
     }
 }
 
+/// Keeps an entity's `Transform::rotation` aimed at a point
+///
+/// Its -Z axis faces `target` and its +Y axis leans towards `up`, the convention cameras look
+/// along. The rotation is rewritten before rendering, so it overrides any other rotation.
+#[derive(Component, Clone, PartialEq)]
+pub struct LookAt {
+    pub target: Point3<f64>,
+    pub up: Vector3<f32>,
+}
+
+impl LookAt {
+    pub fn new(target: Point3<f64>) -> Self {
+        Self {
+            target,
+            up: Vector3::y(),
+        }
+    }
+
+    /// Rotation facing `target` from `position`, `None` if the two coincide
+    pub fn rotation(&self, position: &Point3<f64>) -> Option<UnitQuaternion<f32>> {
+        let direction: Vector3<f32> = (self.target - position).cast();
+        if direction.norm_squared() < f32::EPSILON {
+            return None;
+        }
+        // Any other up vector will do when looking straight along `up`
+        let up = if direction.cross(&self.up).norm_squared() >= f32::EPSILON {
+            self.up
+        } else if self.up.x.abs() < 0.9 {
+            Vector3::x()
+        } else {
+            Vector3::z()
+        };
+        Some(UnitQuaternion::face_towards(&-direction, &up))
+    }
+}
+
+/// Point of the world the GPU sees as its origin
+///
+/// Single precision can't hold positions far from the world origin without vertices and
+/// shadows jittering, so every matrix and position uploaded to the GPU is made relative to this
+/// point in double precision first. It follows the main camera, jumping to it whenever the
+/// camera strays further than `rebase_distance`, which rewrites every model matrix once.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct RenderOrigin {
+    pub position: Point3<f64>,
+    /// Zero keeps the origin exactly on the camera, rewriting every model matrix each frame
+    /// the camera moves
+    pub rebase_distance: f64,
+}
+
+impl Default for RenderOrigin {
+    fn default() -> Self {
+        Self {
+            position: Point3::origin(),
+            rebase_distance: 1024.0,
+        }
+    }
+}
+
+impl RenderOrigin {
+    /// Single precision position of a world point relative to the origin
+    pub fn relative(&self, point: &Point3<f64>) -> Point3<f32> {
+        Point3::from((point - self.position).cast())
+    }
+}
+
+/// Slot of an entity's model matrix in `GpuTransforms`
 #[derive(Component)]
 pub struct GpuTransform {
-    pub buffer: wgpu::Buffer,
-    pub bind_group: wgpu::BindGroup,
+    pub object_id: u32,
 }
 
-// Helper function for computing model matrix
-fn compute_model_matrix(transform: &Transform) -> Matrix4<f32> {
-    let translation = Matrix4::new_translation(&transform.position.coords);
-    let rotation = transform.rotation.to_homogeneous();
-    let scale = Matrix4::new_nonuniform_scaling(&transform.scale);
-    translation * rotation * scale
+/// Model matrices of every entity with a `Transform` in one storage buffer, indexed by object id
+#[derive(Resource)]
+pub struct GpuTransforms {
+    pub buffer: wgpu::Buffer,
+    /// Bumped whenever `buffer` is recreated, so bind groups using it are rebuilt
+    pub generation: u32,
+    capacity: u32,
+    next_id: u32,
+    free_ids: Vec<u32>,
+    ids: HashMap<Entity, u32>,
 }
 
-// GPU Component trait implementations
-impl GpuComponent for Transform {
-    type UserComponent = Transform;
-    type GpuVariant = GpuTransform;
+// The model matrices are uploaded as-is into the `TransformData` array of the raster and shadow shaders
+const _: () = {
+    use crate::shader::layouts::{shader, shadow};
+
+    assert!(std::mem::size_of::<Matrix4<f32>>() == shader::transform_data::SIZE);
+    assert!(std::mem::size_of::<Matrix4<f32>>() == shadow::transform_data::SIZE);
+    assert!(std::mem::size_of::<Matrix4<f32>>() == shadow::shadow_uniform::SIZE);
+};
+
+impl Transform {
+    /// World position of a point in local space, in double precision
+    pub fn transform_point(&self, point: &Point3<f32>) -> Point3<f64> {
+        let offset = self.rotation * point.coords.component_mul(&self.scale);
+        self.position + offset.cast()
+    }
+
+    /// Local to world space matrix, loses precision far from the world origin
+    pub fn model_matrix(&self) -> Matrix4<f32> {
+        self.model_matrix_relative(&Point3::origin())
+    }
+
+    /// Local to world space matrix with `origin` moved to zero
+    ///
+    /// The translation is subtracted in double precision, so the result stays exact near
+    /// `origin` however far it is from the world origin.
+    pub fn model_matrix_relative(&self, origin: &Point3<f64>) -> Matrix4<f32> {
+        let position: Vector3<f32> = (self.position - origin).cast();
+        let translation = Matrix4::new_translation(&position);
+        let rotation = self.rotation.to_homogeneous();
+        let scale = Matrix4::new_nonuniform_scaling(&self.scale);
+        translation * rotation * scale
+    }
 }
 
-impl GpuInitialize for Transform {
-    type Dependencies = ();
+impl GpuTransforms {
+    const INITIAL_CAPACITY: u32 = 1024;
+    const STRIDE: u64 = std::mem::size_of::<Matrix4<f32>>() as u64;
 
-    fn initialize(
-        user: &Self::UserComponent,
-        _dependencies: Option<&Self::Dependencies>,
-        device: &wgpu::Device,
-        _queue: &wgpu::Queue,
-        context: &GpuContext,
-    ) -> Self::GpuVariant {
-        use wgpu::util::DeviceExt;
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            buffer: Self::create_buffer(device, Self::INITIAL_CAPACITY),
+            generation: 0,
+            capacity: Self::INITIAL_CAPACITY,
+            next_id: 0,
+            free_ids: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
 
-        let model_matrix = compute_model_matrix(user);
+    fn create_buffer(device: &wgpu::Device, capacity: u32) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Transforms Buffer"),
+            size: capacity as u64 * Self::STRIDE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
 
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Transform Buffer"),
-            contents: bytemuck::cast_slice(&[model_matrix]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    /// Assign a slot to an entity, reusing freed ones first
+    pub fn allocate(&mut self, entity: Entity) -> u32 {
+        let id = self.free_ids.pop().unwrap_or_else(|| {
+            self.next_id += 1;
+            self.next_id - 1
         });
+        self.ids.insert(entity, id);
+        id
+    }
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &context.transform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-            label: Some("transform_bind_group"),
-        });
+    /// Release the slot of an entity that lost its `Transform`
+    pub fn free(&mut self, entity: Entity) {
+        if let Some(id) = self.ids.remove(&entity) {
+            self.free_ids.push(id);
+        }
+    }
 
-        GpuTransform { buffer, bind_group }
+    /// Grow the buffer until every allocated slot fits
+    ///
+    /// Returns true if the buffer was recreated, its contents are then lost and every
+    /// transform has to be written again.
+    pub fn reserve(&mut self, device: &wgpu::Device) -> bool {
+        if self.next_id <= self.capacity {
+            return false;
+        }
+
+        self.capacity = self.next_id.next_power_of_two();
+        self.buffer = Self::create_buffer(device, self.capacity);
+        self.generation = self.generation.wrapping_add(1);
+        true
     }
-}
 
-impl GpuUpdate for Transform {
-    fn update(
-        user: &Self::UserComponent,
-        gpu: &mut Self::GpuVariant,
-        _dependencies: Option<&()>,
-        _device: &wgpu::Device,
+    pub fn write(
+        &self,
         queue: &wgpu::Queue,
+        object_id: u32,
+        transform: &Transform,
+        origin: &RenderOrigin,
     ) {
-        let model_matrix = compute_model_matrix(user);
-        queue.write_buffer(&gpu.buffer, 0, bytemuck::cast_slice(&[model_matrix]));
+        queue.write_buffer(
+            &self.buffer,
+            object_id as u64 * Self::STRIDE,
+            bytemuck::cast_slice(&[transform.model_matrix_relative(&origin.position)]),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_model_matrix_keeps_precision_far_away() {
+        let origin = Point3::new(1.0e9, 0.0, -1.0e9);
+        let transform = Transform {
+            position: origin + Vector3::new(0.25, 0.5, 0.125),
+            ..Default::default()
+        };
+
+        let matrix = transform.model_matrix_relative(&origin);
+        assert_eq!(matrix.column(3).xyz(), Vector3::new(0.25, 0.5, 0.125));
+
+        let render_origin = RenderOrigin {
+            position: origin,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_origin.relative(&transform.position),
+            Point3::new(0.25, 0.5, 0.125)
+        );
     }
 }
@@ -0,0 +1,402 @@
+use crate::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Axis aligned bounding box of a mesh in its local space
+///
+/// Kept up to date for every `Mesh` and `InstancedLodMesh`, the latter bounding its base mesh.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// Bounds of the vertex positions, `None` for an empty mesh
+    pub fn from_vertices(vertices: &[Vertex]) -> Option<Self> {
+        let mut points = vertices.iter().map(|v| Point3::from(v.position));
+        let first = points.next()?;
+
+        Some(points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, p| Self {
+                min: aabb.min.inf(&p),
+                max: aabb.max.sup(&p),
+            },
+        ))
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Smallest box containing both boxes
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// Bounds of this box after an affine transformation
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        let center = matrix.transform_point(&self.center());
+        let linear = matrix.fixed_view::<3, 3>(0, 0).abs();
+        let half_extents = linear * self.half_extents();
+
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+}
+
+/// The six normalized planes of a view frustum, pointing inwards
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [nalgebra::Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extract the planes of a view-projection matrix with wgpu clip space depth (0..1)
+    ///
+    /// The far plane of an infinite reverse-Z projection has no normal, it is kept as a plane
+    /// everything lies inside of.
+    pub fn from_view_projection(matrix: &Matrix4<f32>) -> Self {
+        let row = |i: usize| matrix.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+                let length = plane.xyz().norm();
+                if length > f32::EPSILON {
+                    plane / length
+                } else {
+                    nalgebra::Vector4::new(0.0, 0.0, 0.0, 1.0)
+                }
+            }),
+        }
+    }
+
+    pub fn planes(&self) -> &[nalgebra::Vector4<f32>; 6] {
+        &self.planes
+    }
+
+    /// Whether any part of the sphere may be inside the frustum
+    pub fn intersects_sphere(&self, center: &Point3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(&center.coords) + plane.w >= -radius)
+    }
+
+    /// Whether any part of the box may be inside the frustum
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let half_extents = aabb.half_extents();
+
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            let radius = half_extents.dot(&normal.abs());
+            normal.dot(&center.coords) + plane.w >= -radius
+        })
+    }
+}
+
+/// Sphere hiding the instances of an instanced mesh that lie below its horizon, e.g. a planet
+#[derive(Component, Clone, Copy, Debug)]
+pub struct HorizonOccluder {
+    /// World space center
+    pub center: Point3<f64>,
+    pub radius: f32,
+}
+
+/// Meshes a camera sees after frustum culling, rebuilt every frame
+#[derive(Component, Default)]
+pub struct VisibleEntities {
+    /// Entities with a `GpuMesh` inside the frustum
+    pub meshes: HashSet<Entity>,
+}
+
+/// Instances of every `GpuInstancedLodMesh` a camera sees, culled on the GPU each frame
+#[derive(Component, Default)]
+pub struct GpuVisibleInstances {
+    pub meshes: HashMap<Entity, GpuCulledInstances>,
+}
+
+/// Culling pass input and output for one instanced mesh seen by one camera
+pub struct GpuCulledInstances {
+    /// Surviving instances, compacted
+    pub instance_buffer: wgpu::Buffer,
+    /// `DrawIndexedIndirectArgs` written by the culling pass
+    pub indirect_buffer: wgpu::Buffer,
+    /// `InstanceCullParams` uniform
+    pub params_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    /// Number of instances tested this frame
+    pub instance_count: u32,
+    /// `GpuInstancedLodMesh::generation` of the buffers bound in `bind_group`
+    pub generation: u32,
+    pub readback: VisibleCountReadback,
+}
+
+impl GpuCulledInstances {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        mesh: &GpuInstancedLodMesh,
+    ) -> Self {
+        // Same capacity as the mesh's instance buffer
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Culled Instance Buffer"),
+            size: mesh.instance_buffer.size(),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let indirect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Culled Instance Indirect Buffer"),
+            size: std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as u64,
+            usage: wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Cull Params Buffer"),
+            size: <InstanceCullParams as encase::ShaderSize>::SHADER_SIZE.get(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: mesh.instance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: mesh.bounds_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: indirect_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("instance_cull_bind_group"),
+        });
+
+        Self {
+            instance_buffer,
+            indirect_buffer,
+            params_buffer,
+            bind_group,
+            instance_count: 0,
+            generation: mesh.generation,
+            readback: VisibleCountReadback::new(device),
+        }
+    }
+}
+
+/// Reads the number of instances that survived culling back from the indirect arguments
+///
+/// Only one read is in flight at a time, so the count trails the rendered frame slightly.
+pub struct VisibleCountReadback {
+    buffer: wgpu::Buffer,
+    pending: Arc<AtomicBool>,
+    visible: Arc<AtomicU32>,
+}
+
+impl VisibleCountReadback {
+    fn new(device: &wgpu::Device) -> Self {
+        Self {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Visible Instance Count Readback"),
+                size: std::mem::size_of::<u32>() as u64,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            pending: Arc::new(AtomicBool::new(false)),
+            visible: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Record a copy of the instance count, returns false while a previous read is in flight
+    pub fn copy_from(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        indirect_buffer: &wgpu::Buffer,
+    ) -> bool {
+        if self.pending.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+
+        let offset = std::mem::offset_of!(wgpu::util::DrawIndexedIndirectArgs, instance_count);
+        encoder.copy_buffer_to_buffer(
+            indirect_buffer,
+            offset as u64,
+            &self.buffer,
+            0,
+            self.buffer.size(),
+        );
+        true
+    }
+
+    /// Map the buffer once the copy recorded by `copy_from` has been submitted
+    pub fn map(&self) {
+        let buffer = self.buffer.clone();
+        let pending = self.pending.clone();
+        let visible = self.visible.clone();
+
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    let count = bytemuck::pod_read_unaligned(&buffer.slice(..).get_mapped_range());
+                    visible.store(count, Ordering::Relaxed);
+                    buffer.unmap();
+                }
+                pending.store(false, Ordering::Release);
+            });
+    }
+
+    /// Last instance count read back
+    pub fn visible(&self) -> u32 {
+        self.visible.load(Ordering::Relaxed)
+    }
+}
+
+/// Per-dispatch parameters of the culling pass, as laid out in the culling shader
+#[derive(ShaderType, Clone, Copy)]
+pub struct InstanceCullParams {
+    /// Normalized frustum planes, pointing inwards
+    pub frustum_planes: [nalgebra::Vector4<f32>; 6],
+    pub camera_position: nalgebra::Vector4<f32>,
+    /// Center and radius of the `HorizonOccluder`, a zero radius disables horizon culling
+    pub horizon_occluder: nalgebra::Vector4<f32>,
+    pub instance_count: u32,
+    pub index_count: u32,
+}
+
+// Keep the params layout in sync with the `InstanceCullParams` struct of the culling shader
+const _: () = {
+    use crate::shader::layouts::instance_cull;
+    use encase::ShaderSize;
+
+    assert!(
+        InstanceCullParams::SHADER_SIZE.get() == instance_cull::instance_cull_params::SIZE as u64
+    );
+    assert!(
+        std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>()
+            == instance_cull::draw_indexed_indirect_args::SIZE
+    );
+};
+
+/// Culling results summed over all cameras
+///
+/// Mesh counts are from the last frame. Instances are culled on the GPU, their counts are
+/// read back asynchronously and trail by a frame or two.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct CullingStats {
+    pub meshes_drawn: u32,
+    pub meshes_culled: u32,
+    pub instances_drawn: u32,
+    pub instances_culled: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_cube_at(center: Point3<f32>) -> Aabb {
+        Aabb {
+            min: center - Vector3::repeat(0.5),
+            max: center + Vector3::repeat(0.5),
+        }
+    }
+
+    #[test]
+    fn transformed_bounds_cover_rotated_box() {
+        let aabb = unit_cube_at(Point3::origin());
+        let rotation =
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::FRAC_PI_4);
+        let matrix =
+            Matrix4::new_translation(&Vector3::new(10.0, 0.0, 0.0)) * rotation.to_homogeneous();
+
+        let transformed = aabb.transformed(&matrix);
+        let half_diagonal = std::f32::consts::SQRT_2 * 0.5;
+
+        assert!((transformed.center() - Point3::new(10.0, 0.0, 0.0)).norm() < 1e-5);
+        assert!((transformed.half_extents().x - half_diagonal).abs() < 1e-5);
+        assert!((transformed.half_extents().y - 0.5).abs() < 1e-5);
+        assert!((transformed.half_extents().z - half_diagonal).abs() < 1e-5);
+    }
+
+    #[test]
+    fn frustum_culls_boxes_outside_the_view() {
+        let camera = Camera {
+            is_main: true,
+            projection: Projection::Perspective {
+                fovy: std::f32::consts::FRAC_PI_2,
+                znear: 0.1,
+                zfar: 100.0,
+            },
+            aperture: 0.0,
+            focus_distance: 1.0,
+            ..Default::default()
+        };
+        let frustum =
+            Frustum::from_view_projection(&camera.view_projection(&Transform::default(), 1.0));
+
+        assert!(frustum.intersects_aabb(&unit_cube_at(Point3::new(0.0, 0.0, -10.0))));
+        // Straddling the left plane
+        assert!(frustum.intersects_aabb(&unit_cube_at(Point3::new(-10.4, 0.0, -10.0))));
+        // Behind the camera, beyond the far plane, and off to the side
+        assert!(!frustum.intersects_aabb(&unit_cube_at(Point3::new(0.0, 0.0, 10.0))));
+        assert!(!frustum.intersects_aabb(&unit_cube_at(Point3::new(0.0, 0.0, -110.0))));
+        assert!(!frustum.intersects_aabb(&unit_cube_at(Point3::new(20.0, 0.0, -10.0))));
+
+        // Normalized planes make sphere tests exact
+        assert!(frustum.intersects_sphere(&Point3::new(11.0, 0.0, -10.0), 0.8));
+        assert!(!frustum.intersects_sphere(&Point3::new(11.0, 0.0, -10.0), 0.6));
+    }
+
+    #[test]
+    fn reverse_z_frustum_has_no_far_plane() {
+        let camera = Camera {
+            projection: Projection::Perspective {
+                fovy: std::f32::consts::FRAC_PI_2,
+                znear: 0.1,
+                zfar: 100.0,
+            },
+            depth_mode: DepthMode::ReverseZ,
+            ..Default::default()
+        };
+        let frustum =
+            Frustum::from_view_projection(&camera.view_projection(&Transform::default(), 1.0));
+
+        assert!(frustum.intersects_aabb(&unit_cube_at(Point3::new(0.0, 0.0, -1.0e6))));
+        assert!(!frustum.intersects_aabb(&unit_cube_at(Point3::new(0.0, 0.0, 10.0))));
+        assert!(!frustum.intersects_aabb(&unit_cube_at(Point3::new(20.0, 0.0, -10.0))));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::prelude::*;
+
+/// Global wireframe overlay, drawing the triangle edges of every mesh over its shaded surface
+///
+/// The edges come from barycentric coordinates, so unlike `Material::wireframe` this works
+/// without `POLYGON_MODE_LINE`.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct WireframeSettings {
+    /// Overlay every mesh, not only the ones with a `Wireframe` component
+    pub overlay: bool,
+    /// Linear RGBA colour of the edges
+    pub color: [f32; 4],
+    /// Edge width in pixels
+    pub width: f32,
+}
+
+impl Default for WireframeSettings {
+    fn default() -> Self {
+        Self {
+            overlay: false,
+            color: [0.1, 1.0, 0.3, 1.0],
+            width: 1.0,
+        }
+    }
+}
+
+/// Overlays the triangle edges of this entity's mesh, see `WireframeSettings`
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Wireframe;
+
+impl Material {
+    /// Whether this material asks for `POLYGON_MODE_LINE` on an adapter without it
+    ///
+    /// Such materials are drawn by the wireframe pass instead of their own shader.
+    pub fn needs_wireframe_fallback(&self, features: &SupportedFeatures) -> bool {
+        self.render_mode.polygon_mode == wgpu::PolygonMode::Line
+            && !features.supports_polygon_mode(wgpu::PolygonMode::Line)
+    }
+}
+
+/// Vertex of a `GpuWireframeMesh`, every triangle has its own three vertices
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct WireframeVertex {
+    pub position: [f32; 3],
+    /// One at the triangle corner of this vertex, zero at the other two
+    pub barycentric: [f32; 3],
+}
+
+impl WireframeVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    /// Unshared vertices of every triangle of `mesh`
+    pub fn from_mesh(mesh: &Mesh) -> Vec<Self> {
+        const CORNERS: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+        mesh.indices
+            .chunks_exact(3)
+            .flat_map(|triangle| {
+                [0, 1, 2].map(|corner| Self {
+                    position: mesh.vertices[triangle[corner] as usize].position,
+                    barycentric: CORNERS[corner],
+                })
+            })
+            .collect()
+    }
+}
+
+/// Barycentric copy of a `Mesh` or an `InstancedLodMesh` base mesh, for the wireframe pass
+///
+/// Only exists while the entity is drawn as a wireframe. The index buffer counts up from zero,
+/// so instanced meshes can reuse the indirect draws written by the culling pass.
+#[derive(Component)]
+pub struct GpuWireframeMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    /// `Uint32` indices
+    pub index_buffer: wgpu::Buffer,
+    pub vertex_count: u32,
+    /// `Mesh::content_id` of the geometry it was built from
+    pub content_id: u64,
+}
+
+impl GpuWireframeMesh {
+    pub fn new(device: &wgpu::Device, mesh: &Mesh) -> Self {
+        use wgpu::util::DeviceExt;
+
+        let vertices = WireframeVertex::from_mesh(mesh);
+        let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Wireframe Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Wireframe Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            vertex_count: vertices.len() as u32,
+            content_id: mesh.content_id(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triangles_get_unshared_corners() {
+        let vertex = |x: f32| Vertex {
+            position: [x, 0.0, 0.0],
+            uv: [0.0; 2],
+            normal: [0.0, 1.0, 0.0],
+        };
+        let mesh = Mesh {
+            vertices: vec![vertex(0.0), vertex(1.0), vertex(2.0), vertex(3.0)],
+            indices: vec![0, 1, 2, 2, 1, 3],
+        };
+
+        let vertices = WireframeVertex::from_mesh(&mesh);
+
+        assert_eq!(vertices.len(), 6);
+        let positions: Vec<f32> = vertices.iter().map(|v| v.position[0]).collect();
+        assert_eq!(positions, [0.0, 1.0, 2.0, 2.0, 1.0, 3.0]);
+        assert_eq!(vertices[3].barycentric, [1.0, 0.0, 0.0]);
+        assert_eq!(vertices[5].barycentric, [0.0, 0.0, 1.0]);
+    }
+}
@@ -13,7 +13,7 @@ pub trait GpuComponent {
 
 /// Trait for components that can be initialized to GPU variants
 pub trait GpuInitialize: GpuComponent {
-    /// Bundle of components this GPU component depends on (e.g., (Transform,) for Camera)
+    /// Bundle of components this GPU component depends on (e.g., (Transform,))
     /// Use () for no dependencies
     type Dependencies: Bundle;
 
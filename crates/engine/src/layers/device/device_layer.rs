@@ -40,6 +40,30 @@ impl DeviceLayer {
             supported_features.polygon_mode_point = true;
         }
 
+        if adapter_features.contains(wgpu::Features::TIMESTAMP_QUERY) {
+            features |= wgpu::Features::TIMESTAMP_QUERY;
+            supported_features.timestamp_query = true;
+        }
+
+        // Sample counts beyond the guaranteed 1 and 4 depend on the adapter and format
+        let msaa_formats = [HDR_FORMAT, wgpu::TextureFormat::Depth32Float];
+        if adapter_features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+            features |= wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+            for count in MsaaSettings::SAMPLE_COUNTS {
+                let supported = msaa_formats.iter().all(|&format| {
+                    adapter
+                        .get_texture_format_features(format)
+                        .flags
+                        .sample_count_supported(count)
+                });
+                if supported {
+                    supported_features.msaa_sample_counts |= count;
+                }
+            }
+        } else {
+            supported_features.msaa_sample_counts = 1 | 4;
+        }
+
         let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
             label: None,
             required_features: features,
@@ -56,6 +80,9 @@ impl DeviceLayer {
 
         // Store everything in world resources
         let mut world = context.world.lock().unwrap();
+        if supported_features.timestamp_query {
+            world.insert_resource(GpuProfiler::new(&device, &queue));
+        }
         world.insert_resource(GpuDevice(device));
         world.insert_resource(GpuQueue(queue));
         world.insert_resource(GpuAdapter(Some(adapter)));
@@ -1,6 +1,6 @@
 // Basic Raytracer Shader
 @group(0) @binding(3)
-var output_texture: texture_storage_2d<rgba8unorm, write>;
+var output_texture: texture_storage_2d<rgba16float, write>;
 
 @group(0) @binding(1)
 var<storage, read> spheres: array<Sphere>;
@@ -108,20 +108,13 @@ struct Sphere {
 }
 
 struct Camera {
-    position: vec3<f32>,
-    look_at: vec3<f32>,
+    // Clip to world space, the same projection the raster renderer uses
+    inverse_view_projection: mat4x4<f32>,
+    // World space camera axes spanning the lens
+    right: vec3<f32>,
     up: vec3<f32>,
-    fov: f32,
-    aspect_ratio: f32,
     aperture: f32,
     focus_distance: f32,
-    // Precomputed camera basis vectors
-    u: vec3<f32>,
-    v: vec3<f32>,
-    w: vec3<f32>,
-    lower_left_corner: vec3<f32>,
-    horizontal: vec3<f32>,
-    vertical: vec3<f32>,
 }
 
 fn hit_sphere(sphere: Sphere, ray: Ray) -> f32 {
@@ -146,26 +139,29 @@ fn hit_sphere(sphere: Sphere, ray: Ray) -> f32 {
 }
 
 fn build_ray(u: f32, v: f32, seed: ptr<function, u32>) -> Ray {
-    // Use precomputed camera basis vectors
+    // Unproject the pixel onto the near and far planes, works for any projection
+    let ndc = vec2<f32>(u, v) * 2.0 - 1.0;
+    let near = camera.inverse_view_projection * vec4<f32>(ndc, 0.0, 1.0);
+    let far = camera.inverse_view_projection * vec4<f32>(ndc, 1.0, 1.0);
+    let origin = near.xyz / near.w;
+    let direction = normalize(far.xyz / far.w - origin);
+
     // depth of field
     if camera.aperture > 0.0 {
-        let focus_point = camera.lower_left_corner + u * camera.horizontal + v * camera.vertical;
-        let ray_direction = normalize(focus_point - camera.position);
-        let point_on_focus_plane = camera.position + ray_direction * camera.focus_distance;
+        let point_on_focus_plane = origin + direction * camera.focus_distance;
 
         // Randomize ray origin within aperture disk
         let random_offset = random_in_unit_disk(seed);
-        let offset = camera.u * random_offset.x * camera.aperture + camera.v * random_offset.y * camera.aperture;
-        let ray_origin = camera.position + offset;
+        let offset = camera.right * random_offset.x * camera.aperture + camera.up * random_offset.y * camera.aperture;
+        let ray_origin = origin + offset;
 
         // Ray direction from randomized origin to point on focus plane
-        let direction = normalize(point_on_focus_plane - ray_origin);
+        let ray_direction = normalize(point_on_focus_plane - ray_origin);
 
-        return Ray(ray_origin, direction);
-    } else {
-        let direction = normalize(camera.lower_left_corner + u * camera.horizontal + v * camera.vertical - camera.position);
-        return Ray(camera.position, direction);
+        return Ray(ray_origin, ray_direction);
     }
+
+    return Ray(origin, direction);
 }
 
 fn get_environment_color(direction: vec3<f32>) -> vec3<f32> {
@@ -305,39 +301,7 @@ fn raytracer(@builtin(global_invocation_id) global_id: vec3<u32>) {
     
     // Write accumulated linear color to ping-pong buffer
     textureStore(accumulation_output, pixel, vec4<f32>(accumulated, 1.0));
-    
-    // Apply gamma correction for display output
-    let gamma_corrected = pow(accumulated, vec3(1.0 / 2.2));
-
-    textureStore(output_texture, pixel, vec4<f32>(gamma_corrected, 1.0));
-}
-
-// basic vertex and fragment shaders to display the raytraced texture
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) uv: vec2<f32>,
-}
-
-@vertex
-fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
-    let uv = vec2<f32>(
-        f32((vertex_index << 1u) & 2u),
-        f32(vertex_index & 2u)
-    );
-
-    var out: VertexOutput;
-    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
-    out.uv = uv;
-    return out;
-}
-
-@group(0) @binding(0)
-var result_texture: texture_2d<f32>;
-@group(0) @binding(1)
-var result_sampler: sampler;
 
-@fragment
-fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(result_texture, result_sampler, in.uv); 
+    // Output stays linear HDR, exposure and display encoding happen in the tonemapping pass
+    textureStore(output_texture, pixel, vec4<f32>(accumulated, 1.0));
 }
- 
\ No newline at end of file
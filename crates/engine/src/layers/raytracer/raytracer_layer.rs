@@ -3,7 +3,11 @@ use crate::prelude::*;
 use crate::layers::raytracer::{
     load_environment_map, reload_environment_map, update_raytracer_camera, update_raytracer_scene,
 };
-use crate::shader::{RaytracerShader, create_shader_loader, create_static_shader_loader};
+use crate::layers::renderer::systems::update_look_at;
+use crate::layers::renderer::{PostProcessor, Tonemapper};
+use crate::shader::{
+    RaytracerShader, ShaderCache, create_shader_loader, create_static_shader_loader,
+};
 use encase::UniformBuffer;
 use wgpu::util::DeviceExt;
 
@@ -11,7 +15,6 @@ pub struct RaytracerLayer {
     device: wgpu::Device,
     queue: wgpu::Queue,
     compute_pipeline: wgpu::ComputePipeline,
-    display_pipeline: wgpu::RenderPipeline,
     output_texture: Option<wgpu::Texture>,
     output_view: Option<wgpu::TextureView>,
     // Ping-pong accumulation buffers for temporal accumulation
@@ -21,19 +24,18 @@ pub struct RaytracerLayer {
     accumulation_view_b: Option<wgpu::TextureView>,
     accumulation_sampler: wgpu::Sampler,
     current_accumulation_index: bool, // false = A, true = B
-    sampler: wgpu::Sampler,
     compute_bind_group: Option<wgpu::BindGroup>,
-    display_bind_group: Option<wgpu::BindGroup>,
     camera_buffer: wgpu::Buffer,
     schedule: Schedule,
     compute_bind_group_layout: wgpu::BindGroupLayout,
-    display_bind_group_layout: wgpu::BindGroupLayout,
     shader_error: Option<String>,
     default_env_map: Option<(wgpu::TextureView, wgpu::Sampler)>,
     frame_count_buffer: wgpu::Buffer,
     frame_count: u32,
-    last_camera_position: Option<Vector3<f32>>,
-    last_camera_target: Option<Vector3<f32>>,
+    last_camera_position: Option<Vector3<f64>>,
+    last_camera_rotation: Option<UnitQuaternion<f32>>,
+    tonemapper: Tonemapper,
+    post_processor: PostProcessor,
 }
 
 #[derive(Resource, Clone, Default)]
@@ -107,7 +109,7 @@ impl RaytracerLayer {
                         visibility: wgpu::ShaderStages::COMPUTE,
                         ty: wgpu::BindingType::StorageTexture {
                             access: wgpu::StorageTextureAccess::WriteOnly,
-                            format: wgpu::TextureFormat::Rgba8Unorm,
+                            format: HDR_FORMAT,
                             view_dimension: wgpu::TextureViewDimension::D2,
                         },
                         count: None,
@@ -173,30 +175,6 @@ impl RaytracerLayer {
                 ],
             });
 
-        // Create bind group layout for display shader
-        let display_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Raytracer Display Bind Group Layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
-
         // Create compute pipeline
         let compute_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -214,74 +192,9 @@ impl RaytracerLayer {
             cache: None,
         });
 
-        // Create display pipeline
-        let display_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Raytracer Display Pipeline Layout"),
-                bind_group_layouts: &[&display_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-        let surface_format = wgpu::TextureFormat::Rgba8Unorm;
-        let display_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Raytracer Display Pipeline"),
-            layout: Some(&display_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
-
-        // Create sampler for display
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
         // Create camera buffer (will be updated by systems)
-        let camera_data = RaytracerCamera::new(
-            Vector3::new(0.0, 2.0, 5.0),
-            Vector3::new(0.0, 0.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-            60.0,
-            16.0 / 9.0,
-            1.0,
-            10.0,
-        );
+        let camera_data =
+            RaytracerCamera::new(&Camera::default(), &Transform::default(), 16.0 / 9.0);
 
         let mut buffer_data = UniformBuffer::new(Vec::new());
         buffer_data.write(&camera_data).unwrap();
@@ -301,34 +214,31 @@ impl RaytracerLayer {
 
         // Clone layouts before storing them in the world
         let compute_bind_group_layout_clone = compute_bind_group_layout.clone();
-        let display_bind_group_layout_clone = display_bind_group_layout.clone();
 
         // Store resources in ECS world
         {
             let mut world = context.world.lock().unwrap();
             world.insert_resource(RaytracerBindGroupLayout(compute_bind_group_layout));
-            world.insert_resource(RaytracerDisplayBindGroupLayout(display_bind_group_layout));
             world.insert_resource(RaytracerComputePipeline(compute_pipeline.clone()));
-            world.insert_resource(RaytracerDisplayPipeline(display_pipeline.clone()));
             world.insert_resource(RaytracerCameraBuffer(camera_buffer.clone()));
 
             // Store RaytracerShader resource
-            let raytracer_shader = RaytracerShader::new(
-                shader_loader,
-                compute_pipeline.clone(),
-                display_pipeline.clone(),
-            );
+            let raytracer_shader = RaytracerShader::new(shader_loader, compute_pipeline.clone());
             world.insert_resource(raytracer_shader);
 
             // Initialize shader error resource
             world.insert_resource(ShaderError::default());
         }
 
+        let tonemapper = Tonemapper::new(&device);
+        let post_processor = PostProcessor::new(&device);
+
         // Setup systems
         let mut schedule = Schedule::default();
         schedule.add_systems((
             update_raytracer_scene,
-            update_raytracer_camera,
+            update_raytracer_camera.after(update_look_at),
+            update_look_at,
             load_environment_map,
             reload_environment_map,
         ));
@@ -349,7 +259,6 @@ impl RaytracerLayer {
             device,
             queue,
             compute_pipeline,
-            display_pipeline,
             output_texture: None,
             output_view: None,
             accumulation_texture_a: None,
@@ -358,19 +267,18 @@ impl RaytracerLayer {
             accumulation_view_b: None,
             accumulation_sampler,
             current_accumulation_index: false,
-            sampler,
             compute_bind_group: None,
-            display_bind_group: None,
             camera_buffer,
             schedule,
             compute_bind_group_layout: compute_bind_group_layout_clone,
-            display_bind_group_layout: display_bind_group_layout_clone,
             shader_error: None,
             default_env_map: None,
             frame_count_buffer,
             frame_count: 0,
             last_camera_position: None,
-            last_camera_target: None,
+            last_camera_rotation: None,
+            tonemapper,
+            post_processor,
         }
     }
 
@@ -401,67 +309,14 @@ impl RaytracerLayer {
                     cache: None,
                 });
 
-        // Recreate display pipeline
-        let display_pipeline_layout =
-            self.device
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("Raytracer Display Pipeline Layout"),
-                    bind_group_layouts: &[&self.display_bind_group_layout],
-                    push_constant_ranges: &[],
-                });
-
-        let surface_format = wgpu::TextureFormat::Rgba8Unorm;
-        let display_pipeline =
-            self.device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Raytracer Display Pipeline"),
-                    layout: Some(&display_pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &shader,
-                        entry_point: Some("vs_main"),
-                        buffers: &[],
-                        compilation_options: wgpu::PipelineCompilationOptions::default(),
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &shader,
-                        entry_point: Some("fs_main"),
-                        targets: &[Some(wgpu::ColorTargetState {
-                            format: surface_format,
-                            blend: Some(wgpu::BlendState::REPLACE),
-                            write_mask: wgpu::ColorWrites::ALL,
-                        })],
-                        compilation_options: wgpu::PipelineCompilationOptions::default(),
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: None,
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        unclipped_depth: false,
-                        conservative: false,
-                    },
-                    depth_stencil: None,
-                    multisample: wgpu::MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    multiview: None,
-                    cache: None,
-                });
-
         self.compute_pipeline = compute_pipeline.clone();
-        self.display_pipeline = display_pipeline.clone();
 
         // Update pipelines in world resources
         world.insert_resource(RaytracerComputePipeline(compute_pipeline.clone()));
-        world.insert_resource(RaytracerDisplayPipeline(display_pipeline.clone()));
 
         // Update RaytracerShader resource
         if let Some(mut raytracer_shader) = world.get_resource_mut::<RaytracerShader>() {
             raytracer_shader.compute_pipeline = compute_pipeline;
-            raytracer_shader.display_pipeline = display_pipeline;
         }
 
         log::info!("Shader reloaded successfully!");
@@ -509,10 +364,16 @@ impl Layer for RaytracerLayer {
             // Run schedule
             self.schedule.run(&mut world);
 
-            // Get window size for render target
-            let window_size = world.get_resource::<WindowSize>().unwrap();
-            let width = window_size.width;
-            let height = window_size.height;
+            // Trace at the resolution of the main camera's render target
+            let window_size = *world.resource::<WindowSize>();
+            let Some((width, height)) = world
+                .query::<&Camera>()
+                .iter(&world)
+                .find(|camera| camera.is_main)
+                .map(|camera| camera.target_size(&window_size))
+            else {
+                return Ok(());
+            };
 
             // Check if scene exists and get the spheres/lights buffers
             let scene_buffers = {
@@ -623,7 +484,7 @@ impl Layer for RaytracerLayer {
                 height
             );
 
-            // Create output texture - use Rgba8Unorm for storage writes
+            // Create output texture - linear HDR, tonemapped into the camera's render target
             let texture = self.device.create_texture(&wgpu::TextureDescriptor {
                 label: Some("Raytracer Output Texture"),
                 size: wgpu::Extent3d {
@@ -634,7 +495,7 @@ impl Layer for RaytracerLayer {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
+                format: HDR_FORMAT,
                 usage: wgpu::TextureUsages::STORAGE_BINDING
                     | wgpu::TextureUsages::TEXTURE_BINDING
                     | wgpu::TextureUsages::RENDER_ATTACHMENT
@@ -686,7 +547,27 @@ impl Layer for RaytracerLayer {
             let accumulation_view_b =
                 accumulation_texture_b.create_view(&wgpu::TextureViewDescriptor::default());
 
-            // Find the main camera entity and update its render target
+            // Display target the output is tonemapped into, presented by the window layer like
+            // any camera's render target
+            let display_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Raytracer Display Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+
+            // Find the main camera entity and update its render targets
             {
                 let mut world = context.world.lock().unwrap();
                 let camera_entity = {
@@ -698,39 +579,25 @@ impl Layer for RaytracerLayer {
                 };
 
                 if let Some(entity) = camera_entity {
-                    if let Some(mut render_target) = world.get_mut::<GpuRenderTarget>(entity) {
-                        render_target.texture = texture.clone();
-                    } else {
-                        world.entity_mut(entity).insert(GpuRenderTarget {
+                    world.entity_mut(entity).insert((
+                        GpuRenderTarget {
+                            texture: display_texture,
+                        },
+                        GpuHdrTarget {
                             texture: texture.clone(),
-                        });
-                    }
+                            view: view.clone(),
+                            multisampled: None,
+                        },
+                    ));
                 }
             }
 
-            // Create display bind group
-            let display_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Raytracer Display Bind Group"),
-                layout: &self.display_pipeline.get_bind_group_layout(0),
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.sampler),
-                    },
-                ],
-            });
-
             self.output_texture = Some(texture);
             self.output_view = Some(view);
             self.accumulation_texture_a = Some(accumulation_texture_a);
             self.accumulation_view_a = Some(accumulation_view_a);
             self.accumulation_texture_b = Some(accumulation_texture_b);
             self.accumulation_view_b = Some(accumulation_view_b);
-            self.display_bind_group = Some(display_bind_group);
         }
 
         // Recreate compute bind group every frame for ping-pong accumulation buffers
@@ -803,25 +670,19 @@ impl Layer for RaytracerLayer {
             let camera_moved = {
                 let mut world = context.world.lock().unwrap();
                 let mut camera_query = world.query::<(&Camera, &Transform)>();
-                if let Some((camera, transform)) =
-                    camera_query.iter(&world).find(|(cam, _)| cam.is_main)
+                if let Some((_, transform)) = camera_query.iter(&world).find(|(cam, _)| cam.is_main)
                 {
-                    let current_pos = Vector3::new(
-                        transform.position.x,
-                        transform.position.y,
-                        transform.position.z,
-                    );
-                    let current_target =
-                        Vector3::new(camera.target.x, camera.target.y, camera.target.z);
+                    let current_pos = transform.position.coords;
+                    let current_rotation = transform.rotation;
 
                     let moved = self.last_camera_position.map_or(true, |last_pos| {
                         (current_pos - last_pos).magnitude() > 0.001
-                    }) || self.last_camera_target.map_or(true, |last_target| {
-                        (current_target - last_target).magnitude() > 0.001
+                    }) || self.last_camera_rotation.map_or(true, |last_rotation| {
+                        last_rotation.angle_to(&current_rotation) > 0.0001
                     });
 
                     self.last_camera_position = Some(current_pos);
-                    self.last_camera_target = Some(current_target);
+                    self.last_camera_rotation = Some(current_rotation);
                     moved
                 } else {
                     false
@@ -850,9 +711,17 @@ impl Layer for RaytracerLayer {
                 );
 
                 // Run compute shader to raytrace the scene
+                let timestamps = context
+                    .world
+                    .lock()
+                    .unwrap()
+                    .get_resource::<GpuProfiler>()
+                    .and_then(|profiler| profiler.begin_pass("Raytracer Compute Pass"));
                 let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
                     label: Some("Raytracer Compute Pass"),
-                    timestamp_writes: None,
+                    timestamp_writes: timestamps
+                        .as_ref()
+                        .map(GpuPassTimestamps::compute_pass_writes),
                 });
 
                 compute_pass.set_pipeline(&self.compute_pipeline);
@@ -873,6 +742,63 @@ impl Layer for RaytracerLayer {
                 // No need to copy - shader writes directly to accumulation_output (binding 9)
             }
 
+            // Post process and tonemap the linear output into the main camera's render target
+            {
+                let mut world = context.world.lock().unwrap();
+                let mut camera_query = world.query::<(
+                    Entity,
+                    &Camera,
+                    &GpuHdrTarget,
+                    &GpuRenderTarget,
+                    Option<&Tonemapping>,
+                    Option<&PostProcessStack>,
+                )>();
+                let shader_cache = world.get_resource::<ShaderCache>();
+                let no_effects = PostProcessStack::default();
+                self.post_processor
+                    .advance(context.delta_time.as_secs_f32());
+
+                if let Some((entity, _, hdr_target, target, tonemapping, post_process)) =
+                    camera_query
+                        .iter(&world)
+                        .find(|(_, camera, ..)| camera.is_main)
+                {
+                    let post_process = post_process.unwrap_or(&no_effects);
+                    self.post_processor.apply(
+                        &self.device,
+                        &self.queue,
+                        &mut encoder,
+                        shader_cache,
+                        entity,
+                        post_process,
+                        PostProcessStage::Hdr,
+                        &hdr_target.texture,
+                    );
+
+                    self.tonemapper.resolve(
+                        &self.device,
+                        &self.queue,
+                        &mut encoder,
+                        entity,
+                        tonemapping.unwrap_or(&Tonemapping::default()),
+                        hdr_target,
+                        &target.texture,
+                        context.delta_time.as_secs_f32(),
+                    );
+
+                    self.post_processor.apply(
+                        &self.device,
+                        &self.queue,
+                        &mut encoder,
+                        shader_cache,
+                        entity,
+                        post_process,
+                        PostProcessStage::Ldr,
+                        &target.texture,
+                    );
+                }
+            }
+
             self.queue.submit(std::iter::once(encoder.finish()));
         }
 
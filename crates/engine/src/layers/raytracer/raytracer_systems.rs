@@ -1,12 +1,11 @@
 use crate::prelude::*;
 
 use encase::{StorageBuffer, UniformBuffer};
-use image::ImageDecoder;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use wgpu::util::DeviceExt;
 
 /// System to collect all spheres and lights and create/update the GPU scene buffer
+// System parameters are injected by the scheduler, bundling them wouldn't make calls clearer
+#[allow(clippy::too_many_arguments)]
 pub fn update_raytracer_scene(
     mut commands: Commands,
     device: Res<GpuDevice>,
@@ -25,11 +24,8 @@ pub fn update_raytracer_scene(
     let spheres: Vec<RaytracerSphere> = sphere_query
         .iter()
         .map(|(sphere, transform)| RaytracerSphere {
-            center: Vector3::new(
-                transform.position.x,
-                transform.position.y,
-                transform.position.z,
-            ),
+            // The raytracer traces in single precision world space
+            center: transform.position.coords.cast(),
             radius: transform.scale.x, // Use x component of scale as radius
             color: Vector3::from_row_slice(&sphere.color),
             material_type: sphere.material_type,
@@ -40,11 +36,7 @@ pub fn update_raytracer_scene(
     let lights: Vec<RaytracerLight> = light_query
         .iter()
         .map(|(light, transform)| RaytracerLight {
-            position: Vector3::new(
-                transform.position.x,
-                transform.position.y,
-                transform.position.z,
-            ),
+            position: transform.position.coords.cast(),
             intensity: light.intensity,
             color: Vector3::from_row_slice(&light.color),
         })
@@ -167,21 +159,10 @@ pub fn update_raytracer_camera(
 ) {
     if let Some(buffer) = camera_buffer {
         if let Some((camera, transform)) = camera_query.iter().find(|(cam, _)| cam.is_main) {
-            let aspect_ratio = window_size.width as f32 / window_size.height as f32;
-
-            let camera_data = RaytracerCamera::new(
-                Vector3::new(
-                    transform.position.x,
-                    transform.position.y,
-                    transform.position.z,
-                ),
-                Vector3::new(camera.target.x, camera.target.y, camera.target.z),
-                Vector3::new(0.0, 1.0, 0.0),
-                camera.fovy.to_degrees(),
-                aspect_ratio,
-                camera.aperture,
-                camera.focus_distance,
-            );
+            let (width, height) = camera.target_size(&window_size);
+            let aspect_ratio = width as f32 / height as f32;
+
+            let camera_data = RaytracerCamera::new(camera, transform, aspect_ratio);
 
             let mut buffer_data = UniformBuffer::new(Vec::new());
             buffer_data.write(&camera_data).unwrap();
@@ -210,48 +191,12 @@ pub fn load_environment_map(
             continue;
         }
 
-        // Try to load as HDR first, fall back to regular image
-        let (width, height, data) = if let Ok(decoder) =
-            image::codecs::hdr::HdrDecoder::new(std::io::Cursor::new(&env_map.bytes))
-        {
-            let metadata = decoder.metadata();
-            let width = metadata.width;
-            let height = metadata.height;
-
-            // Read raw HDR data as bytes (RGB f32)
-            let total_bytes = decoder.total_bytes() as usize;
-            let mut raw_data = vec![0u8; total_bytes];
-            decoder.read_image(&mut raw_data).unwrap();
-
-            // Convert RGB f32 bytes to RGBA f32 bytes
-            let pixel_count = (width * height) as usize;
-            let mut rgba_data = Vec::with_capacity(pixel_count * 4 * 4);
-
-            for i in 0..pixel_count {
-                // Copy RGB (3 * 4 bytes)
-                let offset = i * 12; // 3 channels * 4 bytes
-                rgba_data.extend_from_slice(&raw_data[offset..offset + 12]);
-                // Add alpha = 1.0
-                rgba_data.extend_from_slice(&1.0f32.to_ne_bytes());
-            }
-
-            (width, height, rgba_data)
-        } else {
-            // Fall back to regular image loading
-            let image = image::load_from_memory(&env_map.bytes).unwrap();
-            let image = image.to_rgba8();
-            let (width, height) = image.dimensions();
-
-            // Convert to f32 and normalize to [0, 1]
-            let mut rgba_data = Vec::with_capacity(width as usize * height as usize * 4 * 4);
-            for pixel in image.pixels() {
-                rgba_data.extend_from_slice(&(pixel.0[0] as f32 / 255.0).to_ne_bytes());
-                rgba_data.extend_from_slice(&(pixel.0[1] as f32 / 255.0).to_ne_bytes());
-                rgba_data.extend_from_slice(&(pixel.0[2] as f32 / 255.0).to_ne_bytes());
-                rgba_data.extend_from_slice(&(pixel.0[3] as f32 / 255.0).to_ne_bytes());
+        let (width, height, data) = match env_map.decode() {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                log::error!("Failed to decode environment map: {}", err);
+                continue;
             }
-
-            (width, height, rgba_data)
         };
 
         let texture_size = wgpu::Extent3d {
@@ -298,10 +243,7 @@ pub fn load_environment_map(
             ..Default::default()
         });
 
-        // Calculate hash of bytes
-        let mut hasher = DefaultHasher::new();
-        env_map.bytes.hash(&mut hasher);
-        let bytes_hash = hasher.finish();
+        let bytes_hash = env_map.bytes_hash();
 
         commands.entity(entity).insert(GpuEnvironmentMap {
             texture,
@@ -322,10 +264,7 @@ pub fn reload_environment_map(
     query: Query<(Entity, &EnvironmentMap, &GpuEnvironmentMap), Changed<EnvironmentMap>>,
 ) {
     for (entity, env_map, gpu_env_map) in query.iter() {
-        // Calculate hash of current bytes
-        let mut hasher = DefaultHasher::new();
-        env_map.bytes.hash(&mut hasher);
-        let new_hash = hasher.finish();
+        let new_hash = env_map.bytes_hash();
 
         // Only reload if hash is different
         if new_hash == gpu_env_map.bytes_hash {
@@ -340,48 +279,12 @@ pub fn reload_environment_map(
             continue;
         }
 
-        // Try to load as HDR first, fall back to regular image
-        let (width, height, data) = if let Ok(decoder) =
-            image::codecs::hdr::HdrDecoder::new(std::io::Cursor::new(&env_map.bytes))
-        {
-            let metadata = decoder.metadata();
-            let width = metadata.width;
-            let height = metadata.height;
-
-            // Read raw HDR data as bytes (RGB f32)
-            let total_bytes = decoder.total_bytes() as usize;
-            let mut raw_data = vec![0u8; total_bytes];
-            decoder.read_image(&mut raw_data).unwrap();
-
-            // Convert RGB f32 bytes to RGBA f32 bytes
-            let pixel_count = (width * height) as usize;
-            let mut rgba_data = Vec::with_capacity(pixel_count * 4 * 4);
-
-            for i in 0..pixel_count {
-                // Copy RGB (3 * 4 bytes)
-                let offset = i * 12; // 3 channels * 4 bytes
-                rgba_data.extend_from_slice(&raw_data[offset..offset + 12]);
-                // Add alpha = 1.0
-                rgba_data.extend_from_slice(&1.0f32.to_ne_bytes());
+        let (width, height, data) = match env_map.decode() {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                log::error!("Failed to decode environment map: {}", err);
+                continue;
             }
-
-            (width, height, rgba_data)
-        } else {
-            // Fall back to regular image loading
-            let image = image::load_from_memory(&env_map.bytes).unwrap();
-            let image = image.to_rgba8();
-            let (width, height) = image.dimensions();
-
-            // Convert to f32 and normalize to [0, 1]
-            let mut rgba_data = Vec::with_capacity(width as usize * height as usize * 4 * 4);
-            for pixel in image.pixels() {
-                rgba_data.extend_from_slice(&(pixel.0[0] as f32 / 255.0).to_ne_bytes());
-                rgba_data.extend_from_slice(&(pixel.0[1] as f32 / 255.0).to_ne_bytes());
-                rgba_data.extend_from_slice(&(pixel.0[2] as f32 / 255.0).to_ne_bytes());
-                rgba_data.extend_from_slice(&(pixel.0[3] as f32 / 255.0).to_ne_bytes());
-            }
-
-            (width, height, rgba_data)
         };
 
         let texture_size = wgpu::Extent3d {
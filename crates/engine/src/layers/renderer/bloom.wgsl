@@ -0,0 +1,112 @@
+// Bloom - threshold the HDR image into a mip chain, blur it on the way down and back up,
+// then add the blurred light onto the image
+
+// Must match `PostEffectParams` in components/post_process.rs
+struct PostEffectParams {
+    texel_size: vec2<f32>,
+    time: f32,
+    frame: u32,
+    values: vec4<f32>,
+}
+
+@group(0) @binding(0) var source_texture: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> params: PostEffectParams;
+// Composite only, the top of the blurred mip chain
+@group(1) @binding(0) var bloom_texture: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // Fullscreen triangle
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Texture space has y pointing down
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+fn sample_offset(uv: vec2<f32>, texel: vec2<f32>, x: f32, y: f32) -> vec3<f32> {
+    return textureSampleLevel(source_texture, source_sampler, uv + texel * vec2<f32>(x, y), 0.0).rgb;
+}
+
+// 13 tap box filter from "Next Generation Post Processing in Call of Duty: Advanced Warfare"
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+
+    let a = sample_offset(uv, texel, -2.0, -2.0);
+    let b = sample_offset(uv, texel, 0.0, -2.0);
+    let c = sample_offset(uv, texel, 2.0, -2.0);
+    let d = sample_offset(uv, texel, -1.0, -1.0);
+    let e = sample_offset(uv, texel, 1.0, -1.0);
+    let f = sample_offset(uv, texel, -2.0, 0.0);
+    let g = sample_offset(uv, texel, 0.0, 0.0);
+    let h = sample_offset(uv, texel, 2.0, 0.0);
+    let i = sample_offset(uv, texel, -1.0, 1.0);
+    let j = sample_offset(uv, texel, 1.0, 1.0);
+    let k = sample_offset(uv, texel, -2.0, 2.0);
+    let l = sample_offset(uv, texel, 0.0, 2.0);
+    let m = sample_offset(uv, texel, 2.0, 2.0);
+
+    // Inner box weighs half, the four overlapping outer boxes share the rest
+    return (d + e + i + j) * 0.125
+        + (a + b + f + g) * 0.03125
+        + (b + c + g + h) * 0.03125
+        + (f + g + k + l) * 0.03125
+        + (g + h + l + m) * 0.03125;
+}
+
+// Keeps the light above the threshold, easing in over the knee
+fn threshold(color: vec3<f32>) -> vec3<f32> {
+    let threshold = params.values.x;
+    let knee = threshold * params.values.y;
+
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-5);
+    let contribution = max(soft, brightness - threshold) / max(brightness, 1e-5);
+    return color * contribution;
+}
+
+@fragment
+fn bloom_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(threshold(downsample(in.uv)), 1.0);
+}
+
+@fragment
+fn bloom_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// 3x3 tent filter, blended additively onto the next larger mip
+@fragment
+fn bloom_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+    let uv = in.uv;
+
+    var sum = sample_offset(uv, texel, 0.0, 0.0) * 4.0;
+    sum += (sample_offset(uv, texel, 0.0, -1.0)
+        + sample_offset(uv, texel, -1.0, 0.0)
+        + sample_offset(uv, texel, 1.0, 0.0)
+        + sample_offset(uv, texel, 0.0, 1.0)) * 2.0;
+    sum += sample_offset(uv, texel, -1.0, -1.0)
+        + sample_offset(uv, texel, 1.0, -1.0)
+        + sample_offset(uv, texel, -1.0, 1.0)
+        + sample_offset(uv, texel, 1.0, 1.0);
+    return vec4<f32>(sum / 16.0, 1.0);
+}
+
+@fragment
+fn bloom_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let intensity = params.values.z;
+
+    let color = textureSampleLevel(source_texture, source_sampler, in.uv, 0.0);
+    let bloom = textureSampleLevel(bloom_texture, source_sampler, in.uv, 0.0).rgb;
+    return vec4<f32>(color.rgb + bloom * intensity, color.a);
+}
@@ -0,0 +1,201 @@
+use crate::prelude::*;
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Draws the lines queued by `Gizmos` into a camera's HDR target
+///
+/// Uses line list topology, so it doesn't need `POLYGON_MODE_LINE`. Lines without depth testing
+/// are drawn after the others, on top of everything.
+pub struct GizmoRenderer {
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    /// Pipelines by sample count and depth mode of the camera targets and depth testing,
+    /// created on first use
+    pipelines: HashMap<(u32, DepthMode, bool), wgpu::RenderPipeline>,
+    /// Grown to fit the lines of the frame
+    vertex_buffer: Option<wgpu::Buffer>,
+    depth_tested: Range<u32>,
+    on_top: Range<u32>,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GizmoVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl GizmoVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GizmoVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+impl GizmoRenderer {
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gizmo Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Gizmo Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gizmos.wgsl").into()),
+        });
+
+        Self {
+            pipeline_layout,
+            shader,
+            pipelines: HashMap::new(),
+            vertex_buffer: None,
+            depth_tested: 0..0,
+            on_top: 0..0,
+        }
+    }
+
+    /// Upload the lines of this frame relative to `origin`, shared by every camera
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lines: &[GizmoLine],
+        origin: &RenderOrigin,
+    ) {
+        let (depth_tested, on_top): (Vec<&GizmoLine>, Vec<&GizmoLine>) =
+            lines.iter().partition(|line| line.depth_test);
+        let vertices: Vec<GizmoVertex> = depth_tested
+            .iter()
+            .chain(&on_top)
+            .flat_map(|line| {
+                [line.start, line.end].map(|point| GizmoVertex {
+                    position: origin.relative(&point.cast()).into(),
+                    color: line.color,
+                })
+            })
+            .collect();
+
+        let split = depth_tested.len() as u32 * 2;
+        self.depth_tested = 0..split;
+        self.on_top = split..vertices.len() as u32;
+        if vertices.is_empty() {
+            return;
+        }
+
+        let contents: &[u8] = bytemuck::cast_slice(&vertices);
+        let fits = self
+            .vertex_buffer
+            .as_ref()
+            .is_some_and(|buffer| buffer.size() >= contents.len() as u64);
+        if !fits {
+            self.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Gizmo Vertex Buffer"),
+                size: (contents.len() as u64).next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        queue.write_buffer(self.vertex_buffer.as_ref().unwrap(), 0, contents);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth_tested.is_empty() && self.on_top.is_empty()
+    }
+
+    /// Draw into a pass over a camera's HDR and depth targets, after its scene geometry
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        render_pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        sample_count: u32,
+        depth_mode: DepthMode,
+    ) {
+        let Some(vertex_buffer) = &self.vertex_buffer else {
+            return;
+        };
+
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        for (vertices, depth_test) in [(&self.depth_tested, true), (&self.on_top, false)] {
+            if vertices.is_empty() {
+                continue;
+            }
+            let pipeline = self
+                .pipelines
+                .entry((sample_count, depth_mode, depth_test))
+                .or_insert_with(|| {
+                    create_gizmo_pipeline(
+                        device,
+                        &self.pipeline_layout,
+                        &self.shader,
+                        sample_count,
+                        depth_mode,
+                        depth_test,
+                    )
+                });
+            render_pass.set_pipeline(pipeline);
+            render_pass.draw(vertices.clone(), 0..1);
+        }
+    }
+}
+
+fn create_gizmo_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    sample_count: u32,
+    depth_mode: DepthMode,
+    depth_test: bool,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Gizmo Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vertex"),
+            buffers: &[GizmoVertex::desc()],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fragment"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        },
+        // Lines never write depth, so gizmos don't hide each other or later passes
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare: if depth_test {
+                depth_mode.compare(wgpu::CompareFunction::LessEqual)
+            } else {
+                wgpu::CompareFunction::Always
+            },
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
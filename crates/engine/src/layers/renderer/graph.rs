@@ -0,0 +1,505 @@
+use crate::prelude::*;
+
+use std::collections::HashMap;
+
+use super::render_layer::CameraView;
+
+/// Slots of the built-in nodes, every camera runs the graph with these imported
+pub mod slot {
+    /// Cascaded shadow map of the camera's directional light
+    pub const SHADOW_MAP: &str = "shadow_map";
+    /// Indirect draw arguments of the instances surviving the culling pass, has no resource
+    pub const CULLED_INSTANCES: &str = "culled_instances";
+    /// Linear HDR colour, the resolved `GpuHdrTarget`
+    pub const HDR: &str = "hdr";
+    /// Scene depth, the `GpuDepthTexture`
+    pub const DEPTH: &str = "depth";
+    /// Display ready colour, the `GpuRenderTarget`
+    pub const TARGET: &str = "target";
+}
+
+/// One pass of a `RenderGraph`
+///
+/// Nodes run once per camera, in an order derived from the slots they read and write, see
+/// `RenderGraph`.
+pub trait RenderNode: Send + Sync + 'static {
+    /// Slots the node reads, writes and creates, queried once when the node is added
+    fn slots(&self) -> NodeSlots;
+
+    /// Called once per frame before any camera, to upload data shared by every camera
+    fn prepare(
+        &mut self,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        _world: &mut World,
+        _delta_time: f32,
+    ) {
+    }
+
+    /// Record the node's passes for `context.camera`
+    fn run(&mut self, context: &mut RenderContext);
+
+    /// Called once per frame after every camera, to drop the state of despawned cameras
+    fn cleanup(&mut self, _world: &World) {}
+}
+
+/// Texture a node creates for the later nodes of the same camera
+///
+/// The graph allocates it, sized from the camera's target, and keeps it across frames while
+/// the size and description stay the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransientTexture {
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+    /// Divides the camera target size, 1 for full resolution
+    pub downscale: u32,
+    pub sample_count: u32,
+}
+
+impl TransientTexture {
+    pub fn new(format: wgpu::TextureFormat, usage: wgpu::TextureUsages) -> Self {
+        Self {
+            format,
+            usage,
+            downscale: 1,
+            sample_count: 1,
+        }
+    }
+
+    fn size(&self, target_size: (u32, u32)) -> (u32, u32) {
+        let downscale = self.downscale.max(1);
+        (
+            (target_size.0 / downscale).max(1),
+            (target_size.1 / downscale).max(1),
+        )
+    }
+}
+
+/// Buffer a node creates for the later nodes of the same camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransientBuffer {
+    pub size: u64,
+    pub usage: wgpu::BufferUsages,
+}
+
+/// Slots a node reads and writes
+///
+/// Writing a slot includes reading it first, as passes loading a target do. Slots don't need
+/// a resource, a node can write one only to run before its readers.
+#[derive(Debug, Clone, Default)]
+pub struct NodeSlots {
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+    textures: Vec<(&'static str, TransientTexture)>,
+    buffers: Vec<(&'static str, TransientBuffer)>,
+}
+
+impl NodeSlots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(mut self, slot: &'static str) -> Self {
+        self.reads.push(slot);
+        self
+    }
+
+    pub fn write(mut self, slot: &'static str) -> Self {
+        self.writes.push(slot);
+        self
+    }
+
+    /// Write a texture the graph allocates
+    pub fn create_texture(mut self, slot: &'static str, texture: TransientTexture) -> Self {
+        self.textures.push((slot, texture));
+        self.write(slot)
+    }
+
+    /// Write a buffer the graph allocates
+    pub fn create_buffer(mut self, slot: &'static str, buffer: TransientBuffer) -> Self {
+        self.buffers.push((slot, buffer));
+        self.write(slot)
+    }
+
+    fn writes(&self, slot: &str) -> bool {
+        self.writes.contains(&slot)
+    }
+}
+
+/// Texture bound to a slot, the texture itself and a view of all of it
+#[derive(Debug, Clone)]
+pub struct GraphTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl GraphTexture {
+    pub fn new(texture: &wgpu::Texture) -> Self {
+        Self {
+            texture: texture.clone(),
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        }
+    }
+}
+
+/// Resources bound to the slots of one camera's graph run
+#[derive(Default)]
+pub struct GraphResources {
+    textures: HashMap<&'static str, GraphTexture>,
+    buffers: HashMap<&'static str, wgpu::Buffer>,
+}
+
+impl GraphResources {
+    pub fn import_texture(&mut self, slot: &'static str, texture: GraphTexture) {
+        self.textures.insert(slot, texture);
+    }
+
+    pub fn import_buffer(&mut self, slot: &'static str, buffer: wgpu::Buffer) {
+        self.buffers.insert(slot, buffer);
+    }
+
+    pub fn texture(&self, slot: &str) -> Option<&GraphTexture> {
+        self.textures.get(slot)
+    }
+
+    pub fn buffer(&self, slot: &str) -> Option<&wgpu::Buffer> {
+        self.buffers.get(slot)
+    }
+}
+
+/// Everything a node records its passes with
+pub struct RenderContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub encoder: wgpu::CommandEncoder,
+    pub world: &'a World,
+    /// Camera the graph runs for
+    pub camera: Entity,
+    pub delta_time: f32,
+    pub resources: GraphResources,
+    /// Size of the camera's render targets
+    pub target_size: (u32, u32),
+    /// Draws sorted for this camera, for the built-in scene nodes
+    pub(crate) view: &'a CameraView<'a>,
+    /// Readbacks to map once the encoder was submitted
+    pub(crate) readbacks: Vec<&'a VisibleCountReadback>,
+    pub(crate) profiler: Option<&'a GpuProfiler>,
+}
+
+impl RenderContext<'_> {
+    /// Timestamp queries for a pass while profiling, see `GpuProfiler`
+    pub fn pass_timestamps(&self, name: &str) -> Option<GpuPassTimestamps> {
+        self.profiler.and_then(|profiler| profiler.begin_pass(name))
+    }
+}
+
+/// Named nodes run per camera in dependency order
+///
+/// Writers of a slot run in the order they were added, readers after every writer of the slots
+/// they read. `add_node_before` and `add_node_after` place a node among the writers, e.g. an
+/// atmosphere pass writing `slot::HDR` before `TransparentNode::NAME`.
+#[derive(Resource, Default)]
+pub struct RenderGraph {
+    nodes: Vec<GraphNode>,
+    /// Execution order by node index, rebuilt when nodes change
+    order: Option<Vec<usize>>,
+    /// Transient textures and buffers by camera and slot
+    textures: HashMap<(Entity, &'static str), GraphTexture>,
+    buffers: HashMap<(Entity, &'static str), (TransientBuffer, wgpu::Buffer)>,
+}
+
+struct GraphNode {
+    name: String,
+    slots: NodeSlots,
+    node: Box<dyn RenderNode>,
+}
+
+impl RenderGraph {
+    /// Add a node after every other node
+    pub fn add_node(&mut self, name: impl Into<String>, node: impl RenderNode) -> &mut Self {
+        self.insert(self.nodes.len(), name.into(), node)
+    }
+
+    /// Add a node just before `before`, or last if there is none
+    pub fn add_node_before(
+        &mut self,
+        before: &str,
+        name: impl Into<String>,
+        node: impl RenderNode,
+    ) -> &mut Self {
+        let index = self.position(before).unwrap_or(self.nodes.len());
+        self.insert(index, name.into(), node)
+    }
+
+    /// Add a node just after `after`, or last if there is none
+    pub fn add_node_after(
+        &mut self,
+        after: &str,
+        name: impl Into<String>,
+        node: impl RenderNode,
+    ) -> &mut Self {
+        let index = self
+            .position(after)
+            .map_or(self.nodes.len(), |index| index + 1);
+        self.insert(index, name.into(), node)
+    }
+
+    pub fn remove_node(&mut self, name: &str) -> Option<Box<dyn RenderNode>> {
+        let index = self.position(name)?;
+        self.order = None;
+        Some(self.nodes.remove(index).node)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Node names in the order they run
+    pub fn execution_order(&mut self) -> Result<Vec<&str>> {
+        let order = self.order()?;
+        Ok(order
+            .iter()
+            .map(|&index| self.nodes[index].name.as_str())
+            .collect())
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    fn insert(&mut self, index: usize, name: String, node: impl RenderNode) -> &mut Self {
+        if let Some(existing) = self.position(&name) {
+            log::warn!("Replacing render graph node '{}'", name);
+            self.nodes.remove(existing);
+        }
+        let index = index.min(self.nodes.len());
+        self.nodes.insert(
+            index,
+            GraphNode {
+                name,
+                slots: node.slots(),
+                node: Box::new(node),
+            },
+        );
+        self.order = None;
+        self
+    }
+
+    fn order(&mut self) -> Result<Vec<usize>> {
+        if self.order.is_none() {
+            let slots: Vec<&NodeSlots> = self.nodes.iter().map(|node| &node.slots).collect();
+            let order = execution_order(&slots).map_err(|index| {
+                anyhow::anyhow!(
+                    "Render graph node '{}' depends on itself through its slots",
+                    self.nodes[index].name
+                )
+            })?;
+            self.order = Some(order);
+        }
+        Ok(self.order.clone().unwrap_or_default())
+    }
+
+    pub(crate) fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        world: &mut World,
+        delta_time: f32,
+    ) {
+        for node in &mut self.nodes {
+            node.node.prepare(device, queue, world, delta_time);
+        }
+    }
+
+    /// Allocate the transient resources of `context.camera` and run every node
+    pub(crate) fn run(&mut self, context: &mut RenderContext) {
+        let order = match self.order() {
+            Ok(order) => order,
+            Err(error) => {
+                log::error!("{}", error);
+                return;
+            }
+        };
+
+        let textures: Vec<_> = self
+            .nodes
+            .iter()
+            .flat_map(|node| node.slots.textures.clone())
+            .collect();
+        for (slot, description) in textures {
+            let texture = self.transient_texture(
+                context.device,
+                context.camera,
+                slot,
+                description,
+                context.target_size,
+            );
+            context.resources.import_texture(slot, texture);
+        }
+        let buffers: Vec<_> = self
+            .nodes
+            .iter()
+            .flat_map(|node| node.slots.buffers.clone())
+            .collect();
+        for (slot, description) in buffers {
+            let buffer = self.transient_buffer(context.device, context.camera, slot, description);
+            context.resources.import_buffer(slot, buffer);
+        }
+
+        for index in order {
+            self.nodes[index].node.run(context);
+        }
+    }
+
+    /// Drop the resources of despawned cameras
+    pub(crate) fn cleanup(&mut self, world: &World) {
+        let alive = |camera: Entity| world.get::<Camera>(camera).is_some();
+        self.textures.retain(|(camera, _), _| alive(*camera));
+        self.buffers.retain(|(camera, _), _| alive(*camera));
+        for node in &mut self.nodes {
+            node.node.cleanup(world);
+        }
+    }
+
+    fn transient_texture(
+        &mut self,
+        device: &wgpu::Device,
+        camera: Entity,
+        slot: &'static str,
+        description: TransientTexture,
+        target_size: (u32, u32),
+    ) -> GraphTexture {
+        let size = description.size(target_size);
+        let matches = |texture: &wgpu::Texture| {
+            (texture.width(), texture.height()) == size
+                && texture.format() == description.format
+                && texture.usage() == description.usage
+                && texture.sample_count() == description.sample_count
+        };
+        let texture = self
+            .textures
+            .entry((camera, slot))
+            .and_modify(|texture| {
+                if !matches(&texture.texture) {
+                    *texture = create_transient_texture(device, slot, description, size);
+                }
+            })
+            .or_insert_with(|| create_transient_texture(device, slot, description, size));
+        texture.clone()
+    }
+
+    fn transient_buffer(
+        &mut self,
+        device: &wgpu::Device,
+        camera: Entity,
+        slot: &'static str,
+        description: TransientBuffer,
+    ) -> wgpu::Buffer {
+        let create = || {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(slot),
+                size: description.size,
+                usage: description.usage,
+                mapped_at_creation: false,
+            })
+        };
+        let (_, buffer) = self
+            .buffers
+            .entry((camera, slot))
+            .and_modify(|entry| {
+                if entry.0 != description {
+                    *entry = (description, create());
+                }
+            })
+            .or_insert_with(|| (description, create()));
+        buffer.clone()
+    }
+}
+
+fn create_transient_texture(
+    device: &wgpu::Device,
+    slot: &'static str,
+    description: TransientTexture,
+    (width, height): (u32, u32),
+) -> GraphTexture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(slot),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: description.sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: description.format,
+        usage: description.usage,
+        view_formats: &[],
+    });
+    GraphTexture::new(&texture)
+}
+
+/// Node indices in execution order, or the index of a node on a dependency cycle
+///
+/// Writers of a slot run in insertion order, readers after all its writers. Among nodes free to
+/// run, the earliest added goes first.
+fn execution_order(nodes: &[&NodeSlots]) -> std::result::Result<Vec<usize>, usize> {
+    let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+
+    for (index, node) in nodes.iter().enumerate() {
+        for &slot in &node.writes {
+            // The previous writer of the slot
+            if let Some(previous) = (0..index).rev().find(|&other| nodes[other].writes(slot)) {
+                dependencies[index].push(previous);
+            }
+        }
+        for &slot in &node.reads {
+            if node.writes(slot) {
+                continue;
+            }
+            dependencies[index].extend((0..nodes.len()).filter(|&other| nodes[other].writes(slot)));
+        }
+    }
+
+    let mut order = Vec::with_capacity(nodes.len());
+    let mut done = vec![false; nodes.len()];
+    while order.len() < nodes.len() {
+        let ready = (0..nodes.len())
+            .find(|&index| !done[index] && dependencies[index].iter().all(|&other| done[other]));
+        let Some(index) = ready else {
+            return Err((0..nodes.len()).find(|&index| !done[index]).unwrap_or(0));
+        };
+        done[index] = true;
+        order.push(index);
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readers_run_after_every_writer() {
+        let tonemap = NodeSlots::new().read(slot::HDR).write(slot::TARGET);
+        let opaque = NodeSlots::new().write(slot::HDR).write(slot::DEPTH);
+        let atmosphere = NodeSlots::new().read(slot::DEPTH).write(slot::HDR);
+        let shadow = NodeSlots::new().write(slot::SHADOW_MAP);
+        let transparent = NodeSlots::new()
+            .read(slot::SHADOW_MAP)
+            .read(slot::DEPTH)
+            .write(slot::HDR);
+
+        let order = execution_order(&[&tonemap, &opaque, &atmosphere, &shadow, &transparent]);
+
+        assert_eq!(order, Ok(vec![1, 2, 3, 4, 0]));
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let a = NodeSlots::new().read("b").write("a");
+        let b = NodeSlots::new().read("a").write("b");
+
+        assert!(execution_order(&[&a, &b]).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use crate::prelude::*;
+
+use wgpu::util::DeviceExt;
+
+/// Face size of the environment cubemap the skybox draws and the bakes sample
+const ENVIRONMENT_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const SPECULAR_SIZE: u32 = 128;
+/// Roughness steps of the prefiltered specular map, 128 down to 8 texels
+const SPECULAR_MIPS: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 256;
+
+const CUBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Uniform of the bake passes
+#[derive(ShaderType)]
+struct BakeParams {
+    roughness: f32,
+    /// Face size of the environment cubemap's top mip
+    source_size: f32,
+}
+
+// Keep the params layout in sync with the `BakeParams` struct of the IBL shader
+const _: () = {
+    use crate::shader::layouts::ibl;
+    use encase::ShaderSize;
+
+    assert!(BakeParams::SHADER_SIZE.get() == ibl::bake_params::SIZE as u64);
+};
+
+/// Bakes the image based lighting of an `EnvironmentMap` into a `GpuEnvironmentLighting`
+///
+/// The BRDF lookup table doesn't depend on the environment, it is baked once on creation and
+/// shared by every result.
+#[derive(Resource)]
+pub struct IblBaker {
+    equirect_pipeline: wgpu::ComputePipeline,
+    downsample_pipeline: wgpu::ComputePipeline,
+    irradiance_pipeline: wgpu::ComputePipeline,
+    prefilter_pipeline: wgpu::ComputePipeline,
+    brdf_lut_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+}
+
+impl IblBaker {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("IBL Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("ibl.wgsl").into()),
+        });
+
+        // Each pass binds a different subset of the shader's resources, so the layouts are
+        // derived from the entry points
+        let create_pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: None,
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+        let equirect_pipeline = create_pipeline("Equirect To Cube Pipeline", "equirect_to_cube");
+        let downsample_pipeline = create_pipeline("Cube Downsample Pipeline", "downsample_cube");
+        let irradiance_pipeline = create_pipeline("Irradiance Pipeline", "irradiance");
+        let prefilter_pipeline =
+            create_pipeline("Specular Prefilter Pipeline", "prefilter_specular");
+        let brdf_lut_pipeline = create_pipeline("BRDF LUT Pipeline", "brdf_lut");
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Environment Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let brdf_lut = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("BRDF LUT"),
+            size: wgpu::Extent3d {
+                width: BRDF_LUT_SIZE,
+                height: BRDF_LUT_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CUBE_FORMAT,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let brdf_lut_view = brdf_lut.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("BRDF LUT Encoder"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("brdf_lut_bind_group"),
+            layout: &brdf_lut_pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(&brdf_lut_view),
+            }],
+        });
+        dispatch(
+            &mut encoder,
+            "BRDF LUT Pass",
+            &brdf_lut_pipeline,
+            &bind_group,
+            BRDF_LUT_SIZE,
+            1,
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Self {
+            equirect_pipeline,
+            downsample_pipeline,
+            irradiance_pipeline,
+            prefilter_pipeline,
+            brdf_lut_view,
+            sampler,
+        }
+    }
+
+    /// Lighting without an environment map, black cubemaps that add no ambient light
+    pub fn fallback(&self, device: &wgpu::Device) -> GpuEnvironmentLighting {
+        // Textures start out zeroed
+        let black = create_cube(device, "Black Environment", 1, 1);
+        let view = cube_view(&black);
+
+        GpuEnvironmentLighting {
+            environment_view: view.clone(),
+            irradiance_view: view.clone(),
+            specular_view: view,
+            specular_mips: 1,
+            brdf_lut_view: self.brdf_lut_view.clone(),
+            sampler: self.sampler.clone(),
+            source_hash: None,
+        }
+    }
+
+    /// Convert an environment map into a cubemap and bake its irradiance and specular maps
+    pub fn bake(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        environment: &EnvironmentMap,
+    ) -> Result<GpuEnvironmentLighting, String> {
+        let (width, height, data) = environment
+            .decode()
+            .map_err(|err| format!("Failed to decode environment map: {}", err))?;
+
+        let max_size = device.limits().max_texture_dimension_2d;
+        if width > max_size || height > max_size {
+            return Err(format!(
+                "Environment map is {}x{}, the device supports up to {}x{}",
+                width, height, max_size, max_size
+            ));
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let equirect = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Equirect Environment"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &equirect,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(16 * width), // 4 channels * 4 bytes per f32
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        let equirect_view = equirect.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let environment_mips = ENVIRONMENT_SIZE.ilog2() + 1;
+        let environment_cube = create_cube(
+            device,
+            "Environment Cubemap",
+            ENVIRONMENT_SIZE,
+            environment_mips,
+        );
+        let irradiance = create_cube(device, "Irradiance Cubemap", IRRADIANCE_SIZE, 1);
+        let specular = create_cube(device, "Specular Cubemap", SPECULAR_SIZE, SPECULAR_MIPS);
+        let environment_view = cube_view(&environment_cube);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("IBL Bake Encoder"),
+        });
+
+        // Environment cubemap and its mip chain, sampled by the convolutions below
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("equirect_to_cube_bind_group"),
+            layout: &self.equirect_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&equirect_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&mip_view(&environment_cube, 0)),
+                },
+            ],
+        });
+        dispatch(
+            &mut encoder,
+            "Equirect To Cube Pass",
+            &self.equirect_pipeline,
+            &bind_group,
+            ENVIRONMENT_SIZE,
+            6,
+        );
+
+        for mip in 1..environment_mips {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("downsample_cube_bind_group"),
+                layout: &self.downsample_pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&mip_view(
+                            &environment_cube,
+                            mip - 1,
+                        )),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(&mip_view(
+                            &environment_cube,
+                            mip,
+                        )),
+                    },
+                ],
+            });
+            dispatch(
+                &mut encoder,
+                "Cube Downsample Pass",
+                &self.downsample_pipeline,
+                &bind_group,
+                ENVIRONMENT_SIZE >> mip,
+                6,
+            );
+        }
+
+        // Convolutions of the environment, one pass per output mip
+        let mut convolve = |label,
+                            pipeline: &wgpu::ComputePipeline,
+                            output: &wgpu::TextureView,
+                            size: u32,
+                            roughness| {
+            let params = BakeParams {
+                roughness,
+                source_size: ENVIRONMENT_SIZE as f32,
+            };
+            let mut data = UniformBuffer::new(Vec::new());
+            data.write(&params).unwrap();
+            let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("IBL Bake Params Buffer"),
+                contents: &data.into_inner(),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("ibl_convolution_bind_group"),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&environment_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(output),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+            dispatch(&mut encoder, label, pipeline, &bind_group, size, 6);
+        };
+
+        convolve(
+            "Irradiance Pass",
+            &self.irradiance_pipeline,
+            &mip_view(&irradiance, 0),
+            IRRADIANCE_SIZE,
+            0.0,
+        );
+        for mip in 0..SPECULAR_MIPS {
+            convolve(
+                "Specular Prefilter Pass",
+                &self.prefilter_pipeline,
+                &mip_view(&specular, mip),
+                SPECULAR_SIZE >> mip,
+                mip as f32 / (SPECULAR_MIPS - 1) as f32,
+            );
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(GpuEnvironmentLighting {
+            environment_view,
+            irradiance_view: cube_view(&irradiance),
+            specular_view: cube_view(&specular),
+            specular_mips: SPECULAR_MIPS,
+            brdf_lut_view: self.brdf_lut_view.clone(),
+            sampler: self.sampler.clone(),
+            source_hash: Some(environment.bytes_hash()),
+        })
+    }
+}
+
+fn create_cube(device: &wgpu::Device, label: &str, size: u32, mips: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count: mips,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: CUBE_FORMAT,
+        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+/// Every mip of a cubemap, for sampling
+fn cube_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        ..Default::default()
+    })
+}
+
+/// All six faces of a single cubemap mip, for writing or loading texels
+fn mip_view(texture: &wgpu::Texture, mip: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        base_mip_level: mip,
+        mip_level_count: Some(1),
+        ..Default::default()
+    })
+}
+
+/// Run a pass over a square output of `size` texels with `layers` faces
+fn dispatch(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::ComputePipeline,
+    bind_group: &wgpu::BindGroup,
+    size: u32,
+    layers: u32,
+) {
+    let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some(label),
+        timestamp_writes: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.dispatch_workgroups(size.div_ceil(8), size.div_ceil(8), layers);
+}
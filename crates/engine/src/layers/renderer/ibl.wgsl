@@ -0,0 +1,245 @@
+// Image based lighting - converts the environment map into a cubemap, then bakes its irradiance,
+// its GGX prefiltered specular mips and the split sum BRDF lookup table
+
+const PI: f32 = 3.14159265359;
+
+// Must match `BakeParams` in layers/renderer/ibl.rs
+struct BakeParams {
+    roughness: f32,
+    // Face size of the environment cubemap's top mip
+    source_size: f32,
+}
+
+// Every resource has its own binding, each pass only binds the ones its entry point uses
+@group(0) @binding(0) var equirect_texture: texture_2d<f32>;
+@group(0) @binding(1) var source_mip: texture_2d_array<f32>;
+@group(0) @binding(2) var environment_texture: texture_cube<f32>;
+@group(0) @binding(3) var environment_sampler: sampler;
+@group(0) @binding(4) var cube_output: texture_storage_2d_array<rgba16float, write>;
+@group(0) @binding(5) var lut_output: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(6) var<uniform> params: BakeParams;
+
+// World direction through the centre of a cube face texel, in wgpu's face order and orientation
+fn cube_direction(face: u32, texel: vec2<u32>, size: u32) -> vec3<f32> {
+    let uv = (vec2<f32>(texel) + 0.5) / f32(size) * 2.0 - 1.0;
+
+    var direction: vec3<f32>;
+    switch face {
+        case 0u: { direction = vec3<f32>(1.0, -uv.y, -uv.x); }
+        case 1u: { direction = vec3<f32>(-1.0, -uv.y, uv.x); }
+        case 2u: { direction = vec3<f32>(uv.x, 1.0, uv.y); }
+        case 3u: { direction = vec3<f32>(uv.x, -1.0, -uv.y); }
+        case 4u: { direction = vec3<f32>(uv.x, -uv.y, 1.0); }
+        default: { direction = vec3<f32>(-uv.x, -uv.y, -1.0); }
+    }
+    return normalize(direction);
+}
+
+fn is_outside_cube(id: vec3<u32>) -> bool {
+    let size = textureDimensions(cube_output).x;
+    return id.x >= size || id.y >= size;
+}
+
+// Rotates a direction around +Z onto the hemisphere around `normal`
+fn tangent_to_world(local: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(normal.y) < 0.999);
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return tangent * local.x + bitangent * local.y + normal * local.z;
+}
+
+// === Equirectangular to Cubemap ===
+
+fn equirect_texel(pixel: vec2<i32>, size: vec2<i32>) -> vec4<f32> {
+    // Longitude wraps around, latitude stops at the poles
+    let wrapped = vec2<i32>((pixel.x % size.x + size.x) % size.x, clamp(pixel.y, 0, size.y - 1));
+    return textureLoad(equirect_texture, wrapped, 0);
+}
+
+// Bilinear lookup by hand, f32 textures can't be filtered on every device
+// Uses the same mapping as `get_environment_color` of the raytracer
+fn sample_equirect(direction: vec3<f32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(equirect_texture));
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        1.0 - (asin(clamp(direction.y, -1.0, 1.0)) / PI + 0.5),
+    );
+
+    let position = uv * vec2<f32>(size) - 0.5;
+    let base = vec2<i32>(floor(position));
+    let t = fract(position);
+    let top = mix(equirect_texel(base, size), equirect_texel(base + vec2<i32>(1, 0), size), t.x);
+    let bottom = mix(
+        equirect_texel(base + vec2<i32>(0, 1), size),
+        equirect_texel(base + vec2<i32>(1, 1), size),
+        t.x,
+    );
+    return mix(top, bottom, t.y).rgb;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn equirect_to_cube(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (is_outside_cube(id)) {
+        return;
+    }
+
+    let direction = cube_direction(id.z, id.xy, textureDimensions(cube_output).x);
+    textureStore(cube_output, id.xy, id.z, vec4<f32>(sample_equirect(direction), 1.0));
+}
+
+// Box filters the previous mip of every face into the next
+@compute @workgroup_size(8, 8, 1)
+fn downsample_cube(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (is_outside_cube(id)) {
+        return;
+    }
+
+    let source = vec2<i32>(id.xy * 2u);
+    let face = i32(id.z);
+    let color = textureLoad(source_mip, source, face, 0)
+        + textureLoad(source_mip, source + vec2<i32>(1, 0), face, 0)
+        + textureLoad(source_mip, source + vec2<i32>(0, 1), face, 0)
+        + textureLoad(source_mip, source + vec2<i32>(1, 1), face, 0);
+    textureStore(cube_output, id.xy, id.z, color * 0.25);
+}
+
+// === Irradiance ===
+
+const IRRADIANCE_PHI_STEPS: u32 = 64u;
+const IRRADIANCE_THETA_STEPS: u32 = 16u;
+
+// Cosine weighted integral of the hemisphere around each direction, on a regular grid
+@compute @workgroup_size(8, 8, 1)
+fn irradiance(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (is_outside_cube(id)) {
+        return;
+    }
+
+    let normal = cube_direction(id.z, id.xy, textureDimensions(cube_output).x);
+    // A mip about as coarse as the grid, so the sparse samples still see every texel
+    let level = max(log2(params.source_size / f32(IRRADIANCE_THETA_STEPS)), 0.0);
+
+    var total = vec3<f32>(0.0);
+    for (var i = 0u; i < IRRADIANCE_PHI_STEPS; i++) {
+        let phi = (f32(i) + 0.5) / f32(IRRADIANCE_PHI_STEPS) * 2.0 * PI;
+        for (var j = 0u; j < IRRADIANCE_THETA_STEPS; j++) {
+            let theta = (f32(j) + 0.5) / f32(IRRADIANCE_THETA_STEPS) * 0.5 * PI;
+            let local = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let direction = tangent_to_world(local, normal);
+            let radiance =
+                textureSampleLevel(environment_texture, environment_sampler, direction, level).rgb;
+            total += radiance * cos(theta) * sin(theta);
+        }
+    }
+
+    let irradiance = total * PI / f32(IRRADIANCE_PHI_STEPS * IRRADIANCE_THETA_STEPS);
+    textureStore(cube_output, id.xy, id.z, vec4<f32>(irradiance, 1.0));
+}
+
+// === Specular Prefilter ===
+
+// Low discrepancy point `i` of `count`
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+// Half vector distributed by the GGX normal distribution around `normal`
+fn importance_sample_ggx(xi: vec2<f32>, normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return tangent_to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), normal);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+const PREFILTER_SAMPLES: u32 = 256u;
+
+// Split sum approximation, assumes the view direction equals the normal
+// Samples come from the mip matching their solid angle to keep the result free of fireflies,
+// as in "GPU-Based Importance Sampling" from GPU Gems 3
+@compute @workgroup_size(8, 8, 1)
+fn prefilter_specular(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (is_outside_cube(id)) {
+        return;
+    }
+
+    let normal = cube_direction(id.z, id.xy, textureDimensions(cube_output).x);
+    let roughness = params.roughness;
+    let texel_solid_angle = 4.0 * PI / (6.0 * params.source_size * params.source_size);
+
+    var color = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < PREFILTER_SAMPLES; i++) {
+        let half_dir = importance_sample_ggx(hammersley(i, PREFILTER_SAMPLES), normal, roughness);
+        let light_dir = normalize(2.0 * dot(normal, half_dir) * half_dir - normal);
+        let n_dot_l = dot(normal, light_dir);
+        if (n_dot_l > 0.0) {
+            // View equals normal, so n.h and v.h cancel out of the pdf
+            let pdf = distribution_ggx(max(dot(normal, half_dir), 0.0), roughness) * 0.25 + 1e-4;
+            let sample_solid_angle = 1.0 / (f32(PREFILTER_SAMPLES) * pdf);
+            var level = 0.0;
+            if (roughness > 0.0) {
+                level = max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+            }
+
+            let radiance =
+                textureSampleLevel(environment_texture, environment_sampler, light_dir, level).rgb;
+            color += radiance * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+
+    textureStore(cube_output, id.xy, id.z, vec4<f32>(color / max(weight, 1e-4), 1.0));
+}
+
+// === BRDF Lookup Table ===
+
+const BRDF_SAMPLES: u32 = 512u;
+
+fn geometry_schlick_ggx(n_dot: f32, roughness: f32) -> f32 {
+    // Remapped for image based lighting
+    let k = roughness * roughness * 0.5;
+    return n_dot / (n_dot * (1.0 - k) + k);
+}
+
+// Scale (r) and bias (g) applied to F0 by the specular BRDF, by n.v (x) and roughness (y)
+@compute @workgroup_size(8, 8, 1)
+fn brdf_lut(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(lut_output);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let n_dot_v = (f32(id.x) + 0.5) / f32(size.x);
+    let roughness = (f32(id.y) + 0.5) / f32(size.y);
+    let view_dir = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < BRDF_SAMPLES; i++) {
+        let half_dir = importance_sample_ggx(hammersley(i, BRDF_SAMPLES), normal, roughness);
+        let light_dir = normalize(2.0 * dot(view_dir, half_dir) * half_dir - view_dir);
+        let n_dot_l = saturate(light_dir.z);
+        if (n_dot_l > 0.0) {
+            let n_dot_h = saturate(half_dir.z);
+            let v_dot_h = saturate(dot(view_dir, half_dir));
+            let geometry = geometry_schlick_ggx(n_dot_v, roughness)
+                * geometry_schlick_ggx(n_dot_l, roughness);
+            let visibility = geometry * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+
+    let count = f32(BRDF_SAMPLES);
+    textureStore(lut_output, id.xy, vec4<f32>(scale / count, bias / count, 0.0, 1.0));
+}
@@ -0,0 +1,96 @@
+// Instance culling - frustum and horizon cull every instance of an instanced mesh for one
+// camera, compact the survivors and write the indirect draw arguments
+
+// Must match `InstanceCullParams` in components/visibility.rs
+struct InstanceCullParams {
+    // Normalized frustum planes, pointing inwards
+    frustum_planes: array<vec4<f32>, 6>,
+    camera_position: vec4<f32>,
+    // Sphere hiding everything below its horizon (xyz = center, w = radius, 0 = disabled)
+    horizon_occluder: vec4<f32>,
+    instance_count: u32,
+    index_count: u32,
+}
+
+// Must match `InstanceData` in components/instanced_mesh.rs
+struct InstanceData {
+    model_matrix: mat4x4<f32>,
+    lod_depth: u32,
+}
+
+// Must match `InstanceBounds` in components/instanced_mesh.rs
+struct InstanceBounds {
+    center: vec3<f32>,
+    radius: f32,
+}
+
+// Layout of `wgpu::util::DrawIndexedIndirectArgs`
+struct DrawIndexedIndirectArgs {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: InstanceCullParams;
+@group(0) @binding(1) var<storage, read> instances: array<InstanceData>;
+@group(0) @binding(2) var<storage, read> bounds: array<InstanceBounds>;
+@group(0) @binding(3) var<storage, read_write> visible_instances: array<InstanceData>;
+// Cleared before the dispatch
+@group(0) @binding(4) var<storage, read_write> draw_args: DrawIndexedIndirectArgs;
+
+fn is_outside_frustum(center: vec3<f32>, radius: f32) -> bool {
+    for (var i = 0u; i < 6u; i++) {
+        let plane = params.frustum_planes[i];
+        if (dot(plane.xyz, center) + plane.w < -radius) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Conservative horizon test: the sphere is hidden if its center is hidden by the occluder
+// shrunk by the sphere's radius
+fn is_below_horizon(center: vec3<f32>, radius: f32) -> bool {
+    let occluder_radius = params.horizon_occluder.w - radius;
+    if (occluder_radius <= 0.0) {
+        return false;
+    }
+
+    // Work in a space where the occluder is the unit sphere
+    let camera = (params.camera_position.xyz - params.horizon_occluder.xyz) / occluder_radius;
+    let horizon_distance_sq = dot(camera, camera) - 1.0;
+    if (horizon_distance_sq <= 0.0) {
+        // Camera inside the occluder
+        return false;
+    }
+
+    let to_target = (center - params.horizon_occluder.xyz) / occluder_radius - camera;
+    let along_view = -dot(to_target, camera);
+
+    // Beyond the horizon plane and inside the occluder's silhouette cone
+    return along_view > horizon_distance_sq
+        && along_view * along_view / dot(to_target, to_target) > horizon_distance_sq;
+}
+
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+
+    if (index == 0u) {
+        draw_args.index_count = params.index_count;
+    }
+
+    if (index >= params.instance_count) {
+        return;
+    }
+
+    let sphere = bounds[index];
+    if (is_outside_frustum(sphere.center, sphere.radius) || is_below_horizon(sphere.center, sphere.radius)) {
+        return;
+    }
+
+    let slot = atomicAdd(&draw_args.instance_count, 1u);
+    visible_instances[slot] = instances[index];
+}
@@ -1,4 +1,26 @@
+mod gizmos;
+mod graph;
+mod ibl;
+mod passes;
+mod post_process;
 mod render_layer;
+mod skybox;
 pub mod systems;
+mod tonemapping;
+mod wireframe;
 
+pub use gizmos::GizmoRenderer;
+pub use graph::{
+    GraphResources, GraphTexture, NodeSlots, RenderContext, RenderGraph, RenderNode,
+    TransientBuffer, TransientTexture, slot,
+};
+pub use ibl::IblBaker;
+pub use passes::{
+    GizmoNode, InstanceCullNode, OpaqueNode, PostProcessNode, ShadowNode, TonemapNode,
+    TransparentNode, WireframeNode,
+};
+pub use post_process::PostProcessor;
 pub use render_layer::RenderLayer;
+pub use skybox::Skybox;
+pub use tonemapping::Tonemapper;
+pub use wireframe::{WireframeDraw, WireframeInstances, WireframeRenderer};
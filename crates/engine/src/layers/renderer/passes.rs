@@ -0,0 +1,817 @@
+use crate::prelude::*;
+
+use crate::shader::{BindGroupRequirement, ShaderCache, VertexLayout};
+use std::collections::HashMap;
+
+use super::graph::{NodeSlots, RenderContext, RenderNode, slot};
+use super::render_layer::{Batch, CameraView, MeshDraw};
+use super::{GizmoRenderer, PostProcessor, Skybox, Tonemapper, WireframeRenderer};
+
+/// Compacts the visible instances of every instanced mesh and writes their indirect draws
+pub struct InstanceCullNode {
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl InstanceCullNode {
+    pub const NAME: &str = "instance_cull";
+
+    pub fn new(device: &wgpu::Device, instance_cull_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Instance Cull Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("instance_cull.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Instance Cull Pipeline Layout"),
+            bind_group_layouts: &[instance_cull_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Instance Cull Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cull"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self { pipeline }
+    }
+}
+
+impl RenderNode for InstanceCullNode {
+    fn slots(&self) -> NodeSlots {
+        NodeSlots::new().write(slot::CULLED_INSTANCES)
+    }
+
+    fn run(&mut self, context: &mut RenderContext) {
+        let view = context.view;
+        for culled in &view.culled {
+            context
+                .encoder
+                .clear_buffer(&culled.indirect_buffer, 0, None);
+        }
+
+        let timestamps = context.pass_timestamps("Instance Culling Pass");
+        let mut cull_pass = context
+            .encoder
+            .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Instance Culling Pass"),
+                timestamp_writes: timestamps
+                    .as_ref()
+                    .map(GpuPassTimestamps::compute_pass_writes),
+            });
+        cull_pass.set_pipeline(&self.pipeline);
+
+        for culled in &view.culled {
+            cull_pass.set_bind_group(0, &culled.bind_group, &[]);
+            cull_pass.dispatch_workgroups(culled.instance_count.div_ceil(64), 1, 1);
+        }
+        drop(cull_pass);
+
+        for &culled in &view.culled {
+            if culled
+                .readback
+                .copy_from(&mut context.encoder, &culled.indirect_buffer)
+            {
+                context.readbacks.push(&culled.readback);
+            }
+        }
+    }
+}
+
+/// Renders the shadow casters into every cascade of the camera's shadow map
+pub struct ShadowNode {
+    /// Pipelines for the `DepthMode` of each shadow map
+    pipelines: HashMap<DepthMode, ShadowPipelines>,
+}
+
+/// Shadow pipelines for one `DepthMode`
+struct ShadowPipelines {
+    standard: wgpu::RenderPipeline,
+    instanced: wgpu::RenderPipeline,
+    /// Variants sampling the material texture and discarding the cut-out fragments
+    cutout: wgpu::RenderPipeline,
+    instanced_cutout: wgpu::RenderPipeline,
+}
+
+impl ShadowNode {
+    pub const NAME: &str = "shadow";
+
+    pub fn new(
+        device: &wgpu::Device,
+        transform_layout: &wgpu::BindGroupLayout,
+        shadow_uniform_layout: &wgpu::BindGroupLayout,
+        texture_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
+        });
+
+        let instanced_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Instanced Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow_instanced.wgsl").into()),
+        });
+
+        let pipelines = DepthMode::ALL
+            .into_iter()
+            .map(|depth_mode| {
+                let standard = create_shadow_pipeline(
+                    device,
+                    "Shadow Pipeline",
+                    &shader,
+                    &[transform_layout, shadow_uniform_layout],
+                    &VertexLayout::Standard.buffers(),
+                    depth_mode,
+                    None,
+                );
+                let instanced = create_shadow_pipeline(
+                    device,
+                    "Instanced Shadow Pipeline",
+                    &instanced_shader,
+                    &[shadow_uniform_layout],
+                    &VertexLayout::Instanced.buffers(),
+                    depth_mode,
+                    None,
+                );
+                let cutout = create_shadow_pipeline(
+                    device,
+                    "Cut-out Shadow Pipeline",
+                    &shader,
+                    &[transform_layout, shadow_uniform_layout, texture_layout],
+                    &VertexLayout::Standard.buffers(),
+                    depth_mode,
+                    Some("cutout"),
+                );
+                let instanced_cutout = create_shadow_pipeline(
+                    device,
+                    "Instanced Cut-out Shadow Pipeline",
+                    &instanced_shader,
+                    &[shadow_uniform_layout, texture_layout],
+                    &VertexLayout::Instanced.buffers(),
+                    depth_mode,
+                    Some("cutout"),
+                );
+                let pipelines = ShadowPipelines {
+                    standard,
+                    instanced,
+                    cutout,
+                    instanced_cutout,
+                };
+                (depth_mode, pipelines)
+            })
+            .collect();
+
+        Self { pipelines }
+    }
+}
+
+impl RenderNode for ShadowNode {
+    fn slots(&self) -> NodeSlots {
+        NodeSlots::new().write(slot::SHADOW_MAP)
+    }
+
+    fn run(&mut self, context: &mut RenderContext) {
+        let view = context.view;
+        let shadow_map = view.shadow_map;
+        let pipelines = &self.pipelines[&shadow_map.depth_mode];
+
+        for (cascade_view, cascade_bind_group) in shadow_map
+            .cascade_views
+            .iter()
+            .zip(&shadow_map.cascade_bind_groups)
+        {
+            let timestamps = context.pass_timestamps("Shadow Pass");
+            let mut shadow_pass = context
+                .encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Shadow Pass"),
+                    color_attachments: &[], // No color output
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: cascade_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(shadow_map.depth_mode.far_depth()),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    occlusion_query_set: None,
+                    timestamp_writes: timestamps
+                        .as_ref()
+                        .map(GpuPassTimestamps::render_pass_writes),
+                });
+
+            let mut bound = BoundState::default();
+            bound.set_bind_group(&mut shadow_pass, 0, view.transforms);
+            bound.set_bind_group(&mut shadow_pass, 1, cascade_bind_group);
+
+            // Render all shadow casting meshes from light's perspective
+            for batch in view.shadow_batches {
+                match batch.caster.cutout {
+                    Some(texture) => {
+                        bound.set_pipeline(&mut shadow_pass, &pipelines.cutout);
+                        bound.set_bind_group(&mut shadow_pass, 2, &texture.bind_group);
+                    }
+                    None => bound.set_pipeline(&mut shadow_pass, &pipelines.standard),
+                }
+
+                let mesh = batch.caster.mesh;
+                shadow_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                shadow_pass.set_index_buffer(mesh.index_buffer.slice(..), index_format());
+                shadow_pass.draw_indexed(0..mesh.index_count, 0, batch.objects.clone());
+            }
+
+            let mut bound = BoundState::default();
+            bound.set_bind_group(&mut shadow_pass, 0, cascade_bind_group);
+
+            for caster in view.instanced_casters {
+                let instanced_mesh = caster.mesh;
+                if instanced_mesh.instance_count == 0 {
+                    continue;
+                }
+                match caster.cutout {
+                    Some(texture) => {
+                        bound.set_pipeline(&mut shadow_pass, &pipelines.instanced_cutout);
+                        bound.set_bind_group(&mut shadow_pass, 1, &texture.bind_group);
+                    }
+                    None => bound.set_pipeline(&mut shadow_pass, &pipelines.instanced),
+                }
+                shadow_pass.set_vertex_buffer(0, instanced_mesh.vertex_buffer.slice(..));
+                shadow_pass.set_vertex_buffer(1, instanced_mesh.instance_buffer.slice(..));
+                shadow_pass.set_index_buffer(instanced_mesh.index_buffer.slice(..), index_format());
+                shadow_pass.draw_indexed(
+                    0..instanced_mesh.index_count,
+                    0,
+                    0..instanced_mesh.instance_count,
+                );
+            }
+        }
+    }
+}
+
+/// Clears the camera's targets and draws the opaque geometry with shadows, then the skybox
+/// behind it
+pub struct OpaqueNode {
+    skybox: Skybox,
+}
+
+impl OpaqueNode {
+    pub const NAME: &str = "opaque";
+
+    pub fn new(device: &wgpu::Device, lighting_layout: &wgpu::BindGroupLayout) -> Self {
+        Self {
+            skybox: Skybox::new(device, lighting_layout),
+        }
+    }
+}
+
+impl RenderNode for OpaqueNode {
+    fn slots(&self) -> NodeSlots {
+        NodeSlots::new()
+            .read(slot::SHADOW_MAP)
+            .read(slot::CULLED_INSTANCES)
+            .write(slot::HDR)
+            .write(slot::DEPTH)
+    }
+
+    fn run(&mut self, context: &mut RenderContext) {
+        let view = context.view;
+        // Multisampled cameras draw into their MSAA texture and resolve into the HDR target
+        let (color_view, resolve_target) = view.hdr_target.attachment();
+
+        let timestamps = context.pass_timestamps("Render Pass");
+        let mut render_pass = context
+            .encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(view.settings.clear.color()),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &view.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(view.settings.depth_mode.far_depth()),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: timestamps
+                    .as_ref()
+                    .map(GpuPassTimestamps::render_pass_writes),
+            });
+
+        let mut bound = BoundState::default();
+        for batch in &view.opaque {
+            draw_mesh(&mut render_pass, &mut bound, batch, view);
+        }
+
+        // Behind the opaque geometry, before blending anything over it
+        if view.settings.clear == ClearMode::Skybox
+            && view.has_environment
+            && view.debug_view.is_lit()
+        {
+            self.skybox.draw(
+                context.device,
+                &mut render_pass,
+                &view.shadow_map.bind_group,
+                view.hdr_target.draw_texture().sample_count(),
+                view.settings.depth_mode,
+            );
+        }
+    }
+}
+
+/// Blends the translucent geometry back-to-front over the opaque geometry
+pub struct TransparentNode;
+
+impl TransparentNode {
+    pub const NAME: &str = "transparent";
+}
+
+impl RenderNode for TransparentNode {
+    fn slots(&self) -> NodeSlots {
+        NodeSlots::new()
+            .read(slot::SHADOW_MAP)
+            .read(slot::CULLED_INSTANCES)
+            .read(slot::DEPTH)
+            .write(slot::HDR)
+    }
+
+    fn run(&mut self, context: &mut RenderContext) {
+        let view = context.view;
+        if view.transparent.is_empty() {
+            return;
+        }
+
+        let timestamps = context.pass_timestamps("Transparent Pass");
+        let mut render_pass = begin_overlay_pass(
+            &mut context.encoder,
+            "Transparent Pass",
+            view,
+            timestamps.as_ref(),
+        );
+
+        let mut bound = BoundState::default();
+        for batch in &view.transparent {
+            draw_mesh(&mut render_pass, &mut bound, batch, view);
+        }
+    }
+}
+
+/// Draws the triangle edges of wireframe fallbacks and overlays, see `WireframeSettings`
+pub struct WireframeNode {
+    wireframes: WireframeRenderer,
+}
+
+impl WireframeNode {
+    pub const NAME: &str = "wireframe";
+
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        transform_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            wireframes: WireframeRenderer::new(device, camera_layout, transform_layout),
+        }
+    }
+}
+
+impl RenderNode for WireframeNode {
+    fn slots(&self) -> NodeSlots {
+        NodeSlots::new()
+            .read(slot::CULLED_INSTANCES)
+            .read(slot::DEPTH)
+            .write(slot::HDR)
+    }
+
+    fn prepare(
+        &mut self,
+        _device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        world: &mut World,
+        _delta_time: f32,
+    ) {
+        let settings = world
+            .get_resource::<WireframeSettings>()
+            .copied()
+            .unwrap_or_default();
+        self.wireframes.prepare(queue, &settings);
+    }
+
+    fn run(&mut self, context: &mut RenderContext) {
+        let view = context.view;
+        if view.wireframes.is_empty() {
+            return;
+        }
+
+        let timestamps = context.pass_timestamps("Wireframe Pass");
+        let mut render_pass = begin_overlay_pass(
+            &mut context.encoder,
+            "Wireframe Pass",
+            view,
+            timestamps.as_ref(),
+        );
+
+        self.wireframes.draw(
+            context.device,
+            &mut render_pass,
+            &view.camera.bind_group,
+            view.transforms,
+            view.hdr_target.draw_texture().sample_count(),
+            view.settings.depth_mode,
+            &view.wireframes,
+        );
+    }
+}
+
+/// Draws the lines queued by `Gizmos` over the scene, before post processing
+pub struct GizmoNode {
+    gizmos: GizmoRenderer,
+}
+
+impl GizmoNode {
+    pub const NAME: &str = "gizmos";
+
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        Self {
+            gizmos: GizmoRenderer::new(device, camera_layout),
+        }
+    }
+}
+
+impl RenderNode for GizmoNode {
+    fn slots(&self) -> NodeSlots {
+        NodeSlots::new().read(slot::DEPTH).write(slot::HDR)
+    }
+
+    fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        world: &mut World,
+        delta_time: f32,
+    ) {
+        // Lines queued by `Gizmos` since the last frame, drawn into every camera
+        let lines = world
+            .get_resource_mut::<GizmoBuffer>()
+            .map(|mut buffer| buffer.advance(delta_time))
+            .unwrap_or_default();
+        let origin = world.resource::<RenderOrigin>();
+        self.gizmos.prepare(device, queue, &lines, origin);
+    }
+
+    fn run(&mut self, context: &mut RenderContext) {
+        if self.gizmos.is_empty() {
+            return;
+        }
+
+        let view = context.view;
+        let timestamps = context.pass_timestamps("Gizmo Pass");
+        let mut render_pass = begin_overlay_pass(
+            &mut context.encoder,
+            "Gizmo Pass",
+            view,
+            timestamps.as_ref(),
+        );
+
+        self.gizmos.draw(
+            context.device,
+            &mut render_pass,
+            &view.camera.bind_group,
+            view.hdr_target.draw_texture().sample_count(),
+            view.settings.depth_mode,
+        );
+    }
+}
+
+/// Runs the camera's `PostProcessStack` effects of one stage
+///
+/// The HDR stage runs on `slot::HDR` before tonemapping, the LDR stage on `slot::TARGET`
+/// after it.
+pub struct PostProcessNode {
+    stage: PostProcessStage,
+    post_processor: PostProcessor,
+}
+
+impl PostProcessNode {
+    pub const HDR_NAME: &str = "post_process_hdr";
+    pub const LDR_NAME: &str = "post_process_ldr";
+
+    pub fn new(device: &wgpu::Device, stage: PostProcessStage) -> Self {
+        Self {
+            stage,
+            post_processor: PostProcessor::new(device),
+        }
+    }
+
+    fn slot(&self) -> &'static str {
+        match self.stage {
+            PostProcessStage::Hdr => slot::HDR,
+            PostProcessStage::Ldr => slot::TARGET,
+        }
+    }
+}
+
+impl RenderNode for PostProcessNode {
+    fn slots(&self) -> NodeSlots {
+        NodeSlots::new().write(self.slot())
+    }
+
+    fn prepare(
+        &mut self,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        _world: &mut World,
+        delta_time: f32,
+    ) {
+        self.post_processor.advance(delta_time);
+    }
+
+    fn run(&mut self, context: &mut RenderContext) {
+        let Some(target) = context.resources.texture(self.slot()) else {
+            return;
+        };
+        // Bloom and friends would smear the debug colours
+        if self.stage == PostProcessStage::Hdr && !context.view.debug_view.is_lit() {
+            return;
+        }
+        let no_effects = PostProcessStack::default();
+
+        self.post_processor.apply(
+            context.device,
+            context.queue,
+            &mut context.encoder,
+            context.world.get_resource::<ShaderCache>(),
+            context.camera,
+            context.view.post_process.unwrap_or(&no_effects),
+            self.stage,
+            &target.texture,
+        );
+    }
+
+    fn cleanup(&mut self, world: &World) {
+        self.post_processor
+            .retain_cameras(|entity| world.get::<Camera>(entity).is_some());
+    }
+}
+
+/// Tonemaps `slot::HDR` into the display ready `slot::TARGET`, with auto exposure
+pub struct TonemapNode {
+    tonemapper: Tonemapper,
+}
+
+impl TonemapNode {
+    pub const NAME: &str = "tonemap";
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            tonemapper: Tonemapper::new(device),
+        }
+    }
+}
+
+impl RenderNode for TonemapNode {
+    fn slots(&self) -> NodeSlots {
+        NodeSlots::new().read(slot::HDR).write(slot::TARGET)
+    }
+
+    fn run(&mut self, context: &mut RenderContext) {
+        let Some(target) = context.resources.texture(slot::TARGET) else {
+            return;
+        };
+
+        let mut tonemapping = context.view.tonemapping.copied().unwrap_or_default();
+        // Debug colours are already in display range, metering them would shift them around
+        if !context.view.debug_view.is_lit() {
+            tonemapping.exposure = Exposure::Manual { ev: 0.0 };
+        }
+
+        self.tonemapper.resolve(
+            context.device,
+            context.queue,
+            &mut context.encoder,
+            context.camera,
+            &tonemapping,
+            context.view.hdr_target,
+            &target.texture,
+            context.delta_time,
+        );
+    }
+
+    fn cleanup(&mut self, world: &World) {
+        self.tonemapper
+            .retain_cameras(|entity| world.get::<Camera>(entity).is_some());
+    }
+}
+
+/// Pass loading the camera's HDR and depth targets, to draw over what earlier passes drew
+fn begin_overlay_pass<'e>(
+    encoder: &'e mut wgpu::CommandEncoder,
+    label: &str,
+    view: &CameraView,
+    timestamps: Option<&GpuPassTimestamps>,
+) -> wgpu::RenderPass<'e> {
+    let (color_view, resolve_target) = view.hdr_target.attachment();
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: color_view,
+            resolve_target,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+            depth_slice: None,
+        })],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: &view.depth.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }),
+        occlusion_query_set: None,
+        timestamp_writes: timestamps.map(GpuPassTimestamps::render_pass_writes),
+    })
+}
+
+/// Pipeline rendering casters into a shadow cascade
+///
+/// Depth-only unless a `fragment` entry point is given, which cut-out casters use to discard.
+fn create_shadow_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    buffers: &[wgpu::VertexBufferLayout],
+    depth_mode: DepthMode,
+    fragment: Option<&str>,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vertex"),
+            buffers,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: fragment.map(|entry_point| wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(entry_point),
+            targets: &[], // No color output
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: depth_mode.compare(wgpu::CompareFunction::Less),
+            stencil: wgpu::StencilState::default(),
+            bias: depth_mode.bias(wgpu::DepthBiasState {
+                constant: 4,      // Higher constant bias to reduce shadow acne
+                slope_scale: 4.0, // Higher slope scale for angled surfaces
+                clamp: 0.0,
+            }),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// Pipeline and bind groups currently set on a render pass, to skip redundant rebinding
+#[derive(Default)]
+struct BoundState {
+    pipeline: Option<wgpu::RenderPipeline>,
+    bind_groups: Vec<Option<wgpu::BindGroup>>,
+}
+
+impl BoundState {
+    fn set_pipeline(
+        &mut self,
+        render_pass: &mut wgpu::RenderPass,
+        pipeline: &wgpu::RenderPipeline,
+    ) {
+        if self.pipeline.as_ref() != Some(pipeline) {
+            render_pass.set_pipeline(pipeline);
+            self.pipeline = Some(pipeline.clone());
+        }
+    }
+
+    fn set_bind_group(
+        &mut self,
+        render_pass: &mut wgpu::RenderPass,
+        index: u32,
+        bind_group: &wgpu::BindGroup,
+    ) {
+        let index = index as usize;
+        if self.bind_groups.len() <= index {
+            self.bind_groups.resize(index + 1, None);
+        }
+        if self.bind_groups[index].as_ref() != Some(bind_group) {
+            render_pass.set_bind_group(index as u32, bind_group, &[]);
+            self.bind_groups[index] = Some(bind_group.clone());
+        }
+    }
+}
+
+/// Draw a batch into a camera's targets
+///
+/// Regular meshes draw one instance per merged object, instanced meshes draw the instances
+/// that survived the culling pass.
+fn draw_mesh(
+    render_pass: &mut wgpu::RenderPass,
+    bound: &mut BoundState,
+    batch: &Batch,
+    view: &CameraView,
+) {
+    let shader_instance = &batch.shader;
+    bound.set_pipeline(render_pass, &shader_instance.pipeline);
+
+    let texture = match batch.draw.mesh {
+        MeshDraw::Mesh(_, _, texture, _) | MeshDraw::Instanced(_, _, texture) => texture,
+    };
+
+    // Set bind groups based on shader requirements
+    for (index, requirement) in shader_instance.bind_group_requirements.iter().enumerate() {
+        if let Some(req) = requirement {
+            let index = index as u32;
+            match req {
+                BindGroupRequirement::Texture => {
+                    bound.set_bind_group(render_pass, index, &texture.bind_group);
+                }
+                BindGroupRequirement::Camera => {
+                    bound.set_bind_group(render_pass, index, &view.camera.bind_group);
+                }
+                BindGroupRequirement::Transform => {
+                    bound.set_bind_group(render_pass, index, view.transforms);
+                }
+                BindGroupRequirement::Shadow => {
+                    let shadow_bind_group = view
+                        .shadow_map
+                        .main_pass_bind_group(batch.draw.receives_shadows);
+                    bound.set_bind_group(render_pass, index, shadow_bind_group);
+                }
+                BindGroupRequirement::Unknown(name) => {
+                    log::warn!(
+                        "Unknown bind group requirement '{}' at index {}",
+                        name,
+                        index
+                    );
+                }
+            }
+        }
+    }
+
+    match batch.draw.mesh {
+        MeshDraw::Mesh(_, mesh, _, _) => {
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), index_format());
+            render_pass.draw_indexed(0..mesh.index_count, 0, batch.transforms.clone());
+        }
+        MeshDraw::Instanced(_, instanced_mesh, _) => {
+            let Some(culled) = batch.culled else {
+                log::warn!("Instanced mesh drawn without culling results");
+                return;
+            };
+
+            // Set vertex buffers: slot 0 = geometry, slot 1 = visible instance data
+            render_pass.set_vertex_buffer(0, instanced_mesh.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, culled.instance_buffer.slice(..));
+            render_pass.set_index_buffer(instanced_mesh.index_buffer.slice(..), index_format());
+
+            // Draw the instance count written by the culling pass
+            render_pass.draw_indexed_indirect(&culled.indirect_buffer, 0);
+        }
+    }
+}
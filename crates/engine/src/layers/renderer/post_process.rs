@@ -0,0 +1,812 @@
+use crate::prelude::*;
+
+use crate::shader::ShaderCache;
+use nalgebra::Vector2;
+use std::collections::HashMap;
+
+/// Runs the `PostProcessStack` of cameras on their HDR and display targets
+///
+/// Effects ping-pong between the camera's texture and a scratch texture of the same format,
+/// the result is copied back when the last effect wrote to the scratch texture. Shared by the
+/// raster and raytracing layers.
+pub struct PostProcessor {
+    effect_layout: wgpu::BindGroupLayout,
+    texture_layout: wgpu::BindGroupLayout,
+    lut_layout: wgpu::BindGroupLayout,
+    effect_pipeline_layout: wgpu::PipelineLayout,
+    /// Effect bindings plus a 3D lookup table
+    lut_pipeline_layout: wgpu::PipelineLayout,
+    /// Effect bindings plus the blurred bloom image
+    composite_pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    bloom_shader: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    /// Built-in pipelines by pass and output format, created on first use
+    pipelines: HashMap<(EffectPass, wgpu::TextureFormat), wgpu::RenderPipeline>,
+    /// Custom pipelines with the module they were built from, `None` if building failed
+    custom_pipelines:
+        HashMap<(Shader, wgpu::TextureFormat), (wgpu::ShaderModule, Option<wgpu::RenderPipeline>)>,
+    /// Uploaded lookup tables, `None` for images that aren't a valid table
+    luts: Vec<(ColorLut, Option<wgpu::BindGroup>)>,
+    chains: HashMap<(Entity, PostProcessStage), EffectChain>,
+    /// Offset between the params of consecutive effects in a params buffer
+    params_stride: u64,
+    time: f32,
+    frame: u32,
+}
+
+/// Fullscreen passes of the built-in effects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum EffectPass {
+    BloomPrefilter,
+    BloomDownsample,
+    BloomUpsample,
+    BloomComposite,
+    Fxaa,
+    Vignette,
+    ColorGrading,
+    ChromaticAberration,
+    FilmGrain,
+}
+
+impl EffectPass {
+    fn entry_point(&self) -> &'static str {
+        match self {
+            EffectPass::BloomPrefilter => "bloom_prefilter",
+            EffectPass::BloomDownsample => "bloom_downsample",
+            EffectPass::BloomUpsample => "bloom_upsample",
+            EffectPass::BloomComposite => "bloom_composite",
+            EffectPass::Fxaa => "fxaa",
+            EffectPass::Vignette => "vignette",
+            EffectPass::ColorGrading => "color_grading",
+            EffectPass::ChromaticAberration => "chromatic_aberration",
+            EffectPass::FilmGrain => "film_grain",
+        }
+    }
+}
+
+/// Intermediate textures of one stage of one camera
+struct EffectChain {
+    /// Camera texture the chain was created for
+    target: wgpu::Texture,
+    target_view: wgpu::TextureView,
+    scratch: wgpu::Texture,
+    scratch_view: wgpu::TextureView,
+    /// Effect bindings reading from the target and the scratch texture
+    target_bind_group: wgpu::BindGroup,
+    scratch_bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    /// Number of effects `params_buffer` has room for
+    params_capacity: usize,
+    bloom: Option<BloomChain>,
+}
+
+/// Half resolution mip chain the bloom is blurred through
+struct BloomChain {
+    mip_views: Vec<wgpu::TextureView>,
+    /// Effect bindings reading from each mip
+    mip_bind_groups: Vec<wgpu::BindGroup>,
+    /// Binds the top mip for compositing
+    composite_bind_group: wgpu::BindGroup,
+}
+
+impl PostProcessor {
+    const MAX_BLOOM_MIPS: u32 = 6;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let effect_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        // One slot per effect of a chain
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("post_effect_bind_group_layout"),
+        });
+
+        let texture_entry = |view_dimension| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(wgpu::TextureViewDimension::D2)],
+            label: Some("post_effect_texture_bind_group_layout"),
+        });
+        let lut_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(wgpu::TextureViewDimension::D3)],
+            label: Some("post_effect_lut_bind_group_layout"),
+        });
+
+        let create_pipeline_layout = |label, bind_group_layouts: &[&wgpu::BindGroupLayout]| {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            })
+        };
+        let effect_pipeline_layout =
+            create_pipeline_layout("Post Effect Pipeline Layout", &[&effect_layout]);
+        let lut_pipeline_layout = create_pipeline_layout(
+            "Color Grading Pipeline Layout",
+            &[&effect_layout, &lut_layout],
+        );
+        let composite_pipeline_layout = create_pipeline_layout(
+            "Bloom Composite Pipeline Layout",
+            &[&effect_layout, &texture_layout],
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Effect Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("post_process.wgsl").into()),
+        });
+        let bloom_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bloom Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("bloom.wgsl").into()),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Effect Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let params_size = <PostEffectParams as encase::ShaderSize>::SHADER_SIZE.get();
+        let params_stride = params_size
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+
+        Self {
+            effect_layout,
+            texture_layout,
+            lut_layout,
+            effect_pipeline_layout,
+            lut_pipeline_layout,
+            composite_pipeline_layout,
+            shader,
+            bloom_shader,
+            sampler,
+            pipelines: HashMap::new(),
+            custom_pipelines: HashMap::new(),
+            luts: Vec::new(),
+            chains: HashMap::new(),
+            params_stride,
+            time: 0.0,
+            frame: 0,
+        }
+    }
+
+    /// Advance the time and frame counter effects animate with, once per frame
+    pub fn advance(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// Run the effects of `stack` belonging to `stage` on `target`, leaving the result in it
+    ///
+    /// Custom effects are skipped when no `ShaderCache` is given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn apply(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        shader_cache: Option<&ShaderCache>,
+        camera: Entity,
+        stack: &PostProcessStack,
+        stage: PostProcessStage,
+        target: &wgpu::Texture,
+    ) {
+        let effects: Vec<&PostEffect> = stack.stage(stage).collect();
+        if effects.is_empty() {
+            self.chains.remove(&(camera, stage));
+            return;
+        }
+
+        let needs_bloom = effects
+            .iter()
+            .any(|effect| matches!(effect, PostEffect::Bloom(_)));
+        let chain = match self.chains.remove(&(camera, stage)) {
+            Some(chain)
+                if chain.target == *target
+                    && chain.params_capacity >= effects.len()
+                    && (chain.bloom.is_some() || !needs_bloom) =>
+            {
+                chain
+            }
+            // The target was recreated or the stack grew, rebuild every texture and binding
+            _ => self.create_chain(device, target, effects.len(), needs_bloom),
+        };
+
+        let size = target.size();
+        let texel_size = Vector2::new(1.0 / size.width as f32, 1.0 / size.height as f32);
+        let mut params_data = vec![0; effects.len() * self.params_stride as usize];
+        for (effect, slot) in effects
+            .iter()
+            .zip(params_data.chunks_mut(self.params_stride as usize))
+        {
+            let params = PostEffectParams {
+                texel_size,
+                time: self.time,
+                frame: self.frame,
+                values: effect.values(),
+            };
+            let mut data = UniformBuffer::new(Vec::new());
+            data.write(&params).unwrap();
+            let data = data.into_inner();
+            slot[..data.len()].copy_from_slice(&data);
+        }
+        queue.write_buffer(&chain.params_buffer, 0, &params_data);
+
+        let format = target.format();
+        let mut in_target = true;
+        for (index, effect) in effects.into_iter().enumerate() {
+            let offsets = [(index as u64 * self.params_stride) as u32];
+            let (source, output) = if in_target {
+                (&chain.target_bind_group, &chain.scratch_view)
+            } else {
+                (&chain.scratch_bind_group, &chain.target_view)
+            };
+            let effect_bindings = (source, &offsets[..]);
+
+            let drawn = match effect {
+                PostEffect::Bloom(_) => {
+                    let bloom = chain.bloom.as_ref().unwrap();
+                    self.draw_bloom(device, encoder, bloom, effect_bindings, output, format);
+                    true
+                }
+                PostEffect::ColorGrading(grading) => {
+                    match self.lut_bind_group(device, queue, &grading.lut) {
+                        Some(lut) => {
+                            let pipeline = self.pipeline(device, EffectPass::ColorGrading, format);
+                            draw_fullscreen(
+                                encoder,
+                                "Color Grading Pass",
+                                &pipeline,
+                                output,
+                                &[effect_bindings, (&lut, &[])],
+                                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            );
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                PostEffect::Custom(custom) => {
+                    match self.custom_pipeline(device, shader_cache, &custom.shader, format) {
+                        Some(pipeline) => {
+                            draw_fullscreen(
+                                encoder,
+                                "Custom Post Effect Pass",
+                                &pipeline,
+                                output,
+                                &[effect_bindings],
+                                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            );
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                _ => {
+                    let pass = match effect {
+                        PostEffect::Fxaa(_) => EffectPass::Fxaa,
+                        PostEffect::Vignette(_) => EffectPass::Vignette,
+                        PostEffect::ChromaticAberration(_) => EffectPass::ChromaticAberration,
+                        PostEffect::FilmGrain(_) => EffectPass::FilmGrain,
+                        _ => unreachable!(),
+                    };
+                    let pipeline = self.pipeline(device, pass, format);
+                    draw_fullscreen(
+                        encoder,
+                        effect.name(),
+                        &pipeline,
+                        output,
+                        &[effect_bindings],
+                        wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    );
+                    true
+                }
+            };
+
+            // Effects that couldn't run leave the image where it is
+            if drawn {
+                in_target = !in_target;
+            }
+        }
+
+        if !in_target {
+            encoder.copy_texture_to_texture(
+                chain.scratch.as_image_copy(),
+                chain.target.as_image_copy(),
+                size,
+            );
+        }
+
+        self.chains.insert((camera, stage), chain);
+    }
+
+    /// Drop the intermediate textures of cameras that no longer exist
+    pub fn retain_cameras(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        self.chains.retain(|(entity, _), _| keep(*entity));
+    }
+
+    /// Threshold into the top mip, blur down the chain and back up, then add it to the image
+    fn draw_bloom(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        bloom: &BloomChain,
+        effect_bindings: (&wgpu::BindGroup, &[u32]),
+        output: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+    ) {
+        let offsets = effect_bindings.1;
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        let prefilter = self.pipeline(device, EffectPass::BloomPrefilter, HDR_FORMAT);
+        draw_fullscreen(
+            encoder,
+            "Bloom Prefilter Pass",
+            &prefilter,
+            &bloom.mip_views[0],
+            &[effect_bindings],
+            clear,
+        );
+
+        let downsample = self.pipeline(device, EffectPass::BloomDownsample, HDR_FORMAT);
+        for mip in 1..bloom.mip_views.len() {
+            draw_fullscreen(
+                encoder,
+                "Bloom Downsample Pass",
+                &downsample,
+                &bloom.mip_views[mip],
+                &[(&bloom.mip_bind_groups[mip - 1], offsets)],
+                clear,
+            );
+        }
+
+        let upsample = self.pipeline(device, EffectPass::BloomUpsample, HDR_FORMAT);
+        for mip in (1..bloom.mip_views.len()).rev() {
+            draw_fullscreen(
+                encoder,
+                "Bloom Upsample Pass",
+                &upsample,
+                &bloom.mip_views[mip - 1],
+                &[(&bloom.mip_bind_groups[mip], offsets)],
+                wgpu::LoadOp::Load,
+            );
+        }
+
+        let composite = self.pipeline(device, EffectPass::BloomComposite, format);
+        draw_fullscreen(
+            encoder,
+            "Bloom Composite Pass",
+            &composite,
+            output,
+            &[effect_bindings, (&bloom.composite_bind_group, &[])],
+            clear,
+        );
+    }
+
+    fn pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        pass: EffectPass,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let (module, layout, blend) = match pass {
+            EffectPass::BloomPrefilter | EffectPass::BloomDownsample => {
+                (&self.bloom_shader, &self.effect_pipeline_layout, None)
+            }
+            // Accumulates the blurred smaller mips onto the larger one
+            EffectPass::BloomUpsample => (
+                &self.bloom_shader,
+                &self.effect_pipeline_layout,
+                Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::REPLACE,
+                }),
+            ),
+            EffectPass::BloomComposite => {
+                (&self.bloom_shader, &self.composite_pipeline_layout, None)
+            }
+            EffectPass::ColorGrading => (&self.shader, &self.lut_pipeline_layout, None),
+            _ => (&self.shader, &self.effect_pipeline_layout, None),
+        };
+
+        self.pipelines
+            .entry((pass, format))
+            .or_insert_with(|| {
+                create_effect_pipeline(
+                    device,
+                    pass.entry_point(),
+                    layout,
+                    module,
+                    pass.entry_point(),
+                    format,
+                    blend,
+                )
+            })
+            .clone()
+    }
+
+    /// Pipeline of a custom effect, rebuilt whenever its shader was hot reloaded
+    fn custom_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        shader_cache: Option<&ShaderCache>,
+        shader: &Shader,
+        format: wgpu::TextureFormat,
+    ) -> Option<wgpu::RenderPipeline> {
+        let Some(module) = shader_cache.and_then(|cache| cache.get_post_effect_module(shader))
+        else {
+            log::warn!(
+                "Post effect shader '{}' has not been registered, skipping it",
+                shader
+            );
+            return None;
+        };
+
+        let key = (shader.clone(), format);
+        if let Some((built_from, pipeline)) = self.custom_pipelines.get(&key)
+            && built_from == module
+        {
+            return pipeline.clone();
+        }
+
+        // Catch validation errors so a broken effect doesn't bring the app down
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = create_effect_pipeline(
+            device,
+            &shader.to_string(),
+            &self.effect_pipeline_layout,
+            module,
+            "fragment",
+            format,
+            None,
+        );
+        let pipeline = match pollster::block_on(device.pop_error_scope()) {
+            Some(error) => {
+                log::error!(
+                    "Failed to create post effect pipeline for {}: {}",
+                    shader,
+                    error
+                );
+                None
+            }
+            None => Some(pipeline),
+        };
+
+        self.custom_pipelines
+            .insert(key, (module.clone(), pipeline.clone()));
+        pipeline
+    }
+
+    /// Upload a lookup table the first time it is used
+    fn lut_bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lut: &ColorLut,
+    ) -> Option<wgpu::BindGroup> {
+        if let Some((_, bind_group)) = self.luts.iter().find(|(cached, _)| cached == lut) {
+            return bind_group.clone();
+        }
+
+        let bind_group = match upload_lut(device, queue, lut) {
+            Ok(view) => Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.lut_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                }],
+                label: Some("color_lut_bind_group"),
+            })),
+            Err(error) => {
+                log::error!("Invalid color grading lookup table: {}", error);
+                None
+            }
+        };
+
+        self.luts.push((lut.clone(), bind_group.clone()));
+        bind_group
+    }
+
+    fn create_chain(
+        &self,
+        device: &wgpu::Device,
+        target: &wgpu::Texture,
+        effect_count: usize,
+        needs_bloom: bool,
+    ) -> EffectChain {
+        let size = target.size();
+        let scratch = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Post Process Scratch Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: target.format(),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let params_capacity = effect_count.next_power_of_two();
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post Effect Params Buffer"),
+            size: params_capacity as u64 * self.params_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let scratch_view = scratch.create_view(&wgpu::TextureViewDescriptor::default());
+        let target_bind_group = self.create_effect_bind_group(device, &target_view, &params_buffer);
+        let scratch_bind_group =
+            self.create_effect_bind_group(device, &scratch_view, &params_buffer);
+
+        let bloom = needs_bloom.then(|| self.create_bloom_chain(device, size, &params_buffer));
+
+        EffectChain {
+            target: target.clone(),
+            target_view,
+            scratch,
+            scratch_view,
+            target_bind_group,
+            scratch_bind_group,
+            params_buffer,
+            params_capacity,
+            bloom,
+        }
+    }
+
+    fn create_bloom_chain(
+        &self,
+        device: &wgpu::Device,
+        size: wgpu::Extent3d,
+        params_buffer: &wgpu::Buffer,
+    ) -> BloomChain {
+        let width = (size.width / 2).max(1);
+        let height = (size.height / 2).max(1);
+        // Stop before the smallest mip drops below a single texel
+        let mip_count = width.min(height).ilog2().min(Self::MAX_BLOOM_MIPS - 1) + 1;
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Bloom Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let mip_views: Vec<_> = (0..mip_count)
+            .map(|mip| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Bloom Mip View"),
+                    base_mip_level: mip,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let mip_bind_groups = mip_views
+            .iter()
+            .map(|view| self.create_effect_bind_group(device, view, params_buffer))
+            .collect();
+
+        let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.texture_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&mip_views[0]),
+            }],
+            label: Some("bloom_composite_bind_group"),
+        });
+
+        BloomChain {
+            mip_views,
+            mip_bind_groups,
+            composite_bind_group,
+        }
+    }
+
+    fn create_effect_bind_group(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        params_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.effect_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: params_buffer,
+                        offset: 0,
+                        size: Some(<PostEffectParams as encase::ShaderSize>::SHADER_SIZE),
+                    }),
+                },
+            ],
+            label: Some("post_effect_bind_group"),
+        })
+    }
+}
+
+fn create_effect_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+    entry_point: &str,
+    format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("{} Post Effect Pipeline", label)),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module,
+            entry_point: Some("vertex"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module,
+            entry_point: Some(entry_point),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    output: &wgpu::TextureView,
+    bind_groups: &[(&wgpu::BindGroup, &[u32])],
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+            depth_slice: None,
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+    pass.set_pipeline(pipeline);
+    for (index, (bind_group, offsets)) in bind_groups.iter().enumerate() {
+        pass.set_bind_group(index as u32, *bind_group, offsets);
+    }
+    pass.draw(0..3, 0..1);
+}
+
+/// Rearrange a strip of slices into a 3D texture
+fn upload_lut(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    lut: &ColorLut,
+) -> Result<wgpu::TextureView, String> {
+    let image = image::load_from_memory(&lut.bytes)
+        .map_err(|e| e.to_string())?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+    let size = height;
+    if size < 2 || width != size * size {
+        return Err(format!(
+            "expected a strip of {size} slices of {size}x{size} texels, got a {width}x{height} image"
+        ));
+    }
+
+    // Slice `blue` of the strip becomes depth layer `blue` of the volume
+    let row_bytes = 4 * size as usize;
+    let mut texels = Vec::with_capacity(image.as_raw().len());
+    for blue in 0..size as usize {
+        for green in 0..size as usize {
+            let start = green * width as usize * 4 + blue * row_bytes;
+            texels.extend_from_slice(&image.as_raw()[start..start + row_bytes]);
+        }
+    }
+
+    let extent = wgpu::Extent3d {
+        width: size,
+        height: size,
+        depth_or_array_layers: size,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Color Grading LUT"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        // Encoded values are looked up as they are stored
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    queue.write_texture(
+        texture.as_image_copy(),
+        &texels,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * size),
+            rows_per_image: Some(size),
+        },
+        extent,
+    );
+
+    Ok(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
@@ -0,0 +1,265 @@
+// Built-in post effects - fullscreen passes reading the image left by the previous effect
+// Custom effects bind group 0 the same way, see `CustomPostEffect`
+
+// Must match `PostEffectParams` in components/post_process.rs
+struct PostEffectParams {
+    texel_size: vec2<f32>,
+    time: f32,
+    frame: u32,
+    values: vec4<f32>,
+}
+
+@group(0) @binding(0) var source_texture: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> params: PostEffectParams;
+// Colour grading only, sampled with `source_sampler`
+@group(1) @binding(0) var lut_texture: texture_3d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // Fullscreen triangle
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Texture space has y pointing down
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+fn sample_source(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(source_texture, source_sampler, uv, 0.0);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// === FXAA ===
+
+// Display samples are linear, edges are found on perceptual luma
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn luma_at(uv: vec2<f32>) -> f32 {
+    return luma(sample_source(uv).rgb);
+}
+
+const FXAA_SEARCH_STEPS: i32 = 12;
+
+// Distance in texels of each edge search step, growing once the edge is known to be long
+fn fxaa_step_size(step: i32) -> f32 {
+    if (step < 5) {
+        return 1.0;
+    }
+    if (step == 5) {
+        return 1.5;
+    }
+    if (step < 10) {
+        return 2.0;
+    }
+    if (step == 10) {
+        return 4.0;
+    }
+    return 8.0;
+}
+
+// FXAA 3.11 quality preset, as explained by Simon Rodriguez
+@fragment
+fn fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let edge_threshold = params.values.x;
+    let edge_threshold_min = params.values.y;
+    let subpixel_quality = params.values.z;
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+    let uv = in.uv;
+
+    let center = sample_source(uv);
+    let luma_center = luma(center.rgb);
+    let luma_n = luma_at(uv + vec2<f32>(0.0, -texel.y));
+    let luma_s = luma_at(uv + vec2<f32>(0.0, texel.y));
+    let luma_w = luma_at(uv + vec2<f32>(-texel.x, 0.0));
+    let luma_e = luma_at(uv + vec2<f32>(texel.x, 0.0));
+
+    let luma_min = min(luma_center, min(min(luma_n, luma_s), min(luma_w, luma_e)));
+    let luma_max = max(luma_center, max(max(luma_n, luma_s), max(luma_w, luma_e)));
+    let luma_range = luma_max - luma_min;
+
+    // Flat areas are left alone
+    if (luma_range < max(edge_threshold_min, luma_max * edge_threshold)) {
+        return center;
+    }
+
+    let luma_nw = luma_at(uv + vec2<f32>(-texel.x, -texel.y));
+    let luma_ne = luma_at(uv + vec2<f32>(texel.x, -texel.y));
+    let luma_sw = luma_at(uv + vec2<f32>(-texel.x, texel.y));
+    let luma_se = luma_at(uv + vec2<f32>(texel.x, texel.y));
+
+    let luma_ns = luma_n + luma_s;
+    let luma_we = luma_w + luma_e;
+    let luma_n_corners = luma_nw + luma_ne;
+    let luma_s_corners = luma_sw + luma_se;
+    let luma_w_corners = luma_nw + luma_sw;
+    let luma_e_corners = luma_ne + luma_se;
+
+    let edge_horizontal = abs(-2.0 * luma_w + luma_w_corners)
+        + abs(-2.0 * luma_center + luma_ns) * 2.0
+        + abs(-2.0 * luma_e + luma_e_corners);
+    let edge_vertical = abs(-2.0 * luma_n + luma_n_corners)
+        + abs(-2.0 * luma_center + luma_we) * 2.0
+        + abs(-2.0 * luma_s + luma_s_corners);
+    let is_horizontal = edge_horizontal >= edge_vertical;
+
+    // Pick the side of the edge with the steepest gradient
+    let luma_negative = select(luma_w, luma_n, is_horizontal);
+    let luma_positive = select(luma_e, luma_s, is_horizontal);
+    let gradient_negative = luma_negative - luma_center;
+    let gradient_positive = luma_positive - luma_center;
+    let negative_steepest = abs(gradient_negative) >= abs(gradient_positive);
+    let gradient_scaled = 0.25 * max(abs(gradient_negative), abs(gradient_positive));
+
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    var luma_local_average = 0.5 * (luma_positive + luma_center);
+    if (negative_steepest) {
+        step_length = -step_length;
+        luma_local_average = 0.5 * (luma_negative + luma_center);
+    }
+
+    // Move half a texel onto the edge and search along it in both directions for its ends
+    var edge_uv = uv;
+    if (is_horizontal) {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+
+    let search_offset = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    var uv_negative = edge_uv - search_offset;
+    var uv_positive = edge_uv + search_offset;
+    var luma_end_negative = luma_at(uv_negative) - luma_local_average;
+    var luma_end_positive = luma_at(uv_positive) - luma_local_average;
+    var reached_negative = abs(luma_end_negative) >= gradient_scaled;
+    var reached_positive = abs(luma_end_positive) >= gradient_scaled;
+
+    for (var step = 1; step < FXAA_SEARCH_STEPS && !(reached_negative && reached_positive); step++) {
+        if (!reached_negative) {
+            uv_negative -= search_offset * fxaa_step_size(step);
+            luma_end_negative = luma_at(uv_negative) - luma_local_average;
+            reached_negative = abs(luma_end_negative) >= gradient_scaled;
+        }
+        if (!reached_positive) {
+            uv_positive += search_offset * fxaa_step_size(step);
+            luma_end_positive = luma_at(uv_positive) - luma_local_average;
+            reached_positive = abs(luma_end_positive) >= gradient_scaled;
+        }
+    }
+
+    let distance_negative = select(uv.y - uv_negative.y, uv.x - uv_negative.x, is_horizontal);
+    let distance_positive = select(uv_positive.y - uv.y, uv_positive.x - uv.x, is_horizontal);
+    let negative_closer = distance_negative < distance_positive;
+    let edge_length = distance_negative + distance_positive;
+    let pixel_offset = -min(distance_negative, distance_positive) / edge_length + 0.5;
+
+    // Only blend when the closest end varies the same way as the centre
+    let luma_end = select(luma_end_positive, luma_end_negative, negative_closer);
+    let center_smaller = luma_center < luma_local_average;
+    var offset = select(0.0, pixel_offset, (luma_end < 0.0) != center_smaller);
+
+    // Sub-pixel aliasing, single texel features brighter or darker than their neighbourhood
+    let luma_average = (2.0 * (luma_ns + luma_we) + luma_w_corners + luma_e_corners) / 12.0;
+    let subpixel = saturate(abs(luma_average - luma_center) / luma_range);
+    let subpixel_smooth = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+    offset = max(offset, subpixel_smooth * subpixel_smooth * subpixel_quality);
+
+    var final_uv = uv;
+    if (is_horizontal) {
+        final_uv.y += offset * step_length;
+    } else {
+        final_uv.x += offset * step_length;
+    }
+    return sample_source(final_uv);
+}
+
+// === Vignette ===
+
+@fragment
+fn vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let intensity = params.values.x;
+    let radius = params.values.y;
+    let smoothness = max(params.values.z, 1e-4);
+
+    let color = sample_source(in.uv);
+    // 0.0 at the centre, 1.0 at the corners
+    let distance = length(in.uv * 2.0 - 1.0) / sqrt(2.0);
+    let falloff = smoothstep(radius, radius + smoothness, distance);
+    return vec4<f32>(color.rgb * (1.0 - intensity * falloff), color.a);
+}
+
+// === Colour Grading ===
+
+@fragment
+fn color_grading(in: VertexOutput) -> @location(0) vec4<f32> {
+    let strength = params.values.x;
+
+    // Display samples are linear, the table maps sRGB encoded colour
+    let color = sample_source(in.uv);
+    let encoded = linear_to_srgb(saturate(color.rgb));
+
+    // Sample texel centres, so 0.0 and 1.0 land exactly on the ends of the table
+    let size = f32(textureDimensions(lut_texture).x);
+    let coords = (encoded * (size - 1.0) + 0.5) / size;
+    let graded = textureSampleLevel(lut_texture, source_sampler, coords, 0.0).rgb;
+
+    return vec4<f32>(mix(color.rgb, srgb_to_linear(graded), strength), color.a);
+}
+
+// === Chromatic Aberration ===
+
+@fragment
+fn chromatic_aberration(in: VertexOutput) -> @location(0) vec4<f32> {
+    let intensity = params.values.x;
+
+    // Grows towards the edges, reaching `intensity` at the corners
+    let offset = (in.uv * 2.0 - 1.0) * intensity;
+    let center = sample_source(in.uv);
+    let red = sample_source(in.uv - offset).r;
+    let blue = sample_source(in.uv + offset).b;
+    return vec4<f32>(red, center.g, blue, center.a);
+}
+
+// === Film Grain ===
+
+// PCG hash, "Hash Functions for GPU Rendering" by Jarzynski and Olano
+fn pcg(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+@fragment
+fn film_grain(in: VertexOutput) -> @location(0) vec4<f32> {
+    let intensity = params.values.x;
+
+    let pixel = vec2<u32>(in.position.xy);
+    let seed = pcg(pixel.x + pcg(pixel.y + pcg(params.frame)));
+    let noise = f32(seed) / 4294967295.0 - 0.5;
+
+    // Strongest in the midtones, so shadows and highlights keep their detail
+    let color = sample_source(in.uv);
+    let luminance = saturate(dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    let response = 4.0 * luminance * (1.0 - luminance);
+    return vec4<f32>(max(color.rgb + noise * intensity * response, vec3<f32>(0.0)), color.a);
+}
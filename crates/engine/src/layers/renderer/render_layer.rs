@@ -1,23 +1,32 @@
 use crate::prelude::*;
 
 use crate::layers::renderer::systems::{
-    initialize_depth_textures, initialize_render_targets, initialize_shadow_maps,
-    update_camera_buffers_custom, update_depth_textures, update_render_targets, update_shadow_maps,
+    initialize_camera_buffers, initialize_depth_textures, initialize_render_targets,
+    initialize_shadow_maps, rebase_render_origin, update_camera_buffers_custom,
+    update_depth_textures, update_environment_lighting, update_environment_uniforms,
+    update_gpu_transforms, update_instanced_meshes, update_lights, update_look_at,
+    update_mesh_bounds, update_render_targets, update_shadow_cascades, update_shadow_depth_modes,
+    update_shadow_settings, update_visibility, update_wireframe_meshes,
+};
+use crate::shader::{
+    MaterialBindGroupLayouts, PipelineKey, ShaderCache, ShaderInstance, VertexLayout,
+};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::graph::{GraphResources, GraphTexture, RenderContext, RenderGraph, slot};
+use super::{
+    GizmoNode, IblBaker, InstanceCullNode, OpaqueNode, PostProcessNode, ShadowNode, TonemapNode,
+    TransparentNode, WireframeDraw, WireframeInstances, WireframeNode,
 };
-use crate::shader::{BindGroupRequirement, ShaderCache, ShaderInstance};
 
 pub struct RenderLayer {
     device: wgpu::Device,
     queue: wgpu::Queue,
     schedule: Schedule,
-    texture_bind_group_layout: wgpu::BindGroupLayout,
-    camera_bind_group_layout: wgpu::BindGroupLayout,
     transform_bind_group_layout: wgpu::BindGroupLayout,
-    shadow_bind_group_layout: wgpu::BindGroupLayout,
-    shadow_pipeline: wgpu::RenderPipeline,
-    shadow_uniform_layout: wgpu::BindGroupLayout,
-    surface_format: wgpu::TextureFormat,
-    shadow_map_size: u32,
+    draw_transforms: DrawTransforms,
 }
 
 impl RenderLayer {
@@ -70,21 +79,37 @@ impl RenderLayer {
                 label: Some("camera_bind_group_layout"),
             });
 
+        // Model matrices of every object and the object id of each drawn instance
+        let transform_storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
         let transform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
+                entries: &[transform_storage_entry(0), transform_storage_entry(1)],
                 label: Some("transform_bind_group_layout"),
             });
 
+        let gpu_transforms = GpuTransforms::new(&device);
+        let draw_transforms =
+            DrawTransforms::new(&device, &transform_bind_group_layout, &gpu_transforms);
+
+        let environment_texture_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
         let shadow_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
@@ -93,7 +118,7 @@ impl RenderLayer {
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
                             sample_type: wgpu::TextureSampleType::Depth,
                         },
                         count: None,
@@ -114,19 +139,21 @@ impl RenderLayer {
                         },
                         count: None,
                     },
+                    // All scene lights, see `GpuLights`
                     wgpu::BindGroupLayoutEntry {
                         binding: 3,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
                             has_dynamic_offset: false,
                             min_binding_size: None,
                         },
                         count: None,
                     },
+                    // Camera's `EnvironmentUniform`
                     wgpu::BindGroupLayoutEntry {
                         binding: 4,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
@@ -134,6 +161,18 @@ impl RenderLayer {
                         },
                         count: None,
                     },
+                    // Environment, irradiance and specular cubemaps, see `GpuEnvironmentLighting`
+                    environment_texture_entry(5, wgpu::TextureViewDimension::Cube),
+                    environment_texture_entry(6, wgpu::TextureViewDimension::Cube),
+                    environment_texture_entry(7, wgpu::TextureViewDimension::Cube),
+                    // BRDF lookup table
+                    environment_texture_entry(8, wgpu::TextureViewDimension::D2),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 9,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
                 ],
                 label: Some("shadow_bind_group_layout"),
             });
@@ -154,57 +193,80 @@ impl RenderLayer {
                 label: Some("shadow_uniform_layout"),
             });
 
-        // Create shadow rendering pipeline
-        let shadow_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shadow Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
-        });
-
-        let shadow_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Shadow Pipeline Layout"),
-                bind_group_layouts: &[&transform_bind_group_layout, &shadow_uniform_layout],
-                push_constant_ranges: &[],
+        // Layout of the compute pass culling instanced meshes for each camera
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let instance_cull_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    storage_entry(1, true),  // All instances
+                    storage_entry(2, true),  // Instance bounds
+                    storage_entry(3, false), // Visible instances
+                    storage_entry(4, false), // Indirect draw args
+                ],
+                label: Some("instance_cull_layout"),
             });
 
-        let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Shadow Pipeline"),
-            layout: Some(&shadow_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shadow_shader,
-                entry_point: Some("vertex"),
-                buffers: &[Vertex::desc()],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: None, // Depth-only pass
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState {
-                    constant: 4,      // Higher constant bias to reduce shadow acne
-                    slope_scale: 4.0, // Higher slope scale for angled surfaces
-                    clamp: 0.0,
-                },
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+        // Built-in passes, games add their own to the `RenderGraph` resource
+        let mut graph = RenderGraph::default();
+        graph
+            .add_node(
+                InstanceCullNode::NAME,
+                InstanceCullNode::new(&device, &instance_cull_layout),
+            )
+            .add_node(
+                ShadowNode::NAME,
+                ShadowNode::new(
+                    &device,
+                    &transform_bind_group_layout,
+                    &shadow_uniform_layout,
+                    &texture_bind_group_layout,
+                ),
+            )
+            .add_node(
+                OpaqueNode::NAME,
+                OpaqueNode::new(&device, &shadow_bind_group_layout),
+            )
+            .add_node(TransparentNode::NAME, TransparentNode)
+            .add_node(
+                WireframeNode::NAME,
+                WireframeNode::new(
+                    &device,
+                    &camera_bind_group_layout,
+                    &transform_bind_group_layout,
+                ),
+            )
+            .add_node(
+                GizmoNode::NAME,
+                GizmoNode::new(&device, &camera_bind_group_layout),
+            )
+            .add_node(
+                PostProcessNode::HDR_NAME,
+                PostProcessNode::new(&device, PostProcessStage::Hdr),
+            )
+            .add_node(TonemapNode::NAME, TonemapNode::new(&device))
+            .add_node(
+                PostProcessNode::LDR_NAME,
+                PostProcessNode::new(&device, PostProcessStage::Ldr),
+            );
 
         // ecs resources
         {
@@ -218,6 +280,21 @@ impl RenderLayer {
             ));
             world.insert_resource(ShadowBindGroupLayout(shadow_bind_group_layout.clone()));
             world.insert_resource(ShadowUniformLayout(shadow_uniform_layout.clone()));
+            world.insert_resource(InstanceCullLayout(instance_cull_layout));
+            world.insert_resource(GpuLights::new(&device));
+            world.insert_resource(gpu_transforms);
+            world.init_resource::<ShadowSettings>();
+            world.init_resource::<CullingStats>();
+            world.init_resource::<MsaaSettings>();
+            world.init_resource::<WireframeSettings>();
+            world.init_resource::<DebugView>();
+            world.init_resource::<RenderOrigin>();
+            world.init_resource::<EnvironmentLighting>();
+            let ibl_baker = IblBaker::new(&device, &queue);
+            world.insert_resource(ibl_baker.fallback(&device));
+            world.insert_resource(ibl_baker);
+            world.init_resource::<SupportedFeatures>();
+            world.insert_resource(graph);
 
             // Create GpuContext with all bind group layouts
             let gpu_context = GpuContext::new(
@@ -228,167 +305,77 @@ impl RenderLayer {
             world.insert_resource(gpu_context);
         }
 
-        // shaders - use sRGB format for render targets
-        let surface_format = wgpu::TextureFormat::Bgra8UnormSrgb;
-
         // Initialize empty ShaderCache - shaders will be registered by game code
         {
             let mut world = context.world.lock().unwrap();
-            world.insert_resource(ShaderCache::new());
+            let supported_features = world
+                .get_resource::<SupportedFeatures>()
+                .copied()
+                .unwrap_or_default();
+            world.insert_resource(ShaderCache::new(
+                device.clone(),
+                MaterialBindGroupLayouts {
+                    texture: texture_bind_group_layout,
+                    camera: camera_bind_group_layout,
+                    transform: transform_bind_group_layout.clone(),
+                    shadow: shadow_bind_group_layout,
+                },
+                supported_features,
+            ));
         }
 
         // ecs
         let mut schedule = Schedule::default();
-        schedule.add_systems((
-            // Use trait-based generated systems for all components
-            gpu_initialize_system::<Mesh>,
-            gpu_update_system::<Mesh>,
-            gpu_initialize_system::<Texture>,
-            // Texture has no update system (doesn't implement GpuUpdate)
-            gpu_initialize_system::<Transform>,
-            gpu_update_system::<Transform>,
-            gpu_initialize_with_transform_system::<Camera>,
-            // Use custom camera update system that also watches GpuCamera changes (for aspect ratio)
-            update_camera_buffers_custom,
-            // Instanced mesh systems for LOD rendering
-            gpu_initialize_system::<InstancedLodMesh>,
-            gpu_update_system::<InstancedLodMesh>,
-            // Keep hand-written systems for RenderTarget (special case - depends on WindowSize)
-            initialize_render_targets,
-            update_render_targets,
-            // Depth texture systems
-            initialize_depth_textures,
-            update_depth_textures,
-            // Shadow map systems
-            initialize_shadow_maps,
-            update_shadow_maps,
-        ));
+        // Aim `LookAt` entities before anything reads their transforms
+        schedule.add_systems(update_look_at);
+        // Follow the main camera before any matrix is made relative to the origin
+        schedule.add_systems(rebase_render_origin);
+        schedule.add_systems(update_wireframe_meshes);
+        schedule.add_systems(update_shadow_depth_modes);
+        schedule.add_systems(
+            (
+                // Use trait-based generated systems for all components
+                gpu_initialize_system::<Mesh>,
+                gpu_update_system::<Mesh>,
+                gpu_initialize_system::<Texture>,
+                // Texture has no update system (doesn't implement GpuUpdate)
+                update_gpu_transforms,
+                initialize_camera_buffers,
+                // Use custom camera update system that also watches GpuCamera changes (for aspect ratio)
+                update_camera_buffers_custom,
+                // Instanced mesh systems for LOD rendering
+                gpu_initialize_system::<InstancedLodMesh>,
+                update_instanced_meshes,
+                // Keep hand-written systems for RenderTarget (special case - depends on WindowSize)
+                initialize_render_targets,
+                update_render_targets,
+                // Depth texture systems
+                initialize_depth_textures,
+                update_depth_textures,
+                // Light and shadow map systems
+                update_lights,
+                update_environment_lighting,
+                initialize_shadow_maps.after(update_environment_lighting),
+                update_shadow_settings,
+                update_environment_uniforms.after(initialize_shadow_maps),
+                update_shadow_cascades.after(update_lights),
+                // Frustum culling
+                update_mesh_bounds,
+                update_visibility
+                    .after(update_mesh_bounds)
+                    .after(update_render_targets),
+            )
+                .after(update_look_at)
+                .after(rebase_render_origin),
+        );
 
         Self {
             device,
             queue,
             schedule,
-            texture_bind_group_layout,
-            camera_bind_group_layout,
             transform_bind_group_layout,
-            shadow_bind_group_layout,
-            shadow_pipeline,
-            shadow_uniform_layout,
-            surface_format,
-            shadow_map_size: 2048, // 2K shadow map
-        }
-    }
-
-    fn reload_shader(
-        &mut self,
-        shader_type: &Shader,
-        render_mode: RenderMode,
-        shader: wgpu::ShaderModule,
-        shader_source: &str,
-    ) -> Result<ShaderInstance, Box<dyn std::error::Error>> {
-        log::info!(
-            "Reloading {} shader with render mode {:?}...",
-            shader_type,
-            render_mode
-        );
-
-        // Parse bind group requirements from reloaded shader FIRST
-        let bind_group_requirements = BindGroupRequirement::parse_from_shader(shader_source);
-        log::info!(
-            "Reloading {} shader with bind groups: {:?}",
-            shader_type,
-            bind_group_requirements
-        );
-
-        // Build bind group layouts dynamically based on shader requirements
-        let mut layouts = Vec::new();
-        for requirement in &bind_group_requirements {
-            if let Some(req) = requirement {
-                let layout = match req {
-                    BindGroupRequirement::Texture => &self.texture_bind_group_layout,
-                    BindGroupRequirement::Camera => &self.camera_bind_group_layout,
-                    BindGroupRequirement::Transform => &self.transform_bind_group_layout,
-                    BindGroupRequirement::Shadow => &self.shadow_bind_group_layout,
-                    BindGroupRequirement::Unknown(name) => {
-                        return Err(
-                            format!("Unknown bind group requirement '{}' in shader", name).into(),
-                        );
-                    }
-                };
-                layouts.push(layout);
-            }
+            draw_transforms,
         }
-
-        // Recreate render pipeline with dynamic layouts
-        let render_pipeline_layout =
-            self.device
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("Render Pipeline Layout"),
-                    bind_group_layouts: &layouts,
-                    push_constant_ranges: &[],
-                });
-
-        // Different vertex buffer layout for instanced shaders
-        let vertex_buffer_layout = Vertex::desc();
-        let instance_buffer_layout = InstanceData::desc();
-        
-        let vertex_buffers: &[wgpu::VertexBufferLayout] = if matches!(shader_type, Shader::Instanced) {
-            &[vertex_buffer_layout, instance_buffer_layout]
-        } else {
-            &[vertex_buffer_layout]
-        };
-
-        let render_pipeline = self
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: Some("vertex"),
-                    buffers: vertex_buffers,
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: Some("fragment"),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: self.surface_format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: render_mode.polygon_mode,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: wgpu::TextureFormat::Depth32Float,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-                cache: None,
-            });
-
-        Ok(ShaderInstance {
-            module: shader,
-            pipeline: render_pipeline,
-            bind_group_requirements,
-        })
     }
 }
 
@@ -396,72 +383,19 @@ impl Layer for RenderLayer {
     fn frame(&mut self, context: &LayerContext) -> std::result::Result<(), wgpu::SurfaceError> {
         let mut world = context.world.lock().unwrap();
 
-        // Check for shader hot reload in ShaderCache
+        // Check for shader hot reload in ShaderCache, pipelines are rebuilt lazily
         let reloaded_shaders = {
             if let Some(mut shader_cache) = world.get_resource_mut::<ShaderCache>() {
-                shader_cache.check_hot_reload(&self.device)
+                shader_cache.check_hot_reload()
             } else {
                 Vec::new()
             }
         };
 
-        // Process reloaded shaders - need to recreate pipelines for all render modes
         for (shader, reload_result) in reloaded_shaders {
             use crate::layers::raytracer::ShaderError;
             match reload_result {
-                Ok((shader_module, shader_source)) => {
-                    // Get supported features to determine which render modes to create
-                    let supported_features = world.get_resource::<SupportedFeatures>();
-
-                    // Build list of render modes based on supported features
-                    let mut render_modes = vec![RenderMode::filled()];
-
-                    if supported_features
-                        .map(|f| f.polygon_mode_line)
-                        .unwrap_or(false)
-                    {
-                        render_modes.push(RenderMode::wireframe());
-                    }
-
-                    if supported_features
-                        .map(|f| f.polygon_mode_point)
-                        .unwrap_or(false)
-                    {
-                        render_modes.push(RenderMode {
-                            polygon_mode: wgpu::PolygonMode::Point,
-                        });
-                    }
-
-                    for render_mode in &render_modes {
-                        match self.reload_shader(
-                            &shader,
-                            *render_mode,
-                            shader_module.clone(),
-                            &shader_source,
-                        ) {
-                            Ok(shader_instance) => {
-                                // Update shader cache with new instance
-                                if let Some(mut shader_cache) =
-                                    world.get_resource_mut::<ShaderCache>()
-                                {
-                                    shader_cache.update_shader(
-                                        &shader,
-                                        *render_mode,
-                                        shader_instance,
-                                    );
-                                }
-                            }
-                            Err(e) => {
-                                log::error!(
-                                    "Failed to recreate pipeline for {} with render mode {:?}: {}",
-                                    shader,
-                                    render_mode,
-                                    e
-                                );
-                            }
-                        }
-                    }
-
+                Ok(()) => {
                     // Clear any previous error
                     if let Some(mut errors) = world.get_resource_mut::<ShaderError>() {
                         errors.0.remove(&shader);
@@ -469,6 +403,7 @@ impl Layer for RenderLayer {
                 }
                 Err(error_msg) => {
                     // Shader reload failed - store error
+                    log::error!("Failed to reload shader {}: {}", shader, error_msg);
                     if let Some(mut errors) = world.get_resource_mut::<ShaderError>() {
                         errors.0.insert(shader, error_msg);
                     }
@@ -479,240 +414,772 @@ impl Layer for RenderLayer {
         // Run the schedule first before any queries
         self.schedule.run(&mut world);
 
+        // Nodes run outside the world, so they can read it while recording
+        let delta_time = context.delta_time.as_secs_f32();
+        let mut graph = world.remove_resource::<RenderGraph>().unwrap_or_default();
+        graph.prepare(&self.device, &self.queue, &mut world, delta_time);
+
+        let features = world
+            .get_resource::<SupportedFeatures>()
+            .copied()
+            .unwrap_or_default();
+        let wireframe_settings = world
+            .get_resource::<WireframeSettings>()
+            .copied()
+            .unwrap_or_default();
+        let debug_view = world
+            .get_resource::<DebugView>()
+            .copied()
+            .unwrap_or_default();
+
+        // Make sure a pipeline exists for every material and target combination about to be drawn
+        {
+            let mut target_query = world.query::<(&Camera, &GpuHdrTarget, &GpuDepthTexture)>();
+            let mut mesh_materials = world.query_filtered::<&Material, With<GpuMesh>>();
+            let mut instanced_materials =
+                world.query_filtered::<&Material, With<GpuInstancedLodMesh>>();
+
+            let mut keys = HashSet::new();
+            for (camera, target, depth) in target_query.iter(&world) {
+                // Materials falling back to the wireframe pass never get a pipeline of their own
+                let mesh_materials = mesh_materials
+                    .iter(&world)
+                    .filter(|material| !material.needs_wireframe_fallback(&features));
+                let instanced_materials = instanced_materials
+                    .iter(&world)
+                    .filter(|material| !material.needs_wireframe_fallback(&features));
+                for material in mesh_materials {
+                    keys.insert(pipeline_key(
+                        material,
+                        VertexLayout::Standard,
+                        camera,
+                        target,
+                        depth,
+                        debug_view,
+                    ));
+                }
+                for material in instanced_materials {
+                    keys.insert(pipeline_key(
+                        material,
+                        VertexLayout::Instanced,
+                        camera,
+                        target,
+                        depth,
+                        debug_view,
+                    ));
+                }
+            }
+
+            if let Some(mut shader_cache) = world.get_resource_mut::<ShaderCache>() {
+                for key in &keys {
+                    shader_cache.get_or_create_pipeline(key);
+                }
+            }
+        }
+
         // Store cameras as a separate QueryState to avoid nested mutable borrows
-        let mut camera_query =
-            world.query::<(&GpuCamera, &GpuRenderTarget, &GpuDepthTexture, &GpuShadowMap)>();
-        let mut mesh_query = world.query::<(&Material, &GpuMesh, &GpuTexture, &GpuTransform)>();
-        let mut instanced_mesh_query = world.query::<(&Material, &GpuInstancedLodMesh, &GpuTexture)>();
+        let mut camera_query = world.query::<(
+            Entity,
+            &Camera,
+            &Transform,
+            &GpuCamera,
+            &GpuRenderTarget,
+            &GpuHdrTarget,
+            Option<&Tonemapping>,
+            Option<&PostProcessStack>,
+            &GpuDepthTexture,
+            &GpuShadowMap,
+            &VisibleEntities,
+            &GpuVisibleInstances,
+        )>();
+        let mut mesh_query = world.query::<(
+            Entity,
+            &Material,
+            &GpuMesh,
+            &GpuTexture,
+            &GpuTransform,
+            &Transform,
+            Option<&Aabb>,
+            Has<NotShadowReceiver>,
+            Option<&GpuWireframeMesh>,
+            Has<Wireframe>,
+        )>();
+        let mut instanced_mesh_query = world.query::<(
+            Entity,
+            &Material,
+            &InstancedLodMesh,
+            &GpuInstancedLodMesh,
+            &GpuTexture,
+            Option<&Transform>,
+            Option<&Aabb>,
+            Has<NotShadowReceiver>,
+            Option<&GpuWireframeMesh>,
+            Has<Wireframe>,
+        )>();
+        let mut shadow_caster_query = world.query_filtered::<(
+            Option<&Material>,
+            &GpuMesh,
+            Option<&GpuTexture>,
+            &GpuTransform,
+        ), Without<NotShadowCaster>>();
+        let mut instanced_shadow_caster_query = world.query_filtered::<(
+            Option<&Material>,
+            &GpuInstancedLodMesh,
+            Option<&GpuTexture>,
+        ), Without<NotShadowCaster>>();
 
         // Get shader cache for looking up pipelines
         let shader_cache = world.get_resource::<ShaderCache>();
+        let gpu_transforms = world.resource::<GpuTransforms>();
+        // Timestamp queries for the passes, while profiling on adapters that support them
+        let gpu_profiler = world.get_resource::<GpuProfiler>();
+
+        // Collect every draw once, each camera then picks the ones it can see
+        let mut draws = Vec::new();
+        for (
+            entity,
+            material,
+            mesh,
+            texture,
+            gpu_transform,
+            transform,
+            bounds,
+            not_receiver,
+            wireframe,
+            wireframe_overlay,
+        ) in mesh_query.iter(&world)
+        {
+            let position = bounds.map_or(transform.position, |bounds| {
+                transform.transform_point(&bounds.center())
+            });
+            draws.push(QueuedDraw {
+                entity,
+                mesh: MeshDraw::Mesh(material, mesh, texture, gpu_transform),
+                position,
+                receives_shadows: !not_receiver,
+                wireframe,
+                wireframe_overlay: wireframe_overlay || wireframe_settings.overlay,
+            });
+        }
+        for (
+            entity,
+            material,
+            lod_mesh,
+            instanced_mesh,
+            texture,
+            transform,
+            bounds,
+            not_receiver,
+            wireframe,
+            wireframe_overlay,
+        ) in instanced_mesh_query.iter(&world)
+        {
+            // Chunks of entities without a `Transform` are placed in the world
+            let transform = transform.cloned().unwrap_or_default();
+            let position = bounds
+                .and_then(|bounds| lod_mesh.bounds_center(bounds, &transform))
+                .unwrap_or(transform.position);
+            draws.push(QueuedDraw {
+                entity,
+                mesh: MeshDraw::Instanced(material, instanced_mesh, texture),
+                position,
+                receives_shadows: !not_receiver,
+                wireframe,
+                wireframe_overlay: wireframe_overlay || wireframe_settings.overlay,
+            });
+        }
 
         // Debug: count meshes
-        let mesh_count = mesh_query.iter(&world).count();
-        let instanced_count = instanced_mesh_query.iter(&world).count();
-        if mesh_count > 0 || instanced_count > 0 {
-            log::info!("Rendering {} regular meshes, {} instanced meshes", mesh_count, instanced_count);
+        if !draws.is_empty() {
+            let instanced_count = draws
+                .iter()
+                .filter(|draw| matches!(draw.mesh, MeshDraw::Instanced(..)))
+                .count();
+            log::info!(
+                "Rendering {} regular meshes, {} instanced meshes",
+                draws.len() - instanced_count,
+                instanced_count
+            );
         }
 
-        // Process each camera
-        for (camera, target, depth, shadow_map) in camera_query.iter(&world) {
-            let view = target
-                .texture
-                .create_view(&wgpu::TextureViewDescriptor::default());
+        // Shadow casters are the same for every camera, draw each mesh once for all its objects
+        let mut shadow_object_ids = Vec::new();
+        let shadow_batches = shadow_batches(
+            shadow_caster_query
+                .iter(&world)
+                .map(|(material, mesh, texture, transform)| {
+                    (material, mesh, texture, transform.object_id)
+                }),
+            &mut shadow_object_ids,
+        );
 
-            let mut encoder = self
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Render Encoder"),
-                });
+        let instanced_casters: Vec<ShadowCaster<GpuInstancedLodMesh>> =
+            instanced_shadow_caster_query
+                .iter(&world)
+                .filter_map(|(material, mesh, texture)| ShadowCaster::new(material, mesh, texture))
+                .collect();
+        let has_environment = world.resource::<GpuEnvironmentLighting>().has_environment();
 
-            // === Shadow Pass: Render from light's perspective ===
-            {
-                let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Shadow Pass"),
-                    color_attachments: &[], // No color output
-                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &shadow_map.view,
-                        depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0),
-                            store: wgpu::StoreOp::Store,
-                        }),
-                        stencil_ops: None,
-                    }),
-                    occlusion_query_set: None,
-                    timestamp_writes: None,
-                });
-
-                shadow_pass.set_pipeline(&self.shadow_pipeline);
-
-                // Render all meshes from light's perspective
-                for (_material, mesh, _texture, transform) in mesh_query.iter(&world) {
-                    shadow_pass.set_bind_group(0, &transform.bind_group, &[]);
-                    shadow_pass.set_bind_group(1, &shadow_map.shadow_uniform_bind_group, &[]);
-                    shadow_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-                    shadow_pass.set_index_buffer(mesh.index_buffer.slice(..), index_format());
-                    shadow_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+        // Process each camera, in render order
+        let mut cameras: Vec<_> = camera_query.iter(&world).collect();
+        cameras.sort_by_key(|(_, camera, ..)| camera.order);
+        for (
+            camera_entity,
+            camera_settings,
+            camera_transform,
+            camera,
+            target,
+            hdr_target,
+            tonemapping,
+            post_process,
+            depth,
+            shadow_map,
+            visible,
+            visible_instances,
+        ) in cameras
+        {
+            // Frustum culling: keep the meshes this camera sees, instanced meshes are culled
+            // per instance by the culling pass
+            let visible_draws: Vec<(&QueuedDraw, Option<&GpuCulledInstances>)> = draws
+                .iter()
+                .filter_map(|draw| match draw.mesh {
+                    MeshDraw::Mesh(..) => visible
+                        .meshes
+                        .contains(&draw.entity)
+                        .then_some((draw, None)),
+                    MeshDraw::Instanced(..) => visible_instances
+                        .meshes
+                        .get(&draw.entity)
+                        .filter(|culled| culled.instance_count > 0)
+                        .map(|culled| (draw, Some(culled))),
+                })
+                .collect();
+
+            // Resolve the pipeline of every draw, then sort opaque draws by pipeline, texture and
+            // mesh and transparent ones back-to-front
+            // Relative to the camera itself, so far from the world origin the order holds
+            let camera_position = camera_transform.position;
+            let view_matrix =
+                camera_settings.view_matrix_relative(camera_transform, &camera_position);
+            let mut opaque = Vec::new();
+            let mut transparent = Vec::new();
+            let mut wireframes = Vec::new();
+            for (draw, culled) in &visible_draws {
+                let material = draw.mesh.material();
+                let fallback = material.needs_wireframe_fallback(&features);
+                if let Some(wireframe) = draw.wireframe
+                    && (fallback || draw.wireframe_overlay)
+                {
+                    wireframes.push((draw, *culled, wireframe, !fallback));
+                }
+                if fallback {
+                    continue;
                 }
-            }
 
-            // === Main Pass: Render scene normally with shadows ===
-            {
-                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Render Pass"),
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.1,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
-                        },
-                        depth_slice: None,
-                    })],
-                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &depth.view,
-                        depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0),
-                            store: wgpu::StoreOp::Store,
-                        }),
-                        stencil_ops: None,
-                    }),
-                    occlusion_query_set: None,
-                    timestamp_writes: None,
-                });
-
-                // Render regular meshes
-                for (material, mesh, texture, transform) in mesh_query.iter(&world) {
-                    // Look up shader pipeline from cache with render mode
-                    let shader_instance = shader_cache.as_ref().and_then(|cache| {
-                        cache.get_shader(&material.shader, &material.render_mode)
-                    });
-
-                    if let Some(shader_instance) = shader_instance {
-                        render_pass.set_pipeline(&shader_instance.pipeline);
-
-                        // Set bind groups based on shader requirements
-                        for (index, requirement) in
-                            shader_instance.bind_group_requirements.iter().enumerate()
-                        {
-                            if let Some(req) = requirement {
-                                match req {
-                                    BindGroupRequirement::Texture => {
-                                        render_pass.set_bind_group(
-                                            index as u32,
-                                            Some(&texture.bind_group),
-                                            &[],
-                                        );
-                                    }
-                                    BindGroupRequirement::Camera => {
-                                        render_pass.set_bind_group(
-                                            index as u32,
-                                            &camera.bind_group,
-                                            &[],
-                                        );
-                                    }
-                                    BindGroupRequirement::Transform => {
-                                        render_pass.set_bind_group(
-                                            index as u32,
-                                            &transform.bind_group,
-                                            &[],
-                                        );
-                                    }
-                                    BindGroupRequirement::Shadow => {
-                                        render_pass.set_bind_group(
-                                            index as u32,
-                                            &shadow_map.bind_group,
-                                            &[],
-                                        );
-                                    }
-                                    BindGroupRequirement::Unknown(name) => {
-                                        log::warn!(
-                                            "Unknown bind group requirement '{}' at index {}",
-                                            name,
-                                            index
-                                        );
-                                    }
-                                }
-                            }
-                        }
+                let key = pipeline_key(
+                    material,
+                    draw.mesh.vertex_layout(),
+                    camera_settings,
+                    hdr_target,
+                    depth,
+                    debug_view,
+                );
+                let Some(shader) = shader_cache.and_then(|cache| cache.get_pipeline(&key)) else {
+                    log::warn!("Shader '{}' not found in cache", material.shader);
+                    continue;
+                };
 
-                        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-                        render_pass.set_index_buffer(mesh.index_buffer.slice(..), index_format());
-                        render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
-                    } else {
-                        log::warn!("Shader '{}' not found in cache", material.shader);
-                    }
+                let batch = Batch {
+                    draw,
+                    culled: *culled,
+                    shader,
+                    transforms: 0..0,
+                };
+                if material.render_mode.is_transparent() {
+                    // View space looks down -Z, so the distance in front of the camera is -z
+                    let position: Vector3<f32> = (draw.position - camera_position).cast();
+                    let view_depth = -(view_matrix * position.push(1.0)).z;
+                    transparent.push((view_depth, batch));
+                } else {
+                    opaque.push(batch);
                 }
+            }
+            opaque.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+            transparent.sort_by(|a, b| b.0.total_cmp(&a.0));
 
-                // Render instanced meshes (uses instanced shader with per-instance transforms)
-                for (material, instanced_mesh, texture) in instanced_mesh_query.iter(&world) {
-                    // Skip if no instances
-                    if instanced_mesh.instance_count == 0 {
-                        log::warn!("Skipping instanced mesh with 0 instances");
-                        continue;
-                    }
-                    
-                    log::info!("Rendering instanced mesh with {} instances", instanced_mesh.instance_count);
-
-                    // Look up shader pipeline from cache with render mode
-                    let shader_instance = shader_cache.as_ref().and_then(|cache| {
-                        cache.get_shader(&material.shader, &material.render_mode)
-                    });
-
-                    if let Some(shader_instance) = shader_instance {
-                        render_pass.set_pipeline(&shader_instance.pipeline);
-
-                        // Set bind groups (instanced shader doesn't use Transform bind group)
-                        for (index, requirement) in
-                            shader_instance.bind_group_requirements.iter().enumerate()
-                        {
-                            if let Some(req) = requirement {
-                                match req {
-                                    BindGroupRequirement::Texture => {
-                                        render_pass.set_bind_group(
-                                            index as u32,
-                                            Some(&texture.bind_group),
-                                            &[],
-                                        );
-                                    }
-                                    BindGroupRequirement::Camera => {
-                                        render_pass.set_bind_group(
-                                            index as u32,
-                                            &camera.bind_group,
-                                            &[],
-                                        );
-                                    }
-                                    BindGroupRequirement::Shadow => {
-                                        render_pass.set_bind_group(
-                                            index as u32,
-                                            &shadow_map.bind_group,
-                                            &[],
-                                        );
-                                    }
-                                    BindGroupRequirement::Transform => {
-                                        // Instanced shader doesn't use transform bind group
-                                        log::warn!("Instanced shader should not require Transform bind group");
-                                    }
-                                    BindGroupRequirement::Unknown(name) => {
-                                        log::warn!(
-                                            "Unknown bind group requirement '{}' at index {}",
-                                            name,
-                                            index
-                                        );
-                                    }
-                                }
-                            }
+            // Merge draws of the same mesh into instanced draws, the shadow casters come first
+            let mut object_ids = shadow_object_ids.clone();
+            let opaque = batch_draws(opaque, &mut object_ids);
+            let transparent = batch_draws(
+                transparent.into_iter().map(|(_, batch)| batch),
+                &mut object_ids,
+            );
+            let wireframes = wireframes
+                .into_iter()
+                .filter_map(|(draw, culled, mesh, overlay)| {
+                    let instances = match draw.mesh {
+                        MeshDraw::Mesh(.., transform) => {
+                            let object = object_ids.len() as u32;
+                            object_ids.push(transform.object_id);
+                            WireframeInstances::Objects(object..object + 1)
                         }
+                        MeshDraw::Instanced(..) => WireframeInstances::Culled(culled?),
+                    };
+                    Some(WireframeDraw {
+                        mesh,
+                        instances,
+                        overlay,
+                    })
+                })
+                .collect();
 
-                        // Set vertex buffers: slot 0 = geometry, slot 1 = instance data
-                        render_pass.set_vertex_buffer(0, instanced_mesh.vertex_buffer.slice(..));
-                        render_pass.set_vertex_buffer(1, instanced_mesh.instance_buffer.slice(..));
-                        render_pass.set_index_buffer(instanced_mesh.index_buffer.slice(..), index_format());
-                        
-                        // Draw with instancing
-                        render_pass.draw_indexed(
-                            0..instanced_mesh.index_count,
-                            0,
-                            0..instanced_mesh.instance_count,
-                        );
-                        
-                        log::debug!("Drew {} instances", instanced_mesh.instance_count);
-                    } else {
-                        log::warn!("Shader '{}' not found in cache", material.shader);
-                    }
-                }
+            // Writes are ordered between submissions, so every camera can reuse the same buffer
+            self.draw_transforms.write(
+                &self.device,
+                &self.queue,
+                &self.transform_bind_group_layout,
+                gpu_transforms,
+                &object_ids,
+            );
+
+            let view = CameraView {
+                settings: camera_settings,
+                camera,
+                hdr_target,
+                depth,
+                shadow_map,
+                tonemapping,
+                post_process,
+                culled: visible_draws
+                    .iter()
+                    .filter_map(|(_, culled)| *culled)
+                    .collect(),
+                opaque,
+                transparent,
+                wireframes,
+                transforms: &self.draw_transforms.bind_group,
+                shadow_batches: &shadow_batches,
+                instanced_casters: &instanced_casters,
+                has_environment,
+                debug_view,
+            };
+
+            let mut resources = GraphResources::default();
+            resources.import_texture(
+                slot::SHADOW_MAP,
+                GraphTexture {
+                    texture: shadow_map.texture.clone(),
+                    view: shadow_map.view.clone(),
+                },
+            );
+            resources.import_texture(
+                slot::HDR,
+                GraphTexture {
+                    texture: hdr_target.texture.clone(),
+                    view: hdr_target.view.clone(),
+                },
+            );
+            resources.import_texture(
+                slot::DEPTH,
+                GraphTexture {
+                    texture: depth.texture.clone(),
+                    view: depth.view.clone(),
+                },
+            );
+            resources.import_texture(slot::TARGET, GraphTexture::new(&target.texture));
+
+            let mut render_context = RenderContext {
+                device: &self.device,
+                queue: &self.queue,
+                encoder: self
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("Render Encoder"),
+                    }),
+                world: &world,
+                camera: camera_entity,
+                delta_time,
+                resources,
+                target_size: (target.texture.width(), target.texture.height()),
+                view: &view,
+                readbacks: Vec::new(),
+                profiler: gpu_profiler,
             };
+            graph.run(&mut render_context);
 
+            let RenderContext {
+                encoder, readbacks, ..
+            } = render_context;
             self.queue.submit(std::iter::once(encoder.finish()));
+
+            for readback in readbacks {
+                readback.map();
+            }
         }
 
+        graph.cleanup(&world);
+        world.insert_resource(graph);
+
         Ok(())
     }
 
     fn detach(&mut self, _context: &LayerContext) {}
 }
+
+/// A single mesh draw collected from the world
+pub(crate) struct QueuedDraw<'w> {
+    pub entity: Entity,
+    pub mesh: MeshDraw<'w>,
+    /// World position used for depth sorting, the center of the bounds if there are any
+    pub position: Point3<f64>,
+    pub receives_shadows: bool,
+    /// Barycentric copy of the mesh while it is drawn by the wireframe pass
+    pub wireframe: Option<&'w GpuWireframeMesh>,
+    /// Edges drawn over the shaded mesh, by `Wireframe` or `WireframeSettings::overlay`
+    pub wireframe_overlay: bool,
+}
+
+/// The mesh and resources of a draw
+pub(crate) enum MeshDraw<'w> {
+    Mesh(&'w Material, &'w GpuMesh, &'w GpuTexture, &'w GpuTransform),
+    Instanced(&'w Material, &'w GpuInstancedLodMesh, &'w GpuTexture),
+}
+
+impl MeshDraw<'_> {
+    fn material(&self) -> &Material {
+        match self {
+            MeshDraw::Mesh(material, ..) | MeshDraw::Instanced(material, ..) => material,
+        }
+    }
+
+    fn vertex_layout(&self) -> VertexLayout {
+        match self {
+            MeshDraw::Mesh(..) => VertexLayout::Standard,
+            MeshDraw::Instanced(..) => VertexLayout::Instanced,
+        }
+    }
+}
+
+/// Pipeline key for drawing a material into a camera's targets
+///
+/// Transparent materials are drawn in the sorted transparent pass, which always
+/// depth tests against the opaque geometry but never writes depth.
+fn pipeline_key(
+    material: &Material,
+    vertex_layout: VertexLayout,
+    camera: &Camera,
+    target: &GpuHdrTarget,
+    depth: &GpuDepthTexture,
+    debug_view: DebugView,
+) -> PipelineKey {
+    let mut key = PipelineKey::for_targets(
+        material,
+        vertex_layout,
+        target.draw_texture(),
+        Some(&depth.texture),
+    );
+    if key.render_mode.is_transparent() {
+        key.render_mode.depth_test = true;
+        key.render_mode.depth_write = false;
+    }
+    key.depth_mode = camera.depth_mode;
+    key.debug_view = debug_view;
+    key
+}
+
+/// Per-camera draws and resources the built-in nodes record their passes with
+pub(crate) struct CameraView<'a> {
+    pub settings: &'a Camera,
+    pub camera: &'a GpuCamera,
+    pub hdr_target: &'a GpuHdrTarget,
+    pub depth: &'a GpuDepthTexture,
+    pub shadow_map: &'a GpuShadowMap,
+    pub tonemapping: Option<&'a Tonemapping>,
+    pub post_process: Option<&'a PostProcessStack>,
+    /// Instanced meshes this camera sees, compacted by the culling pass
+    pub culled: Vec<&'a GpuCulledInstances>,
+    /// Sorted by pipeline, texture and mesh
+    pub opaque: Vec<Batch<'a, 'a>>,
+    /// Sorted back-to-front
+    pub transparent: Vec<Batch<'a, 'a>>,
+    /// Wireframe fallbacks and overlays
+    pub wireframes: Vec<WireframeDraw<'a>>,
+    /// Model matrices and the object ids of this camera's draws
+    pub transforms: &'a wgpu::BindGroup,
+    /// Shadow casting meshes and their range of the object ids, shared by every camera
+    pub shadow_batches: &'a [ShadowBatch<'a>],
+    pub instanced_casters: &'a [ShadowCaster<'a, GpuInstancedLodMesh>],
+    pub has_environment: bool,
+    /// Shading replaced by a visualization, the skybox and HDR effects are skipped
+    pub debug_view: DebugView,
+}
+
+/// One or more draws of the same mesh, drawn as a single instanced draw
+pub(crate) struct Batch<'a, 'w> {
+    pub draw: &'a QueuedDraw<'w>,
+    /// Instances that survived the culling pass, for instanced meshes
+    pub culled: Option<&'a GpuCulledInstances>,
+    pub shader: Arc<ShaderInstance>,
+    /// Range of the object ids buffer holding the object of each merged draw
+    pub transforms: Range<u32>,
+}
+
+impl Batch<'_, '_> {
+    /// Draws with equal keys can share a single instanced draw
+    fn sort_key(&self) -> (&wgpu::RenderPipeline, u64, u64, bool) {
+        let (texture, mesh) = match self.draw.mesh {
+            MeshDraw::Mesh(_, mesh, texture, _) => (texture.content_id, mesh.content_id),
+            MeshDraw::Instanced(_, _, texture) => (texture.content_id, 0),
+        };
+        (
+            &self.shader.pipeline,
+            texture,
+            mesh,
+            self.draw.receives_shadows,
+        )
+    }
+}
+
+/// Merge consecutive draws sharing a key into instanced draws
+///
+/// Appends the object id of every regular mesh draw to `object_ids`, in draw order.
+/// Instanced meshes keep their own instance buffers and are never merged.
+fn batch_draws<'a, 'w>(
+    draws: impl IntoIterator<Item = Batch<'a, 'w>>,
+    object_ids: &mut Vec<u32>,
+) -> Vec<Batch<'a, 'w>> {
+    let mut batches: Vec<Batch> = Vec::new();
+
+    for mut batch in draws {
+        let MeshDraw::Mesh(.., transform) = batch.draw.mesh else {
+            batches.push(batch);
+            continue;
+        };
+
+        let object = object_ids.len() as u32;
+        object_ids.push(transform.object_id);
+
+        match batches.last_mut() {
+            Some(last)
+                if matches!(last.draw.mesh, MeshDraw::Mesh(..))
+                    && last.sort_key() == batch.sort_key() =>
+            {
+                last.transforms.end += 1;
+            }
+            _ => {
+                batch.transforms = object..object + 1;
+                batches.push(batch);
+            }
+        }
+    }
+
+    batches
+}
+
+/// GPU data drawn together with other data holding the same content
+pub(crate) trait ContentId {
+    fn content_id(&self) -> u64;
+}
+
+impl ContentId for GpuMesh {
+    fn content_id(&self) -> u64 {
+        self.content_id
+    }
+}
+
+impl ContentId for GpuTexture {
+    fn content_id(&self) -> u64 {
+        self.content_id
+    }
+}
+
+/// A mesh drawn into the shadow map, with the texture cut-outs sample their coverage from
+pub(crate) struct ShadowCaster<'w, M, T = GpuTexture> {
+    pub mesh: &'w M,
+    /// `None` for opaque casters, drawn without a fragment stage
+    pub cutout: Option<&'w T>,
+}
+
+impl<'w, M, T> ShadowCaster<'w, M, T> {
+    /// Caster of a mesh drawn with `material`, `None` if the material is transparent
+    ///
+    /// Blended surfaces would shadow like solid ones from a depth-only pass, so they cast none.
+    /// Meshes without a material are opaque.
+    pub fn new(material: Option<&Material>, mesh: &'w M, texture: Option<&'w T>) -> Option<Self> {
+        let render_mode = material.map(|material| material.render_mode);
+        if render_mode.is_some_and(|mode| mode.is_transparent()) {
+            return None;
+        }
+
+        let cutout = texture.filter(|_| render_mode.is_some_and(|mode| mode.is_cutout()));
+        Some(Self { mesh, cutout })
+    }
+}
+
+impl<M: ContentId, T: ContentId> ShadowCaster<'_, M, T> {
+    /// Casters with equal keys can share a single instanced draw
+    fn key(&self) -> (Option<u64>, u64) {
+        (
+            self.cutout.map(ContentId::content_id),
+            self.mesh.content_id(),
+        )
+    }
+}
+
+/// One or more shadow casters of the same mesh and cut-out texture, drawn as a single
+/// instanced draw
+pub(crate) struct ShadowBatch<'w, M = GpuMesh, T = GpuTexture> {
+    pub caster: ShadowCaster<'w, M, T>,
+    /// Range of the object ids buffer holding the object of each merged caster
+    pub objects: Range<u32>,
+}
+
+/// Merge `(material, mesh, texture, object id)` shadow casters into instanced draws
+///
+/// Transparent casters are skipped, opaque ones come first and cut-outs after them, grouped by
+/// texture so the cut-out pipeline is set once.
+fn shadow_batches<'w, M: ContentId, T: ContentId>(
+    casters: impl IntoIterator<Item = (Option<&'w Material>, &'w M, Option<&'w T>, u32)>,
+    object_ids: &mut Vec<u32>,
+) -> Vec<ShadowBatch<'w, M, T>> {
+    let mut casters: Vec<_> = casters
+        .into_iter()
+        .filter_map(|(material, mesh, texture, object_id)| {
+            Some((ShadowCaster::new(material, mesh, texture)?, object_id))
+        })
+        .collect();
+    casters.sort_by_key(|(caster, _)| caster.key());
+
+    let mut batches: Vec<ShadowBatch<M, T>> = Vec::new();
+    for (caster, object_id) in casters {
+        let object = object_ids.len() as u32;
+        object_ids.push(object_id);
+
+        match batches.last_mut() {
+            Some(last) if last.caster.key() == caster.key() => last.objects.end += 1,
+            _ => batches.push(ShadowBatch {
+                caster,
+                objects: object..object + 1,
+            }),
+        }
+    }
+
+    batches
+}
+
+/// Object ids of the drawn instances, indexing the model matrices in `GpuTransforms`
+struct DrawTransforms {
+    ids_buffer: wgpu::Buffer,
+    capacity: u32,
+    bind_group: wgpu::BindGroup,
+    /// `GpuTransforms::generation` the bind group was created for
+    transforms_generation: u32,
+}
+
+impl DrawTransforms {
+    const INITIAL_CAPACITY: u32 = 1024;
+
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        transforms: &GpuTransforms,
+    ) -> Self {
+        Self::with_capacity(device, layout, transforms, Self::INITIAL_CAPACITY)
+    }
+
+    fn with_capacity(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        transforms: &GpuTransforms,
+        capacity: u32,
+    ) -> Self {
+        let ids_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Object Ids Buffer"),
+            size: capacity as u64 * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: transforms.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: ids_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("transform_bind_group"),
+        });
+
+        Self {
+            ids_buffer,
+            capacity,
+            bind_group,
+            transforms_generation: transforms.generation,
+        }
+    }
+
+    /// Upload the object ids of a frame's draws
+    ///
+    /// The bind group is recreated when the ids outgrow the buffer or the transforms buffer
+    /// was recreated.
+    fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        transforms: &GpuTransforms,
+        object_ids: &[u32],
+    ) {
+        let count = object_ids.len() as u32;
+        if count > self.capacity || self.transforms_generation != transforms.generation {
+            let capacity = count.max(self.capacity).next_power_of_two();
+            *self = Self::with_capacity(device, layout, transforms, capacity);
+        }
+
+        queue.write_buffer(&self.ids_buffer, 0, bytemuck::cast_slice(object_ids));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Content(u64);
+
+    impl ContentId for Content {
+        fn content_id(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn transparent_meshes_cast_no_shadow() {
+        let opaque = Material::standard();
+        let transparent = Material::standard().transparent();
+        let cutout = Material::standard().alpha_to_coverage();
+        let (mesh, texture) = (Content(1), Content(2));
+
+        let mut object_ids = Vec::new();
+        let batches = shadow_batches(
+            [
+                (Some(&cutout), &mesh, Some(&texture), 0),
+                (Some(&transparent), &mesh, Some(&texture), 1),
+                (Some(&opaque), &mesh, Some(&texture), 2),
+                (None, &mesh, None, 3),
+            ],
+            &mut object_ids,
+        );
+
+        assert_eq!(object_ids, vec![2, 3, 0]);
+        assert_eq!(batches.len(), 2);
+        assert!(batches[0].caster.cutout.is_none());
+        assert_eq!(batches[0].objects, 0..2);
+        assert!(batches[1].caster.cutout.is_some());
+        assert_eq!(batches[1].objects, 2..3);
+    }
+}
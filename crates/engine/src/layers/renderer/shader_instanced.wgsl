@@ -1,62 +1,129 @@
 // Instanced rendering shader - uses per-instance transforms instead of uniform
+
+// Debug visualization replacing the lit output, 0 = lit, see `DebugView` in
+// components/debug_view.rs
+override DEBUG_VIEW: u32 = 0u;
+// Alpha written for the material, see `RenderMode::alpha_mode_index` in components/material.rs
+// 0 = opaque, 1 = texture alpha for blending or alpha-to-coverage, 2 = discarded cut-out
+override ALPHA_MODE: u32 = 0u;
+const ALPHA_CUTOFF: f32 = 0.5;
+
 struct CameraUniform {
     view_proj: mat4x4<f32>,
 };
 
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+// Per-instance transform matrix, one vec4 column per location
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+    @location(7) lod_depth: u32,
+}
+
+// Must match `LightData` in components/light.rs
+struct LightData {
+    position: vec3<f32>,
+    range: f32,
+    direction: vec3<f32>, // Direction the light shines in
+    kind: u32,            // 0 = directional, 1 = point, 2 = spot
+    color: vec3<f32>,
+    intensity: f32,
+    spot_scale: f32,
+    spot_offset: f32,
+}
+
+struct LightBuffer {
+    count: u32,
+    shadow_light: u32, // Index of the light that samples the shadow map
+    lights: array<LightData>,
+}
+
+// Must match `ShadowCascades` in components/shadow.rs
+struct ShadowCascades {
+    light_space_matrices: array<mat4x4<f32>, 4>,
+    split_depths: vec4<f32>, // View space distance at which each cascade ends
+    texel_sizes: vec4<f32>,  // World space size of a shadow map texel per cascade
+    cascade_count: u32,
+    blend_fraction: f32,
+    reverse_z: u32, // Reverse-Z maps store 1 - depth and pass when the reference is greater
+}
+
+// Must match `EnvironmentUniform` in components/environment.rs
+struct EnvironmentUniform {
+    sky_inverse_view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    intensity: f32,
+    skybox_intensity: f32,
+    specular_mips: f32,
+}
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) world_pos: vec3<f32>,
-    @location(3) light_space_pos: vec4<f32>,
+    @location(3) view_depth: f32,
+    @location(4) @interpolate(flat) object_id: u32,
+    @location(5) @interpolate(flat) lod_depth: u32,
 }
 
 @group(0) @binding(0) var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1) var s_diffuse: sampler;
 @group(1) @binding(0) var<uniform> camera: CameraUniform;
-@group(2) @binding(0) var t_shadow: texture_depth_2d;
+@group(2) @binding(0) var t_shadow: texture_depth_2d_array;
 @group(2) @binding(1) var sampler_shadow: sampler_comparison;
-@group(2) @binding(2) var<uniform> light_space_matrix: mat4x4<f32>;
-@group(2) @binding(3) var<uniform> light_direction: vec4<f32>;
-@group(2) @binding(4) var<uniform> light_properties: vec4<f32>; // x=intensity, yzw=color
+@group(2) @binding(2) var<uniform> shadow_cascades: ShadowCascades;
+@group(2) @binding(3) var<storage, read> lights: LightBuffer;
+@group(2) @binding(4) var<uniform> environment: EnvironmentUniform;
+@group(2) @binding(6) var irradiance_map: texture_cube<f32>;
+@group(2) @binding(7) var specular_map: texture_cube<f32>;
+@group(2) @binding(8) var brdf_lut: texture_2d<f32>;
+@group(2) @binding(9) var environment_sampler: sampler;
  
 @vertex
-fn vertex(
-    @location(0) position: vec3<f32>,
-    @location(1) uv: vec2<f32>,
-    @location(2) normal: vec3<f32>,
-    // Instance data (per-instance transform matrix)
-    @location(3) model_matrix_0: vec4<f32>,
-    @location(4) model_matrix_1: vec4<f32>,
-    @location(5) model_matrix_2: vec4<f32>,
-    @location(6) model_matrix_3: vec4<f32>,
-) -> VertexOutput {
+fn vertex(in: VertexInput, instance: InstanceInput) -> VertexOutput {
     var out: VertexOutput;
     
     // Reconstruct model matrix from instance data
     let model_matrix = mat4x4<f32>(
-        model_matrix_0,
-        model_matrix_1,
-        model_matrix_2,
-        model_matrix_3,
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
     );
     
-    let world_position = model_matrix * vec4<f32>(position, 1.0);
+    let world_position = model_matrix * vec4<f32>(in.position, 1.0);
 
-    out.uv = uv;
-    out.normal = normalize((model_matrix * vec4<f32>(normal, 0.0)).xyz);
+    out.uv = in.uv;
+    out.normal = normalize((model_matrix * vec4<f32>(in.normal, 0.0)).xyz);
     out.world_pos = world_position.xyz;
     out.clip_position = camera.view_proj * world_position;
-    out.light_space_pos = light_space_matrix * world_position;
+    out.view_depth = out.clip_position.w;
+    // Instances have no object id, hash their translation instead
+    let translation = bitcast<vec3<u32>>(instance.model_matrix_3.xyz);
+    out.object_id = pcg_hash(translation.x ^ pcg_hash(translation.y ^ pcg_hash(translation.z)));
+    out.lod_depth = instance.lod_depth;
     return out;
 }
 
-fn calculate_shadow(light_space_pos: vec4<f32>, normal: vec3<f32>, light_dir: vec3<f32>) -> f32 {
+// Shadow factor from a single cascade, 1.0 = fully lit
+fn sample_cascade(cascade: u32, world_pos: vec3<f32>, normal: vec3<f32>, n_dot_l: f32) -> f32 {
+    // Offset along the normal by about a texel to avoid acne without detaching shadows
+    let offset_pos = world_pos + normal * shadow_cascades.texel_sizes[cascade] * 1.5;
+    let light_space_pos = shadow_cascades.light_space_matrices[cascade] * vec4<f32>(offset_pos, 1.0);
+
     // Perspective divide
     let proj_coords = light_space_pos.xyz / light_space_pos.w;
 
-    // Transform to [0,1] range (from NDC [-1,1])
-    let uv = proj_coords.xy * 0.5 + 0.5;
+    // Transform to [0,1] texture range (from NDC [-1,1], Y pointing down)
+    let uv = proj_coords.xy * vec2<f32>(0.5, -0.5) + 0.5;
     let depth = proj_coords.z;
 
     // Outside shadow map = fully lit
@@ -64,17 +131,17 @@ fn calculate_shadow(light_space_pos: vec4<f32>, normal: vec3<f32>, light_dir: ve
         return 1.0;
     }
 
-    // Facing away from light = in shadow
-    let n_dot_l = dot(normal, light_dir);
-    if (n_dot_l <= 0.0) {
-        return 0.0;
-    }
+    // Small slope-scaled bias, the normal offset does most of the work
+    let bias = max(0.0002 * (1.0 - n_dot_l), 0.00005);
 
-    // Smaller adaptive bias for higher resolution shadow map
-    let bias = max(0.002 * (1.0 - n_dot_l), 0.0005);
+    // Biased towards the light, which is the other way in reverse-Z
+    var reference = depth - bias;
+    if (shadow_cascades.reverse_z != 0u) {
+        reference = depth + bias;
+    }
 
     // PCF with Poisson disk samples for smoother, less grid-like shadows
-    let texel_size = 1.0 / 4096.0;
+    let texel_size = 1.0 / f32(textureDimensions(t_shadow).x);
     let filter_radius = 2.0 * texel_size;
 
     // 16-sample Poisson disk
@@ -100,44 +167,226 @@ fn calculate_shadow(light_space_pos: vec4<f32>, normal: vec3<f32>, light_dir: ve
     var shadow = 0.0;
     for (var i = 0; i < 16; i++) {
         let offset = poisson[i] * filter_radius;
-        shadow += textureSampleCompare(t_shadow, sampler_shadow, uv + offset, depth - bias);
+        shadow += textureSampleCompareLevel(t_shadow, sampler_shadow, uv + offset, cascade, reference);
     }
 
     return shadow / 16.0;
 }
 
+fn calculate_shadow(world_pos: vec3<f32>, normal: vec3<f32>, light_dir: vec3<f32>, view_depth: f32) -> f32 {
+    // Facing away from light = in shadow
+    let n_dot_l = dot(normal, light_dir);
+    if (n_dot_l <= 0.0) {
+        return 0.0;
+    }
+
+    // Pick the first cascade that reaches past this fragment
+    let count = shadow_cascades.cascade_count;
+    var cascade = count;
+    for (var i = 0u; i < count; i++) {
+        if (view_depth < shadow_cascades.split_depths[i]) {
+            cascade = i;
+            break;
+        }
+    }
+
+    // Beyond the shadow distance = fully lit
+    if (cascade >= count) {
+        return 1.0;
+    }
+
+    var shadow = sample_cascade(cascade, world_pos, normal, n_dot_l);
+
+    // Fade into the next cascade (or out of shadow after the last one) near the end of this one
+    var cascade_start = 0.0;
+    if (cascade > 0u) {
+        cascade_start = shadow_cascades.split_depths[cascade - 1u];
+    }
+    let cascade_end = shadow_cascades.split_depths[cascade];
+    let blend_start = cascade_end - (cascade_end - cascade_start) * shadow_cascades.blend_fraction;
+
+    if (view_depth > blend_start) {
+        var next = 1.0;
+        if (cascade + 1u < count) {
+            next = sample_cascade(cascade + 1u, world_pos, normal, n_dot_l);
+        }
+        let t = (view_depth - blend_start) / max(cascade_end - blend_start, 0.0001);
+        shadow = mix(shadow, next, t);
+    }
+
+    return shadow;
+}
+
+// Direction towards the light (xyz) and its attenuation (w) at a world position
+fn light_incidence(light: LightData, world_pos: vec3<f32>) -> vec4<f32> {
+    if (light.kind == 0u) {
+        return vec4<f32>(-light.direction, 1.0);
+    }
+
+    let to_light = light.position - world_pos;
+    let distance_sq = max(dot(to_light, to_light), 0.0001);
+    let light_dir = to_light * inverseSqrt(distance_sq);
+
+    // Inverse square falloff, windowed so it reaches zero at the light's range
+    let range_factor = distance_sq / (light.range * light.range);
+    let window = saturate(1.0 - range_factor * range_factor);
+    var attenuation = window * window / distance_sq;
+
+    // Spot cone, fading between the inner and outer angle
+    if (light.kind == 2u) {
+        let cone = saturate(dot(-light_dir, light.direction) * light.spot_scale + light.spot_offset);
+        attenuation *= cone * cone;
+    }
+
+    return vec4<f32>(light_dir, attenuation);
+}
+
+// Ambient light from the environment map, split sum image based lighting
+fn environment_light(albedo: vec3<f32>, normal: vec3<f32>, view_dir: vec3<f32>) -> vec3<f32> {
+    // Fixed dielectric response until materials carry roughness and metalness
+    let roughness = 0.5;
+    let f0 = vec3<f32>(0.04);
+
+    let n_dot_v = max(dot(normal, view_dir), 1e-4);
+    let fresnel = f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+
+    let irradiance = textureSampleLevel(irradiance_map, environment_sampler, normal, 0.0).rgb;
+    let diffuse = (1.0 - fresnel) * albedo * irradiance;
+
+    let reflected = reflect(-view_dir, normal);
+    let level = roughness * (environment.specular_mips - 1.0);
+    let prefiltered = textureSampleLevel(specular_map, environment_sampler, reflected, level).rgb;
+    let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+    let specular = prefiltered * (fresnel * brdf.x + brdf.y);
+
+    return (diffuse + specular) * environment.intensity;
+}
+
+// Pseudo random hash (PCG), for stable per-object colours
+fn pcg_hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn hash_color(id: u32) -> vec3<f32> {
+    let hash = pcg_hash(id);
+    return vec3<f32>(f32(hash & 255u), f32((hash >> 8u) & 255u), f32((hash >> 16u) & 255u)) / 255.0;
+}
+
+// Colour of the debug view selected by `DEBUG_VIEW`, the values match `DebugView::shader_index`
+fn debug_color(in: VertexOutput, normal: vec3<f32>) -> vec3<f32> {
+    var color = vec3<f32>(1.0, 0.0, 1.0);
+    switch DEBUG_VIEW {
+        // Normals
+        case 1u: {
+            color = normal * 0.5 + 0.5;
+        }
+        // UVs
+        case 2u: {
+            color = vec3<f32>(fract(in.uv), 0.0);
+        }
+        // World position, one colour cycle per unit
+        case 3u: {
+            color = fract(in.world_pos);
+        }
+        // Linear depth, compressed so distant detail stays visible
+        case 4u: {
+            color = vec3<f32>(1.0 - exp(-in.view_depth / 50.0));
+        }
+        // Shadow factor of the shadow casting light
+        case 5u: {
+            color = vec3<f32>(1.0);
+            if (lights.shadow_light < lights.count) {
+                let light_dir = light_incidence(lights.lights[lights.shadow_light], in.world_pos).xyz;
+                color = vec3<f32>(calculate_shadow(in.world_pos, normal, light_dir, in.view_depth));
+            }
+        }
+        // Overdraw, every fragment adds a little heat through additive blending
+        case 6u: {
+            color = vec3<f32>(0.08, 0.03, 0.01);
+        }
+        // Random colour per object
+        case 7u: {
+            color = hash_color(in.object_id);
+        }
+        // Quadtree depth of the chunk
+        case 8u: {
+            let palette = array<vec3<f32>, 8>(
+                vec3<f32>(0.9, 0.1, 0.1),
+                vec3<f32>(0.9, 0.5, 0.1),
+                vec3<f32>(0.9, 0.9, 0.1),
+                vec3<f32>(0.1, 0.9, 0.1),
+                vec3<f32>(0.1, 0.9, 0.9),
+                vec3<f32>(0.1, 0.3, 0.9),
+                vec3<f32>(0.6, 0.1, 0.9),
+                vec3<f32>(0.9, 0.1, 0.6)
+            );
+            color = palette[in.lod_depth % 8u];
+        }
+        default: {}
+    }
+    return color;
+}
+
+// Alpha of the fragment for `ALPHA_MODE`, discarding cut-out fragments below the cutoff
+fn output_alpha(alpha: f32) -> f32 {
+    if (ALPHA_MODE == 1u) {
+        return alpha;
+    }
+    if (ALPHA_MODE == 2u && alpha < ALPHA_CUTOFF) {
+        discard;
+    }
+    return 1.0;
+}
+
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    // Get light properties
-    let light_dir = normalize(light_direction.xyz);
-    let light_intensity = light_properties.x;
-    let light_color = light_properties.yzw * light_intensity;
-
-    // Material
+    // Material - coverage comes from the texture alpha for blended and cut-out materials
     let albedo = vec3<f32>(0.7, 0.6, 0.5);
+    let alpha = textureSample(t_diffuse, s_diffuse, in.uv).a;
 
     let normal = normalize(in.normal);
-    let view_dir = normalize(-in.world_pos);
 
-    // Basic diffuse
-    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    if (DEBUG_VIEW == 6u) {
+        return vec4<f32>(debug_color(in, normal), 1.0);
+    }
+    if (DEBUG_VIEW != 0u) {
+        return vec4<f32>(debug_color(in, normal), output_alpha(alpha));
+    }
+
+    let view_dir = normalize(environment.camera_position - in.world_pos);
 
-    // Shadow
-    let shadow = calculate_shadow(in.light_space_pos, normal, light_dir);
+    var diffuse = vec3<f32>(0.0);
+    var specular = vec3<f32>(0.0);
 
-    // Ambient light so we can always see something
-    let ambient = albedo * 0.1;
+    for (var i = 0u; i < lights.count; i++) {
+        let light = lights.lights[i];
+        let incidence = light_incidence(light, in.world_pos);
+        let light_dir = incidence.xyz;
+        let light_color = light.color * light.intensity * incidence.w;
 
-    // Simple Lambertian shading
-    let diffuse = albedo * light_color * n_dot_l * shadow;
+        // Shadow
+        var shadow = 1.0;
+        if (i == lights.shadow_light) {
+            shadow = calculate_shadow(in.world_pos, normal, light_dir, in.view_depth);
+        }
 
-    // Tiny bit of specular for highlights
-    let half_dir = normalize(light_dir + view_dir);
-    let n_dot_h = max(dot(normal, half_dir), 0.0);
-    let spec = pow(n_dot_h, 32.0) * 0.2;
-    let specular = light_color * spec * shadow;
+        // Simple Lambertian shading
+        let n_dot_l = max(dot(normal, light_dir), 0.0);
+        diffuse += albedo * light_color * n_dot_l * shadow;
+
+        // Tiny bit of specular for highlights
+        let half_dir = normalize(light_dir + view_dir);
+        let n_dot_h = max(dot(normal, half_dir), 0.0);
+        let spec = pow(n_dot_h, 32.0) * 0.2;
+        specular += light_color * spec * shadow;
+    }
+
+    // Ambient light so we can always see something
+    let ambient = albedo * 0.1;
 
-    let final_color = ambient + diffuse + specular;
+    let final_color = ambient + diffuse + specular + environment_light(albedo, normal, view_dir);
 
-    return vec4<f32>(final_color, 1.0);
+    return vec4<f32>(final_color, output_alpha(alpha));
 }
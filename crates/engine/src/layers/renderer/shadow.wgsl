@@ -1,6 +1,9 @@
 // Shadow pass shader - depth-only rendering from light's perspective
 
-struct TransformUniform {
+// Same cutoff as the cut-out `ALPHA_MODE` in shader.wgsl
+const ALPHA_CUTOFF: f32 = 0.5;
+
+struct TransformData {
     model: mat4x4<f32>,
 }
 
@@ -8,11 +11,37 @@ struct ShadowUniform {
     light_space_matrix: mat4x4<f32>,
 }
 
-@group(0) @binding(0) var<uniform> transform: TransformUniform;
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var<storage, read> transforms: array<TransformData>;
+@group(0) @binding(1) var<storage, read> transform_ids: array<u32>;
 @group(1) @binding(0) var<uniform> shadow: ShadowUniform;
+// Only bound for cut-out casters
+@group(2) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(2) @binding(1) var s_diffuse: sampler;
 
 @vertex
-fn vertex(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
-    let world_position = transform.model * vec4<f32>(position, 1.0);
-    return shadow.light_space_matrix * world_position;
+fn vertex(
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let model = transforms[transform_ids[instance_index]].model;
+    let world_position = model * vec4<f32>(position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = shadow.light_space_matrix * world_position;
+    out.uv = uv;
+    return out;
+}
+
+// Cut-out casters only, opaque ones are drawn without a fragment stage
+@fragment
+fn cutout(in: VertexOutput) {
+    if (textureSample(t_diffuse, s_diffuse, in.uv).a < ALPHA_CUTOFF) {
+        discard;
+    }
 }
@@ -0,0 +1,55 @@
+// Instanced shadow pass shader - depth-only rendering from light's perspective
+
+// Same cutoff as the cut-out `ALPHA_MODE` in shader_instanced.wgsl
+const ALPHA_CUTOFF: f32 = 0.5;
+
+struct ShadowUniform {
+    light_space_matrix: mat4x4<f32>,
+}
+
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+    @location(7) lod_depth: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> shadow: ShadowUniform;
+// Only bound for cut-out casters
+@group(1) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1) var s_diffuse: sampler;
+
+@vertex
+fn vertex(
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    let world_position = model_matrix * vec4<f32>(position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = shadow.light_space_matrix * world_position;
+    out.uv = uv;
+    return out;
+}
+
+// Cut-out casters only, opaque ones are drawn without a fragment stage
+@fragment
+fn cutout(in: VertexOutput) {
+    if (textureSample(t_diffuse, s_diffuse, in.uv).a < ALPHA_CUTOFF) {
+        discard;
+    }
+}
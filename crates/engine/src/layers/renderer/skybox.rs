@@ -0,0 +1,91 @@
+use crate::prelude::*;
+
+use std::collections::HashMap;
+
+/// Draws the baked environment behind the opaque geometry of a camera
+///
+/// Reads the camera's lighting bind group, see `GpuShadowMap::bind_group`.
+pub struct Skybox {
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    /// Pipelines by sample count and depth mode of the camera targets, created on first use
+    pipelines: HashMap<(u32, DepthMode), wgpu::RenderPipeline>,
+}
+
+impl Skybox {
+    pub fn new(device: &wgpu::Device, lighting_layout: &wgpu::BindGroupLayout) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[lighting_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("skybox.wgsl").into()),
+        });
+
+        Self {
+            pipeline_layout,
+            shader,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Draw into a pass over a camera's HDR and depth targets, after its opaque geometry
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        render_pass: &mut wgpu::RenderPass,
+        lighting_bind_group: &wgpu::BindGroup,
+        sample_count: u32,
+        depth_mode: DepthMode,
+    ) {
+        let key = (sample_count, depth_mode);
+        let pipeline = self.pipelines.entry(key).or_insert_with(|| {
+            let constants = [("FAR_DEPTH", depth_mode.far_depth() as f64)];
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Skybox Pipeline"),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: Some("vertex"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: &constants,
+                        ..Default::default()
+                    },
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: Some("fragment"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // Drawn at the far plane, only where the depth buffer is still clear
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: depth_mode.compare(wgpu::CompareFunction::LessEqual),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, lighting_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,42 @@
+// Skybox - draws the environment cubemap at the far plane, so only uncovered pixels show it
+
+// Must match `EnvironmentUniform` in components/environment.rs
+struct EnvironmentUniform {
+    sky_inverse_view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    intensity: f32,
+    skybox_intensity: f32,
+    specular_mips: f32,
+}
+
+// Depth of the far plane, 0 for reverse-Z cameras, see `DepthMode`
+override FAR_DEPTH: f32 = 1.0;
+
+// The camera's lighting bind group, only the environment bindings are used
+@group(0) @binding(4) var<uniform> environment: EnvironmentUniform;
+@group(0) @binding(5) var environment_cube: texture_cube<f32>;
+@group(0) @binding(9) var environment_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip: vec2<f32>,
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // Fullscreen triangle
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.clip = uv * 2.0 - 1.0;
+    out.position = vec4<f32>(out.clip, FAR_DEPTH, 1.0);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let world = environment.sky_inverse_view_projection * vec4<f32>(in.clip, 1.0, 1.0);
+    let direction = normalize(world.xyz / world.w);
+    let color = textureSampleLevel(environment_cube, environment_sampler, direction, 0.0).rgb;
+    return vec4<f32>(color * environment.skybox_intensity, 1.0);
+}
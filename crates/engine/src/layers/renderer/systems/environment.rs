@@ -0,0 +1,64 @@
+use crate::prelude::*;
+
+use crate::layers::renderer::IblBaker;
+
+/// Bake the image based lighting again when the scene's environment map is added, changed or
+/// removed
+pub fn update_environment_lighting(
+    mut commands: Commands,
+    device: Res<GpuDevice>,
+    queue: Res<GpuQueue>,
+    baker: Res<IblBaker>,
+    mut lighting: ResMut<GpuEnvironmentLighting>,
+    environments: Query<Ref<EnvironmentMap>>,
+    shadow_maps: Query<Entity, With<GpuShadowMap>>,
+) {
+    let environment = environments
+        .iter()
+        .find(|environment| !environment.bytes.is_empty());
+
+    // Only hash the bytes after mutable access, they can be large
+    let source_hash = match &environment {
+        Some(environment) if !environment.is_changed() => return,
+        Some(environment) => Some(environment.bytes_hash()),
+        None => None,
+    };
+    if source_hash == lighting.source_hash {
+        return;
+    }
+
+    *lighting = match environment {
+        Some(environment) => match baker.bake(&device.0, &queue.0, &environment) {
+            Ok(baked) => baked,
+            Err(err) => {
+                log::error!("{}", err);
+                return;
+            }
+        },
+        None => baker.fallback(&device.0),
+    };
+
+    // The lighting is bound next to the shadow maps, recreate them around the new textures
+    for entity in shadow_maps.iter() {
+        commands.entity(entity).remove::<GpuShadowMap>();
+    }
+}
+
+/// Upload the environment uniform of every camera
+pub fn update_environment_uniforms(
+    queue: Res<GpuQueue>,
+    settings: Res<EnvironmentLighting>,
+    lighting: Res<GpuEnvironmentLighting>,
+    origin: Res<RenderOrigin>,
+    query: Query<(&Camera, &Transform, &GpuCamera, &GpuShadowMap)>,
+) {
+    for (camera, transform, gpu_camera, shadow_map) in query.iter() {
+        let uniform = lighting.uniform(&settings, camera, transform, &origin, gpu_camera.aspect);
+
+        let mut data = UniformBuffer::new(Vec::new());
+        data.write(&uniform).unwrap();
+        queue
+            .0
+            .write_buffer(&shadow_map.environment_buffer, 0, &data.into_inner());
+    }
+}
@@ -0,0 +1,47 @@
+use crate::prelude::*;
+
+/// Upload every light in the scene to the shared lights storage buffer
+///
+/// The first directional light is the one that casts shadows.
+pub fn update_lights(
+    queue: Res<GpuQueue>,
+    mut gpu_lights: ResMut<GpuLights>,
+    origin: Res<RenderOrigin>,
+    light_query: Query<(&Light, &Transform)>,
+) {
+    let mut lights = Vec::new();
+    let mut shadow_light = None;
+
+    for (light, transform) in light_query.iter() {
+        if lights.len() == MAX_LIGHTS {
+            log::warn!(
+                "More than {} lights in the scene, ignoring the rest",
+                MAX_LIGHTS
+            );
+            break;
+        }
+
+        if shadow_light.is_none() && light.kind == LightKind::Directional {
+            shadow_light = Some((lights.len() as u32, Light::direction(transform)));
+        }
+
+        lights.push(light.to_light_data(transform, &origin));
+    }
+
+    let light_buffer = LightBuffer {
+        count: lights.len() as u32,
+        shadow_light: shadow_light.map(|(index, _)| index).unwrap_or(u32::MAX),
+        lights,
+    };
+
+    let mut data = StorageBuffer::new(Vec::new());
+    data.write(&light_buffer).unwrap();
+    queue
+        .0
+        .write_buffer(&gpu_lights.buffer, 0, &data.into_inner());
+
+    let shadow_direction = shadow_light.map(|(_, direction)| direction);
+    if gpu_lights.shadow_direction != shadow_direction {
+        gpu_lights.shadow_direction = shadow_direction;
+    }
+}
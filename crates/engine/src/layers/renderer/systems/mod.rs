@@ -1,7 +1,17 @@
 mod camera;
+mod environment;
+mod light;
+mod shadow;
 mod texture;
 mod transform;
+mod visibility;
+mod wireframe;
 
 pub use camera::*;
+pub use environment::*;
+pub use light::*;
+pub use shadow::*;
 pub use texture::*;
 pub use transform::*;
+pub use visibility::*;
+pub use wireframe::*;
@@ -0,0 +1,280 @@
+use crate::prelude::*;
+
+use encase::UniformBuffer;
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn initialize_shadow_maps(
+    mut commands: Commands,
+    device: Res<GpuDevice>,
+    shadow_layout: Res<ShadowBindGroupLayout>,
+    shadow_uniform_layout: Res<ShadowUniformLayout>,
+    gpu_lights: Res<GpuLights>,
+    lighting: Res<GpuEnvironmentLighting>,
+    settings: Res<ShadowSettings>,
+    camera_query: Query<(Entity, &Camera), (With<RenderTarget>, Without<GpuShadowMap>)>,
+) {
+    let device = &device.0;
+    let cascade_count = settings.cascade_count();
+
+    for (entity, camera) in camera_query.iter() {
+        // Create shadow map texture, one layer per cascade
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: wgpu::Extent3d {
+                width: settings.map_size,
+                height: settings.map_size,
+                depth_or_array_layers: cascade_count as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Map Array View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let cascade_views = (0..cascade_count as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow Cascade View"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        // Create comparison sampler for shadow testing
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(camera.depth_mode.compare(wgpu::CompareFunction::LessEqual)),
+            ..Default::default()
+        });
+
+        // Filled in every frame by update_shadow_cascades
+        let cascades_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Cascades Buffer"),
+            size: <ShadowCascades as encase::ShaderSize>::SHADER_SIZE.get(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Never written, buffers start zeroed so no cascade is ever selected
+        let unshadowed_cascades_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Unshadowed Cascades Buffer"),
+            size: <ShadowCascades as encase::ShaderSize>::SHADER_SIZE.get(),
+            usage: wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
+
+        // Filled in every frame by update_environment_uniforms
+        let environment_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Environment Uniform Buffer"),
+            size: <EnvironmentUniform as encase::ShaderSize>::SHADER_SIZE.get(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Create bind groups for main pass (shadow map, sampler, cascades, all scene lights and
+        // the environment lighting)
+        let create_bind_group = |cascades: &wgpu::Buffer, label| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &shadow_layout.0,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: cascades.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: gpu_lights.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: environment_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: wgpu::BindingResource::TextureView(&lighting.environment_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: wgpu::BindingResource::TextureView(&lighting.irradiance_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: wgpu::BindingResource::TextureView(&lighting.specular_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 8,
+                        resource: wgpu::BindingResource::TextureView(&lighting.brdf_lut_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 9,
+                        resource: wgpu::BindingResource::Sampler(&lighting.sampler),
+                    },
+                ],
+                label: Some(label),
+            })
+        };
+        let bind_group = create_bind_group(&cascades_buffer, "shadow_bind_group");
+        let unshadowed_bind_group =
+            create_bind_group(&unshadowed_cascades_buffer, "unshadowed_bind_group");
+
+        // Create a light space matrix buffer and bind group per cascade for the shadow pass
+        let cascade_buffers: Vec<wgpu::Buffer> = (0..cascade_count)
+            .map(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Cascade Light Space Matrix Buffer"),
+                    size: std::mem::size_of::<Matrix4<f32>>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+
+        let cascade_bind_groups = cascade_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &shadow_uniform_layout.0,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some("shadow_uniform_bind_group"),
+                })
+            })
+            .collect();
+
+        commands.entity(entity).insert(GpuShadowMap {
+            texture,
+            view,
+            cascade_views,
+            sampler,
+            depth_mode: camera.depth_mode,
+            bind_group,
+            unshadowed_bind_group,
+            cascades_buffer,
+            unshadowed_cascades_buffer,
+            environment_buffer,
+            cascade_buffers,
+            cascade_bind_groups,
+        });
+    }
+}
+
+/// Recreate every shadow map when the resolution or cascade count changes
+pub fn update_shadow_settings(
+    mut commands: Commands,
+    settings: Res<ShadowSettings>,
+    query: Query<Entity, With<GpuShadowMap>>,
+) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).remove::<GpuShadowMap>();
+    }
+}
+
+/// Recreate a camera's shadow map when its depth mode changes, the sampler compares in it
+pub fn update_shadow_depth_modes(
+    mut commands: Commands,
+    query: Query<(Entity, &Camera, &GpuShadowMap), Changed<Camera>>,
+) {
+    for (entity, camera, shadow_map) in query.iter() {
+        if camera.depth_mode != shadow_map.depth_mode {
+            commands.entity(entity).remove::<GpuShadowMap>();
+        }
+    }
+}
+
+/// Split each camera's view frustum and fit a light projection to every cascade
+pub fn update_shadow_cascades(
+    queue: Res<GpuQueue>,
+    gpu_lights: Res<GpuLights>,
+    settings: Res<ShadowSettings>,
+    origin: Res<RenderOrigin>,
+    query: Query<(&Camera, &Transform, &GpuCamera, &GpuShadowMap)>,
+) {
+    let Some(light_dir) = gpu_lights.shadow_direction else {
+        return;
+    };
+
+    for (camera, transform, gpu_camera, shadow_map) in query.iter() {
+        // Cascades are fitted relative to the render origin, where the shaders shade
+        let view_matrix = camera.view_matrix_relative(transform, &origin.position);
+        let Some(camera_to_world) = view_matrix.try_inverse() else {
+            continue;
+        };
+
+        let cascade_count = shadow_map.cascade_views.len();
+        let (znear, zfar) = camera.projection.depth_range();
+        let projection = camera.projection.matrix(gpu_camera.aspect);
+        let far = settings.max_distance.min(zfar);
+        let splits = cascade_split_depths(znear, far, cascade_count, settings.split_lambda);
+
+        let mut cascades = ShadowCascades {
+            light_space_matrices: [Matrix4::identity(); MAX_SHADOW_CASCADES],
+            split_depths: nalgebra::Vector4::zeros(),
+            texel_sizes: nalgebra::Vector4::zeros(),
+            cascade_count: cascade_count as u32,
+            blend_fraction: settings.blend_fraction,
+            reverse_z: shadow_map.depth_mode.is_reversed() as u32,
+        };
+
+        let mut near = znear;
+        for (i, &split) in splits.iter().enumerate() {
+            let corners = frustum_slice_corners(&camera_to_world, &projection, near, split);
+            let (light_space_matrix, texel_size) = fit_cascade(
+                &corners,
+                &origin.position,
+                &light_dir,
+                settings.map_size,
+                settings.caster_distance,
+            );
+            let light_space_matrix = shadow_map.depth_mode.clip_transform() * light_space_matrix;
+
+            cascades.light_space_matrices[i] = light_space_matrix;
+            cascades.split_depths[i] = split;
+            cascades.texel_sizes[i] = texel_size;
+
+            queue.0.write_buffer(
+                &shadow_map.cascade_buffers[i],
+                0,
+                bytemuck::cast_slice(&[light_space_matrix]),
+            );
+
+            near = split;
+        }
+
+        let mut data = UniformBuffer::new(Vec::new());
+        data.write(&cascades).unwrap();
+        queue
+            .0
+            .write_buffer(&shadow_map.cascades_buffer, 0, &data.into_inner());
+    }
+}
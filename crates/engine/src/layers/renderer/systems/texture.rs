@@ -12,6 +12,7 @@ pub fn initialize_texture_buffers(
     let bind_group_layout = &bind_group_layout.0;
 
     for (entity, texture) in query.iter() {
+        let content_id = texture.content_id();
         let image = image::load_from_memory(&texture.bytes).unwrap();
         let image = image.to_rgba8();
 
@@ -83,6 +84,7 @@ pub fn initialize_texture_buffers(
             view,
             sampler,
             bind_group,
+            content_id,
         });
 
         log::debug!("Created texture buffer")
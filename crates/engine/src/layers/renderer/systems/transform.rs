@@ -1,58 +1,98 @@
 use crate::prelude::*;
 
-use wgpu::util::DeviceExt;
+/// Aim every `LookAt` entity at its target
+///
+/// Only writes rotations that changed, so still cameras keep their buffers untouched.
+pub fn update_look_at(mut query: Query<(&mut Transform, &LookAt)>) {
+    for (mut transform, look_at) in query.iter_mut() {
+        let Some(rotation) = look_at.rotation(&transform.position) else {
+            continue;
+        };
+        if transform.rotation.angle_to(&rotation) > 1e-6 {
+            transform.rotation = rotation;
+        }
+    }
+}
 
-fn compute_model_matrix(transform: &Transform) -> nalgebra::Matrix4<f32> {
-    let translation = nalgebra::Matrix4::new_translation(&transform.position.coords);
-    let rotation = transform.rotation.to_homogeneous();
-    let scale = nalgebra::Matrix4::new_nonuniform_scaling(&transform.scale);
-    translation * rotation * scale
+/// Move the `RenderOrigin` onto the main camera once it strays past the rebase distance
+///
+/// Only touches the resource when it actually moves, its change is what makes the other systems
+/// rewrite every matrix relative to the new origin.
+pub fn rebase_render_origin(
+    mut origin: ResMut<RenderOrigin>,
+    camera_query: Query<(&Camera, &Transform)>,
+) {
+    let Some((_, transform)) = camera_query.iter().find(|(camera, _)| camera.is_main) else {
+        return;
+    };
+
+    let distance = (transform.position - origin.position).norm();
+    if distance > origin.rebase_distance {
+        origin.position = transform.position;
+    }
 }
 
-pub fn initialize_transform_buffers(
+/// Give every `Transform` a slot in `GpuTransforms` and keep its model matrix up to date
+#[allow(clippy::too_many_arguments)]
+pub fn update_gpu_transforms(
     mut commands: Commands,
     device: Res<GpuDevice>,
-    layout: Res<TransformBindGroupLayout>,
-    query: Query<(Entity, &Transform), Without<GpuTransform>>,
+    queue: Res<GpuQueue>,
+    mut transforms: ResMut<GpuTransforms>,
+    origin: Res<RenderOrigin>,
+    mut removed: RemovedComponents<Transform>,
+    added_query: Query<(Entity, &Transform), Without<GpuTransform>>,
+    query: Query<(Ref<Transform>, &GpuTransform)>,
 ) {
-    for (entity, transform) in query.iter() {
-        let model_matrix = compute_model_matrix(transform);
-
-        let buffer = device
-            .0
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Transform Buffer"),
-                contents: bytemuck::cast_slice(&[model_matrix]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
-
-        let bind_group = device.0.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &layout.0,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-            label: Some("transform_bind_group"),
-        });
-
-        commands
-            .entity(entity)
-            .insert(GpuTransform { buffer, bind_group });
+    for entity in removed.read() {
+        transforms.free(entity);
+        if let Ok(mut entity) = commands.get_entity(entity) {
+            entity.remove::<GpuTransform>();
+        }
+    }
+
+    let mut added = Vec::new();
+    for (entity, transform) in added_query.iter() {
+        let object_id = transforms.allocate(entity);
+        commands.entity(entity).insert(GpuTransform { object_id });
+        added.push((object_id, transform));
+    }
+
+    // Moving the origin moves every model matrix
+    let rewrite = transforms.reserve(&device.0) || origin.is_changed();
+
+    for (transform, gpu_transform) in query.iter() {
+        if rewrite || transform.is_changed() {
+            transforms.write(&queue.0, gpu_transform.object_id, &transform, &origin);
+        }
+    }
+
+    for (object_id, transform) in added {
+        transforms.write(&queue.0, object_id, transform, &origin);
     }
 }
 
-pub fn update_transform_buffers(
+/// Upload the instances of dirty or moved `InstancedLodMesh`es, or of all of them when the
+/// origin moves
+#[allow(clippy::type_complexity)]
+pub fn update_instanced_meshes(
+    device: Res<GpuDevice>,
     queue: Res<GpuQueue>,
-    query: Query<(&Transform, &GpuTransform), Changed<Transform>>,
+    origin: Res<RenderOrigin>,
+    mut query: Query<(
+        Ref<InstancedLodMesh>,
+        Option<Ref<Transform>>,
+        &mut GpuInstancedLodMesh,
+    )>,
 ) {
-    let queue = &queue.0;
-
-    for (transform, gpu_transform) in query.iter() {
-        let model_matrix = compute_model_matrix(transform);
-        queue.write_buffer(
-            &gpu_transform.buffer,
-            0,
-            bytemuck::cast_slice(&[model_matrix]),
-        );
+    for (instanced_mesh, transform, mut gpu_mesh) in query.iter_mut() {
+        let dirty = instanced_mesh.is_changed() && instanced_mesh.dirty;
+        let moved = transform
+            .as_ref()
+            .is_some_and(|transform| transform.is_changed());
+        if dirty || moved || gpu_mesh.is_added() || origin.is_changed() {
+            let transform = transform.as_deref().cloned().unwrap_or_default();
+            gpu_mesh.update(&instanced_mesh, &transform, &origin, &device.0, &queue.0);
+        }
     }
 }
@@ -0,0 +1,121 @@
+use crate::prelude::*;
+
+/// Recompute local bounds whenever mesh geometry changes
+pub fn update_mesh_bounds(
+    mut commands: Commands,
+    mesh_query: Query<(Entity, &Mesh), Changed<Mesh>>,
+    instanced_mesh_query: Query<(Entity, &InstancedLodMesh), Changed<InstancedLodMesh>>,
+) {
+    let base_meshes = instanced_mesh_query
+        .iter()
+        .map(|(entity, instanced_mesh)| (entity, &instanced_mesh.base_mesh));
+
+    for (entity, mesh) in mesh_query.iter().chain(base_meshes) {
+        match Aabb::from_vertices(&mesh.vertices) {
+            Some(aabb) => {
+                commands.entity(entity).insert(aabb);
+            }
+            None => {
+                commands.entity(entity).remove::<Aabb>();
+            }
+        }
+    }
+}
+
+/// Frustum cull every mesh against each camera and prepare the GPU culling of instances
+///
+/// Meshes without bounds are always visible. Instanced meshes keep every instance in their
+/// own buffers for the shadow pass, while the culling pass compacts the ones each camera
+/// sees into that camera's `GpuVisibleInstances`.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn update_visibility(
+    mut commands: Commands,
+    device: Res<GpuDevice>,
+    queue: Res<GpuQueue>,
+    cull_layout: Res<InstanceCullLayout>,
+    origin: Res<RenderOrigin>,
+    mut stats: ResMut<CullingStats>,
+    mut camera_query: Query<(
+        Entity,
+        &Camera,
+        &Transform,
+        &GpuCamera,
+        Option<&mut GpuVisibleInstances>,
+    )>,
+    mesh_query: Query<(Entity, &Transform, Option<&Aabb>), With<GpuMesh>>,
+    instanced_mesh_query: Query<(Entity, &GpuInstancedLodMesh, Option<&HorizonOccluder>)>,
+) {
+    *stats = CullingStats::default();
+
+    for (camera_entity, camera, transform, gpu_camera, visible_instances) in camera_query.iter_mut()
+    {
+        // Culling runs relative to the render origin, like the model matrices on the GPU
+        let view_projection =
+            camera.view_projection_relative(transform, &origin, gpu_camera.aspect);
+        let frustum = Frustum::from_view_projection(&view_projection);
+        let camera_position = origin.relative(&transform.position);
+
+        let mut visible = VisibleEntities::default();
+
+        for (entity, transform, aabb) in mesh_query.iter() {
+            let inside = aabb.is_none_or(|aabb| {
+                let model = transform.model_matrix_relative(&origin.position);
+                frustum.intersects_aabb(&aabb.transformed(&model))
+            });
+
+            if inside {
+                visible.meshes.insert(entity);
+                stats.meshes_drawn += 1;
+            } else {
+                stats.meshes_culled += 1;
+            }
+        }
+
+        commands.entity(camera_entity).insert(visible);
+
+        let Some(mut visible_instances) = visible_instances else {
+            commands
+                .entity(camera_entity)
+                .insert(GpuVisibleInstances::default());
+            continue;
+        };
+
+        // Reuse the culling buffers of meshes whose instance buffers were not recreated
+        let mut previous = std::mem::take(&mut visible_instances.meshes);
+
+        for (entity, mesh, occluder) in instanced_mesh_query.iter() {
+            let mut culled = previous
+                .remove(&entity)
+                .filter(|culled| culled.generation == mesh.generation)
+                .unwrap_or_else(|| GpuCulledInstances::new(&device.0, &cull_layout.0, mesh));
+
+            let drawn = culled.readback.visible().min(culled.instance_count);
+            stats.instances_drawn += drawn;
+            stats.instances_culled += culled.instance_count - drawn;
+
+            culled.instance_count = mesh.instance_count;
+
+            let horizon_occluder = occluder.map_or_else(nalgebra::Vector4::zeros, |occluder| {
+                origin
+                    .relative(&occluder.center)
+                    .coords
+                    .push(occluder.radius)
+            });
+            let params = InstanceCullParams {
+                frustum_planes: *frustum.planes(),
+                camera_position: camera_position.to_homogeneous(),
+                horizon_occluder,
+                instance_count: mesh.instance_count,
+                index_count: mesh.index_count,
+            };
+
+            let mut data = UniformBuffer::new(Vec::new());
+            data.write(&params).unwrap();
+            queue
+                .0
+                .write_buffer(&culled.params_buffer, 0, &data.into_inner());
+
+            visible_instances.meshes.insert(entity, culled);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::prelude::*;
+
+/// Build the barycentric meshes of entities drawn by the wireframe pass, and drop them once
+/// they aren't anymore
+///
+/// Entities are drawn by the pass while `WireframeSettings::overlay` is on, when they have a
+/// `Wireframe` component, or when their material needs the wireframe fallback.
+#[allow(clippy::type_complexity)]
+pub fn update_wireframe_meshes(
+    mut commands: Commands,
+    device: Res<GpuDevice>,
+    settings: Res<WireframeSettings>,
+    features: Res<SupportedFeatures>,
+    mesh_query: Query<(
+        Entity,
+        Ref<Mesh>,
+        &Material,
+        Has<Wireframe>,
+        Option<&GpuWireframeMesh>,
+    )>,
+    instanced_mesh_query: Query<(
+        Entity,
+        Ref<InstancedLodMesh>,
+        &Material,
+        Has<Wireframe>,
+        Option<&GpuWireframeMesh>,
+    )>,
+) {
+    let meshes = mesh_query
+        .iter()
+        .map(|(entity, mesh, material, marked, wireframe)| {
+            (
+                entity,
+                mesh.is_changed(),
+                mesh.into_inner(),
+                material,
+                marked,
+                wireframe,
+            )
+        });
+    let base_meshes =
+        instanced_mesh_query
+            .iter()
+            .map(|(entity, instanced_mesh, material, marked, wireframe)| {
+                let changed = instanced_mesh.is_changed();
+                (
+                    entity,
+                    changed,
+                    &instanced_mesh.into_inner().base_mesh,
+                    material,
+                    marked,
+                    wireframe,
+                )
+            });
+
+    for (entity, changed, mesh, material, marked, wireframe) in meshes.chain(base_meshes) {
+        let drawn = settings.overlay || marked || material.needs_wireframe_fallback(&features);
+
+        match wireframe {
+            Some(_) if !drawn => {
+                commands.entity(entity).remove::<GpuWireframeMesh>();
+            }
+            // Instanced meshes change with their chunks, only rebuild for new base geometry
+            Some(wireframe) if changed && wireframe.content_id != mesh.content_id() => {
+                commands
+                    .entity(entity)
+                    .insert(GpuWireframeMesh::new(&device.0, mesh));
+            }
+            None if drawn => {
+                commands
+                    .entity(entity)
+                    .insert(GpuWireframeMesh::new(&device.0, mesh));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use crate::prelude::*;
+
+use std::collections::HashMap;
+
+/// Exposure metering and tonemapping of HDR camera renders into their display targets
+///
+/// Keeps the histogram and adapted exposure of every camera it resolves, so auto exposure
+/// adapts smoothly over frames. Shared by the raster and raytracing layers.
+pub struct Tonemapper {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+    /// Resolve pipelines by output format, created on first use
+    resolve_pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+    cameras: HashMap<Entity, CameraExposure>,
+}
+
+/// Metering state of one camera
+struct CameraExposure {
+    params_buffer: wgpu::Buffer,
+    histogram_buffer: wgpu::Buffer,
+    exposure_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// HDR texture bound in `bind_group`
+    source: wgpu::Texture,
+}
+
+impl Tonemapper {
+    const HISTOGRAM_BINS: u64 = 256;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let visibility = wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT;
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                storage_entry(2), // Luminance histogram
+                storage_entry(3), // Adapted exposure
+            ],
+            label: Some("tonemapping_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tonemapping Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tonemapping Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("tonemapping.wgsl").into()),
+        });
+
+        let create_compute_pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+        let histogram_pipeline =
+            create_compute_pipeline("Luminance Histogram Pipeline", "histogram_pass");
+        let average_pipeline = create_compute_pipeline("Exposure Average Pipeline", "average_pass");
+
+        Self {
+            bind_group_layout,
+            pipeline_layout,
+            shader,
+            histogram_pipeline,
+            average_pipeline,
+            resolve_pipelines: HashMap::new(),
+            cameras: HashMap::new(),
+        }
+    }
+
+    /// Meter and tonemap a camera's HDR render into `output`
+    ///
+    /// `hdr` and `output` must have the same size.
+    #[allow(clippy::too_many_arguments)]
+    pub fn resolve(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: Entity,
+        settings: &Tonemapping,
+        hdr: &GpuHdrTarget,
+        output: &wgpu::Texture,
+        delta_time: f32,
+    ) {
+        let state = match self.cameras.remove(&camera) {
+            Some(state) if state.source == hdr.texture => state,
+            // The target was recreated, keep the adapted exposure but rebind the new texture
+            previous => self.create_camera_exposure(device, hdr, previous),
+        };
+        let state = self.cameras.entry(camera).or_insert(state);
+
+        let params = TonemapParams::new(settings, output.format(), delta_time);
+        let mut data = UniformBuffer::new(Vec::new());
+        data.write(&params).unwrap();
+        queue.write_buffer(&state.params_buffer, 0, &data.into_inner());
+
+        if matches!(settings.exposure, Exposure::Auto(_)) {
+            let mut metering_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Exposure Metering Pass"),
+                timestamp_writes: None,
+            });
+            metering_pass.set_bind_group(0, &state.bind_group, &[]);
+
+            let size = hdr.texture.size();
+            metering_pass.set_pipeline(&self.histogram_pipeline);
+            metering_pass.dispatch_workgroups(size.width.div_ceil(16), size.height.div_ceil(16), 1);
+            metering_pass.set_pipeline(&self.average_pipeline);
+            metering_pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        let pipeline = self
+            .resolve_pipelines
+            .entry(output.format())
+            .or_insert_with(|| {
+                create_resolve_pipeline(
+                    device,
+                    &self.pipeline_layout,
+                    &self.shader,
+                    output.format(),
+                )
+            });
+
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut resolve_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemapping Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        resolve_pass.set_pipeline(pipeline);
+        resolve_pass.set_bind_group(0, &state.bind_group, &[]);
+        resolve_pass.draw(0..3, 0..1);
+    }
+
+    /// Drop the metering state of cameras that no longer exist
+    pub fn retain_cameras(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        self.cameras.retain(|entity, _| keep(*entity));
+    }
+
+    fn create_camera_exposure(
+        &self,
+        device: &wgpu::Device,
+        hdr: &GpuHdrTarget,
+        previous: Option<CameraExposure>,
+    ) -> CameraExposure {
+        let (params_buffer, histogram_buffer, exposure_buffer) = match previous {
+            Some(previous) => (
+                previous.params_buffer,
+                previous.histogram_buffer,
+                previous.exposure_buffer,
+            ),
+            None => {
+                let create_buffer = |label, size, usage| {
+                    device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(label),
+                        size,
+                        usage,
+                        mapped_at_creation: false,
+                    })
+                };
+                (
+                    create_buffer(
+                        "Tonemap Params Buffer",
+                        <TonemapParams as encase::ShaderSize>::SHADER_SIZE.get(),
+                        wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    ),
+                    create_buffer(
+                        "Luminance Histogram Buffer",
+                        Self::HISTOGRAM_BINS * std::mem::size_of::<u32>() as u64,
+                        wgpu::BufferUsages::STORAGE,
+                    ),
+                    // Zeroed, the first metered luminance is taken as is
+                    create_buffer(
+                        "Exposure Buffer",
+                        2 * std::mem::size_of::<f32>() as u64,
+                        wgpu::BufferUsages::STORAGE,
+                    ),
+                )
+            }
+        };
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&hdr.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: histogram_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: exposure_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("tonemapping_bind_group"),
+        });
+
+        CameraExposure {
+            params_buffer,
+            histogram_buffer,
+            exposure_buffer,
+            bind_group,
+            source: hdr.texture.clone(),
+        }
+    }
+}
+
+// The histogram and exposure buffers are sized here rather than from the shader
+const _: () = {
+    use crate::shader::layouts::tonemapping;
+
+    assert!(tonemapping::exposure_state::SIZE == 2 * std::mem::size_of::<f32>());
+};
+
+fn create_resolve_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Tonemapping Resolve Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vertex"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fragment"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,196 @@
+// Tonemapping - meter the HDR image into a luminance histogram, adapt the exposure and
+// resolve the exposed, tonemapped image into the camera's display target
+
+const HISTOGRAM_BINS: u32 = 256u;
+
+// Must match `TonemapParams` in components/tonemapping.rs
+struct TonemapParams {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    compensation: f32,
+    manual_exposure: f32,
+    adapt_brighten: f32,
+    adapt_darken: f32,
+    tonemap_operator: u32,
+    auto_exposure: u32,
+    encode_srgb: u32,
+}
+
+// Persists across frames, the luminance the exposure has adapted to so far
+struct ExposureState {
+    average_luminance: f32,
+    exposure: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: TonemapParams;
+@group(0) @binding(1) var hdr_texture: texture_2d<f32>;
+// Pixel count per log luminance bin, bin 0 holds black pixels. Cleared by `average_pass`
+@group(0) @binding(2) var<storage, read_write> histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+@group(0) @binding(3) var<storage, read_write> exposure: ExposureState;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// === Metering ===
+
+var<workgroup> local_bins: array<atomic<u32>, HISTOGRAM_BINS>;
+
+fn luminance_bin(value: f32) -> u32 {
+    if (value < 1e-5) {
+        return 0u;
+    }
+    let t = saturate((log2(value) - params.min_log_luminance) / params.log_luminance_range);
+    return u32(t * f32(HISTOGRAM_BINS - 2u)) + 1u;
+}
+
+@compute @workgroup_size(16, 16)
+fn histogram_pass(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    atomicStore(&local_bins[local_index], 0u);
+    workgroupBarrier();
+
+    if (all(id.xy < textureDimensions(hdr_texture))) {
+        let color = textureLoad(hdr_texture, id.xy, 0).rgb;
+        atomicAdd(&local_bins[luminance_bin(luminance(color))], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[local_index], atomicLoad(&local_bins[local_index]));
+}
+
+var<workgroup> weighted_bins: array<u32, HISTOGRAM_BINS>;
+
+@compute @workgroup_size(256)
+fn average_pass(@builtin(local_invocation_index) local_index: u32) {
+    let count = atomicLoad(&histogram[local_index]);
+    weighted_bins[local_index] = count * local_index;
+    atomicStore(&histogram[local_index], 0u);
+    workgroupBarrier();
+
+    for (var stride = HISTOGRAM_BINS / 2u; stride > 0u; stride >>= 1u) {
+        if (local_index < stride) {
+            weighted_bins[local_index] += weighted_bins[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (local_index != 0u) {
+        return;
+    }
+
+    // Thread 0 read the black bin, which is left out of the average
+    let size = textureDimensions(hdr_texture);
+    let lit_pixels = f32(size.x * size.y - count);
+    if (lit_pixels < 1.0) {
+        return;
+    }
+
+    let average_bin = f32(weighted_bins[0]) / lit_pixels - 1.0;
+    let metered = exp2(average_bin / f32(HISTOGRAM_BINS - 2u) * params.log_luminance_range + params.min_log_luminance);
+
+    var adapted = metered;
+    if (exposure.average_luminance > 0.0) {
+        let rate = select(params.adapt_darken, params.adapt_brighten, metered > exposure.average_luminance);
+        adapted = mix(exposure.average_luminance, metered, rate);
+    }
+
+    // Maps the average luminance to middle grey, as a camera meter would
+    exposure.average_luminance = adapted;
+    exposure.exposure = exp2(params.compensation) / (9.6 * adapted);
+}
+
+// === Resolve ===
+
+// Fitted ACES RRT and ODT by Stephen Hill
+fn tonemap_aces(color: vec3<f32>) -> vec3<f32> {
+    let input = mat3x3<f32>(
+        vec3<f32>(0.59719, 0.07600, 0.02840),
+        vec3<f32>(0.35458, 0.90834, 0.13383),
+        vec3<f32>(0.04823, 0.01566, 0.83777),
+    );
+    let output = mat3x3<f32>(
+        vec3<f32>(1.60475, -0.10208, -0.00327),
+        vec3<f32>(-0.53108, 1.10813, -0.07276),
+        vec3<f32>(-0.07367, -0.00605, 1.07602),
+    );
+
+    let v = input * color;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return saturate(output * (a / b));
+}
+
+// Minimal AgX with the default look, polynomial fit of the contrast curve
+fn tonemap_agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var x = log2(max(inset * color, vec3<f32>(1e-10)));
+    x = (clamp(x, vec3<f32>(min_ev), vec3<f32>(max_ev)) - min_ev) / (max_ev - min_ev);
+
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    x = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+
+    // The curve outputs display encoded values, return to linear
+    return pow(saturate(outset * x), vec3<f32>(2.2));
+}
+
+fn tonemap_reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + luminance(color));
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // Fullscreen triangle
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let hdr = textureLoad(hdr_texture, vec2<u32>(position.xy), 0);
+
+    var scale = params.manual_exposure;
+    if (params.auto_exposure != 0u) {
+        scale = exposure.exposure;
+    }
+    let exposed = max(hdr.rgb * scale, vec3<f32>(0.0));
+
+    var color: vec3<f32>;
+    switch params.tonemap_operator {
+        case 1u: {
+            color = tonemap_agx(exposed);
+        }
+        case 2u: {
+            color = tonemap_reinhard(exposed);
+        }
+        default: {
+            color = tonemap_aces(exposed);
+        }
+    }
+
+    if (params.encode_srgb != 0u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,240 @@
+use crate::prelude::*;
+
+use crate::shader::VertexLayout;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Draws triangle edges of meshes into a camera's HDR target, see `WireframeSettings`
+///
+/// Edges are found from the barycentric coordinates of `GpuWireframeMesh`, so this works on
+/// adapters without `POLYGON_MODE_LINE`. Overlays are biased towards the camera to win the
+/// depth test against the shaded surface they are drawn over.
+pub struct WireframeRenderer {
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    /// Pipelines by sample count and depth mode of the camera targets, vertex layout and
+    /// overlay, created on first use
+    pipelines: HashMap<(u32, DepthMode, VertexLayout, bool), wgpu::RenderPipeline>,
+    params_buffer: wgpu::Buffer,
+    params_bind_group: wgpu::BindGroup,
+}
+
+/// `WireframeParams` uniform of the wireframe shader
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct WireframeParams {
+    color: [f32; 4],
+    width: f32,
+    _padding: [f32; 3],
+}
+
+const _: () = {
+    use crate::shader::layouts::wireframe;
+    use std::mem::{offset_of, size_of};
+
+    assert!(size_of::<WireframeParams>() == wireframe::wireframe_params::SIZE);
+    assert!(offset_of!(WireframeParams, width) == wireframe::wireframe_params::WIDTH_OFFSET);
+};
+
+/// One mesh drawn by the wireframe pass
+pub struct WireframeDraw<'a> {
+    pub mesh: &'a GpuWireframeMesh,
+    pub instances: WireframeInstances<'a>,
+    /// Drawn over the shaded surface of the mesh, otherwise in place of it
+    pub overlay: bool,
+}
+
+pub enum WireframeInstances<'a> {
+    /// Range of the object ids buffer holding the mesh's object
+    Objects(Range<u32>),
+    /// Instances that survived the culling pass
+    Culled(&'a GpuCulledInstances),
+}
+
+impl WireframeRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        transform_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("wireframe_params_layout"),
+        });
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Wireframe Params Buffer"),
+            size: std::mem::size_of::<WireframeParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("wireframe_params_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Wireframe Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, transform_layout, &params_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Wireframe Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("wireframe.wgsl").into()),
+        });
+
+        Self {
+            pipeline_layout,
+            shader,
+            pipelines: HashMap::new(),
+            params_buffer,
+            params_bind_group,
+        }
+    }
+
+    /// Upload the edge colour and width of this frame, shared by every camera
+    pub fn prepare(&self, queue: &wgpu::Queue, settings: &WireframeSettings) {
+        let params = WireframeParams {
+            color: settings.color,
+            width: settings.width.max(0.0),
+            _padding: [0.0; 3],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    /// Draw into a pass over a camera's HDR and depth targets, after its scene geometry
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        render_pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        transforms_bind_group: &wgpu::BindGroup,
+        sample_count: u32,
+        depth_mode: DepthMode,
+        draws: &[WireframeDraw],
+    ) {
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, transforms_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.params_bind_group, &[]);
+
+        for draw in draws {
+            let vertex_layout = match draw.instances {
+                WireframeInstances::Objects(_) => VertexLayout::Standard,
+                WireframeInstances::Culled(_) => VertexLayout::Instanced,
+            };
+            let pipeline = self
+                .pipelines
+                .entry((sample_count, depth_mode, vertex_layout, draw.overlay))
+                .or_insert_with(|| {
+                    create_wireframe_pipeline(
+                        device,
+                        &self.pipeline_layout,
+                        &self.shader,
+                        sample_count,
+                        depth_mode,
+                        vertex_layout,
+                        draw.overlay,
+                    )
+                });
+            render_pass.set_pipeline(pipeline);
+
+            render_pass.set_vertex_buffer(0, draw.mesh.vertex_buffer.slice(..));
+            render_pass
+                .set_index_buffer(draw.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            match &draw.instances {
+                WireframeInstances::Objects(objects) => {
+                    render_pass.draw_indexed(0..draw.mesh.vertex_count, 0, objects.clone());
+                }
+                WireframeInstances::Culled(culled) => {
+                    // The culled index count is the base mesh's, one wireframe vertex per index
+                    render_pass.set_vertex_buffer(1, culled.instance_buffer.slice(..));
+                    render_pass.draw_indexed_indirect(&culled.indirect_buffer, 0);
+                }
+            }
+        }
+    }
+}
+
+fn create_wireframe_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    sample_count: u32,
+    depth_mode: DepthMode,
+    vertex_layout: VertexLayout,
+    overlay: bool,
+) -> wgpu::RenderPipeline {
+    let (entry_point, buffers) = match vertex_layout {
+        VertexLayout::Standard => ("vertex", vec![WireframeVertex::desc()]),
+        VertexLayout::Instanced => (
+            "vertex_instanced",
+            vec![WireframeVertex::desc(), InstanceData::desc()],
+        ),
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Wireframe Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some(entry_point),
+            buffers: &buffers,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fragment"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        // Both faces, like `POLYGON_MODE_LINE` with culling off
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: None,
+            ..Default::default()
+        },
+        // Edges never write depth, so they don't hide each other or later passes
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare: depth_mode.compare(wgpu::CompareFunction::LessEqual),
+            stencil: wgpu::StencilState::default(),
+            bias: if overlay {
+                // Pull the edges in front of the surface they were shaded on
+                depth_mode.bias(wgpu::DepthBiasState {
+                    constant: -4,
+                    slope_scale: -1.0,
+                    clamp: 0.0,
+                })
+            } else {
+                wgpu::DepthBiasState::default()
+            },
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
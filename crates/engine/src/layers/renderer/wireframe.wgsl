@@ -0,0 +1,76 @@
+// Wireframe - triangle edges from barycentric coordinates, see `WireframeRenderer`
+// in layers/renderer/wireframe.rs
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+// Must match the slots of `GpuTransforms` in components/transform.rs
+struct TransformData {
+    model: mat4x4<f32>,
+}
+
+// Must match `WireframeParams` in layers/renderer/wireframe.rs
+struct WireframeParams {
+    color: vec4<f32>,
+    width: f32,
+}
+
+// Must match `WireframeVertex` in components/wireframe.rs
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) barycentric: vec3<f32>,
+}
+
+// Per-instance transform matrix, one vec4 column per location
+struct InstanceInput {
+    @location(3) model_matrix_0: vec4<f32>,
+    @location(4) model_matrix_1: vec4<f32>,
+    @location(5) model_matrix_2: vec4<f32>,
+    @location(6) model_matrix_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) barycentric: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+@group(1) @binding(0) var<storage, read> transforms: array<TransformData>;
+@group(1) @binding(1) var<storage, read> transform_ids: array<u32>; // Object id of each drawn instance
+@group(2) @binding(0) var<uniform> params: WireframeParams;
+
+@vertex
+fn vertex(in: VertexInput, @builtin(instance_index) instance_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let model = transforms[transform_ids[instance_index]].model;
+    out.clip_position = camera.view_proj * (model * vec4<f32>(in.position, 1.0));
+    out.barycentric = in.barycentric;
+    return out;
+}
+
+@vertex
+fn vertex_instanced(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+    let model = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    out.clip_position = camera.view_proj * (model * vec4<f32>(in.position, 1.0));
+    out.barycentric = in.barycentric;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Distance to the closest edge in pixels, antialiased over one pixel
+    let pixels = in.barycentric / max(fwidth(in.barycentric), vec3<f32>(1e-6));
+    let distance = min(min(pixels.x, pixels.y), pixels.z);
+    let coverage = 1.0 - smoothstep(params.width * 0.5 - 0.5, params.width * 0.5 + 0.5, distance);
+    if coverage <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(params.color.rgb, params.color.a * coverage);
+}
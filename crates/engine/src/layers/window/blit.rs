@@ -0,0 +1,244 @@
+use crate::prelude::*;
+
+use std::collections::HashMap;
+use wgpu::util::DeviceExt;
+
+/// Draws camera render targets into rectangles of the window surface
+///
+/// Converts between any source and surface format, including sRGB encoding, and scales with
+/// the filter of `PresentSettings`.
+pub struct Blitter {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    /// Pipelines by output format, created on first use
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+    samplers: HashMap<PresentFilter, wgpu::Sampler>,
+    sources: HashMap<Entity, BlitSource>,
+}
+
+/// Bind group of one camera's render target
+struct BlitSource {
+    bind_group: wgpu::BindGroup,
+    /// Render target bound in `bind_group`
+    texture: wgpu::Texture,
+    filter: PresentFilter,
+    output_format: wgpu::TextureFormat,
+}
+
+/// sRGB conversion of a blit, as laid out in the blit shader
+#[derive(ShaderType, Clone, Copy)]
+struct BlitParams {
+    decode_srgb: u32,
+    encode_srgb: u32,
+}
+
+impl BlitParams {
+    /// Render targets hold display encoded values, sRGB formats decode them when sampled and
+    /// encode them when written
+    fn new(source_format: wgpu::TextureFormat, output_format: wgpu::TextureFormat) -> Self {
+        Self {
+            decode_srgb: (!source_format.is_srgb() && output_format.is_srgb()) as u32,
+            encode_srgb: (source_format.is_srgb() && !output_format.is_srgb()) as u32,
+        }
+    }
+}
+
+// Keep the params layout in sync with the `BlitParams` struct of the blit shader
+const _: () = {
+    use crate::shader::layouts::blit;
+    use encase::ShaderSize;
+
+    assert!(BlitParams::SHADER_SIZE.get() == blit::blit_params::SIZE as u64);
+};
+
+impl Blitter {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("blit_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("blit.wgsl").into()),
+        });
+
+        let samplers = PresentFilter::ALL
+            .into_iter()
+            .map(|filter| {
+                let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("Blit Sampler"),
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: filter.filter_mode(),
+                    min_filter: filter.filter_mode(),
+                    ..Default::default()
+                });
+                (filter, sampler)
+            })
+            .collect();
+
+        Self {
+            bind_group_layout,
+            pipeline_layout,
+            shader,
+            pipelines: HashMap::new(),
+            samplers,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Draw a camera's render target into `rect` of a pass over a texture of `output_format`
+    ///
+    /// `rect` is the pixel `x, y, width, height` of the target in the output.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        render_pass: &mut wgpu::RenderPass,
+        camera: Entity,
+        source: &wgpu::Texture,
+        output_format: wgpu::TextureFormat,
+        filter: PresentFilter,
+        rect: [f32; 4],
+    ) {
+        let state = match self.sources.remove(&camera) {
+            Some(state)
+                if state.texture == *source
+                    && state.filter == filter
+                    && state.output_format == output_format =>
+            {
+                state
+            }
+            // The target was recreated or the settings changed
+            _ => self.create_source(device, source, output_format, filter),
+        };
+        let state = self.sources.entry(camera).or_insert(state);
+
+        let pipeline = self.pipelines.entry(output_format).or_insert_with(|| {
+            create_blit_pipeline(device, &self.pipeline_layout, &self.shader, output_format)
+        });
+
+        let [x, y, width, height] = rect;
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &state.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Forget the bind groups of cameras that weren't drawn this frame
+    pub fn retain(&mut self, cameras: &[Entity]) {
+        self.sources.retain(|camera, _| cameras.contains(camera));
+    }
+
+    fn create_source(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::Texture,
+        output_format: wgpu::TextureFormat,
+        filter: PresentFilter,
+    ) -> BlitSource {
+        let mut data = UniformBuffer::new(Vec::new());
+        data.write(&BlitParams::new(source.format(), output_format))
+            .unwrap();
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Blit Params Buffer"),
+            contents: &data.into_inner(),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let view = source.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.samplers[&filter]),
+                },
+            ],
+            label: Some("blit_bind_group"),
+        });
+
+        BlitSource {
+            bind_group,
+            texture: source.clone(),
+            filter,
+            output_format,
+        }
+    }
+}
+
+fn create_blit_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Blit Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vertex"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fragment"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
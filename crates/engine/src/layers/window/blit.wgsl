@@ -0,0 +1,54 @@
+// Blit - draws a camera's render target into its viewport of the window surface
+
+// Must match `BlitParams` in layers/window/blit.rs
+struct BlitParams {
+    // The source holds sRGB encoded values but samples as linear, decode them
+    decode_srgb: u32,
+    // The output stores values as is, encode them for display
+    encode_srgb: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: BlitParams;
+@group(0) @binding(1) var source_texture: texture_2d<f32>;
+@group(0) @binding(2) var source_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // Fullscreen triangle, scaled into the viewport by the render pass
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(source_texture, source_sampler, in.uv);
+
+    if (params.decode_srgb != 0u) {
+        color = vec4<f32>(srgb_to_linear(color.rgb), color.a);
+    }
+    if (params.encode_srgb != 0u) {
+        color = vec4<f32>(linear_to_srgb(color.rgb), color.a);
+    }
+    return color;
+}
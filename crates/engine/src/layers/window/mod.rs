@@ -1,2 +1,3 @@
+mod blit;
 mod window_layer;
 pub use window_layer::WindowLayer;
@@ -1,11 +1,14 @@
 use crate::prelude::*;
 
+use super::blit::Blitter;
+
 pub struct WindowLayer {
     surface: wgpu::Surface<'static>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     is_surface_configured: bool,
+    blitter: Blitter,
 }
 
 impl WindowLayer {
@@ -25,28 +28,23 @@ impl WindowLayer {
             let mut surface_res = world.get_resource_mut::<GpuSurface>().unwrap();
             let surface = surface_res.0.take().expect("Surface already taken");
 
+            world.init_resource::<PresentSettings>();
+
             (device, queue, adapter, surface)
         };
 
         let surface_caps = surface.get_capabilities(&adapter);
 
-        // Try to use Rgba8Unorm to match raytracer output, fallback to sRGB
-        let surface_format = if surface_caps
+        // Prefer an sRGB surface, the blit converts from any render target format
+        let surface_format = surface_caps
             .formats
-            .contains(&wgpu::TextureFormat::Rgba8Unorm)
-        {
-            wgpu::TextureFormat::Rgba8Unorm
-        } else {
-            surface_caps
-                .formats
-                .iter()
-                .find(|f| f.is_srgb())
-                .copied()
-                .unwrap_or(surface_caps.formats[0])
-        };
+            .iter()
+            .find(|f| f.is_srgb())
+            .copied()
+            .unwrap_or(surface_caps.formats[0]);
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_DST,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -56,12 +54,15 @@ impl WindowLayer {
             desired_maximum_frame_latency: 2,
         };
 
+        let blitter = Blitter::new(&device);
+
         Self {
             surface,
             device,
             queue,
             config,
             is_surface_configured: false,
+            blitter,
         }
     }
 
@@ -87,17 +88,19 @@ impl Layer for WindowLayer {
 
         let mut world = context.world.lock().unwrap();
 
-        // Find the main camera and its render target
-        let target = world
-            .query::<(&Camera, &GpuRenderTarget)>()
+        // Cameras are shown in their viewport, in render order so later ones end up on top
+        let window_size = *world.resource::<WindowSize>();
+        let settings = *world.resource::<PresentSettings>();
+        let mut cameras: Vec<_> = world
+            .query::<(Entity, &Camera, &GpuRenderTarget)>()
             .iter(&world)
-            .find(|(camera, _)| camera.is_main)
-            .map(|(_, target)| target);
+            .collect();
 
-        let Some(target) = target else {
-            // No main camera found, skip rendering
+        if cameras.is_empty() {
+            // No camera to show, skip rendering
             return Ok(());
-        };
+        }
+        cameras.sort_by_key(|(_, camera, _)| camera.order);
 
         // Get the window surface texture
         let surface_texture = self.surface.get_current_texture()?;
@@ -112,32 +115,9 @@ impl Layer for WindowLayer {
                 label: Some("Window Blit Encoder"),
             });
 
-        // Check if formats are compatible for direct copy
-        let source_format = target.texture.format();
-        let dest_format = self.config.format;
-
-        if source_format == dest_format {
-            // Direct copy if formats match
-            encoder.copy_texture_to_texture(
-                target.texture.as_image_copy(),
-                surface_texture.texture.as_image_copy(),
-                wgpu::Extent3d {
-                    width: self.config.width.min(target.texture.width()),
-                    height: self.config.height.min(target.texture.height()),
-                    depth_or_array_layers: 1,
-                },
-            );
-        } else {
-            // Use a render pass to convert formats (e.g., Rgba8Unorm -> Bgra8UnormSrgb)
-            // This will handle format conversion automatically through the GPU
-            let _target_view = target
-                .texture
-                .create_view(&wgpu::TextureViewDescriptor::default());
-
-            // Create a simple fullscreen blit using a render pass
-            // We need a simple shader for this - for now, just clear to a debug color
-            // In a production setup, you'd have a proper blit shader
-            let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        {
+            // Parts of the window no viewport covers and letterbox bars stay black
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Window Blit Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &surface_view,
@@ -152,15 +132,36 @@ impl Layer for WindowLayer {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            drop(render_pass);
 
-            log::warn!(
-                "Format mismatch: source={:?}, dest={:?}. Need blit shader for proper conversion.",
-                source_format,
-                dest_format
-            );
+            for &(entity, camera, target) in &cameras {
+                let source = (target.texture.width(), target.texture.height());
+                let viewport = camera.viewport.pixel_rect(&window_size);
+                let [x, y, width, height] = settings.scaling.fit(source, viewport);
+
+                // Viewports must lie within the surface
+                let x = x.clamp(0.0, self.config.width as f32);
+                let y = y.clamp(0.0, self.config.height as f32);
+                let width = width.min(self.config.width as f32 - x);
+                let height = height.min(self.config.height as f32 - y);
+                if width < 1.0 || height < 1.0 {
+                    continue;
+                }
+
+                self.blitter.draw(
+                    &self.device,
+                    &mut render_pass,
+                    entity,
+                    &target.texture,
+                    self.config.format,
+                    settings.filter,
+                    [x, y, width, height],
+                );
+            }
         }
 
+        let entities: Vec<Entity> = cameras.iter().map(|(entity, ..)| *entity).collect();
+        self.blitter.retain(&entities);
+
         self.queue.submit(std::iter::once(encoder.finish()));
         surface_texture.present();
 
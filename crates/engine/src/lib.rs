@@ -4,7 +4,7 @@ use std::time::{Duration, Instant};
 use winit::{application::ApplicationHandler, event::WindowEvent, window::Window};
 
 use crate::input::InputState;
-use crate::prelude::Shader;
+use crate::prelude::{GizmoBuffer, GpuProfiler, Profiler, Shader};
 pub type Result<T> = anyhow::Result<T>;
 
 pub mod async_task;
@@ -19,6 +19,12 @@ pub trait Layer: 'static {
     fn frame(&mut self, context: &LayerContext) -> std::result::Result<(), wgpu::SurfaceError>;
     fn detach(&mut self, context: &LayerContext);
     fn event(&mut self, _context: &LayerContext, _event: LayerEvent) {}
+
+    /// Name of the layer in profiles, its type name by default
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
 }
 
 pub trait LayerFactory: 'static {
@@ -63,10 +69,23 @@ impl ApplicationBuilder {
     pub fn build(self) -> Application {
         let world = Arc::new(Mutex::new(World::new()));
         
-        // Initialize InputState resource
+        // Initialize InputState, GizmoBuffer and Profiler resources
         {
             let mut w = world.lock().unwrap();
             w.insert_resource(InputState::new());
+            w.init_resource::<GizmoBuffer>();
+            w.insert_resource(Profiler::new());
+        }
+
+        // Time ECS systems, only possible if the host app hasn't installed a subscriber already
+        #[cfg(feature = "profiling")]
+        {
+            let subscriber = world.lock().unwrap().resource::<Profiler>().system_subscriber();
+            if tracing::subscriber::set_global_default(subscriber).is_err() {
+                log::warn!(
+                    "A tracing subscriber is already installed, ECS systems won't be profiled"
+                );
+            }
         }
         
         Application {
@@ -141,11 +160,32 @@ impl Application {
             delta_time,
         };
 
+        // Layers and ECS systems are only timed while the profiler is enabled
+        let profiling = {
+            let mut world = self.world.lock().unwrap();
+            let profiling = world
+                .get_resource_mut::<Profiler>()
+                .is_some_and(|mut profiler| profiler.begin_frame());
+            if let Some(gpu_profiler) = world.get_resource::<GpuProfiler>() {
+                gpu_profiler.begin_frame(profiling);
+            }
+            profiling
+        };
+
         for layer in &mut state.layers {
+            let start = Instant::now();
             layer.frame(&context)?;
+            if profiling {
+                let mut world = self.world.lock().unwrap();
+                world
+                    .resource_mut::<Profiler>()
+                    .record_layer(layer.name(), start, start.elapsed());
+            }
         }
 
-        self.world.lock().unwrap().clear_trackers();
+        let mut world = self.world.lock().unwrap();
+        crate::components::end_profiled_frame(&mut world);
+        world.clear_trackers();
 
         Ok(())
     }
@@ -192,30 +232,12 @@ impl Application {
 
     /// Internal method to actually perform shader registrations after layers are initialized
     fn perform_shader_registrations(&mut self) -> Result<()> {
-        use crate::prelude::*;
         use crate::shader::*;
 
         let registrations = std::mem::take(&mut self.shader_registrations);
+        let mut world = self.world.lock().unwrap();
 
         for registration in registrations {
-            let mut world = self.world.lock().unwrap();
-
-            // Get required resources
-            let device = world.get_resource::<GpuDevice>()
-                .ok_or_else(|| anyhow::anyhow!("GpuDevice resource not found - make sure DeviceLayer is added before registering shaders"))?;
-            let texture_layout = world
-                .get_resource::<TextureBindGroupLayout>()
-                .ok_or_else(|| anyhow::anyhow!("TextureBindGroupLayout resource not found"))?;
-            let camera_layout = world
-                .get_resource::<CameraBindGroupLayout>()
-                .ok_or_else(|| anyhow::anyhow!("CameraBindGroupLayout resource not found"))?;
-            let transform_layout = world
-                .get_resource::<TransformBindGroupLayout>()
-                .ok_or_else(|| anyhow::anyhow!("TransformBindGroupLayout resource not found"))?;
-            let supported_features = world
-                .get_resource::<SupportedFeatures>()
-                .ok_or_else(|| anyhow::anyhow!("SupportedFeatures resource not found"))?;
-
             // Create shader loader based on build configuration
             #[cfg(debug_assertions)]
             let shader_loader =
@@ -223,171 +245,23 @@ impl Application {
                     .map_err(|e| anyhow::anyhow!("Failed to create shader loader: {}", e))?;
 
             #[cfg(not(debug_assertions))]
-            let shader_loader =
-                create_static_shader_loader(registration.static_source, &registration.name);
-
-            let shader = shader_loader.get_shader(&device.0);
-            let shader_source = shader_loader.get_source();
-
-            // Parse bind group requirements
-            let bind_group_requirements = BindGroupRequirement::parse_from_shader(&shader_source);
-            log::info!(
-                "Registered shader '{}' with bind groups: {:?}",
-                registration.shader,
-                bind_group_requirements
+            let shader_loader = create_static_shader_loader(
+                registration.static_source,
+                registration.shader.to_string(),
             );
 
-            // Build bind group layouts dynamically
-            let mut layouts = Vec::new();
-            for requirement in &bind_group_requirements {
-                if let Some(req) = requirement {
-                    let layout = match req {
-                        BindGroupRequirement::Texture => &texture_layout.0,
-                        BindGroupRequirement::Camera => &camera_layout.0,
-                        BindGroupRequirement::Transform => &transform_layout.0,
-                        BindGroupRequirement::Shadow => {
-                            let shadow_layout = world.get_resource::<ShadowBindGroupLayout>().ok_or_else(|| {
-                                anyhow::anyhow!("ShadowBindGroupLayout resource not found - make sure RenderLayer is initialized")
-                            })?;
-                            &shadow_layout.0
-                        }
-                        BindGroupRequirement::Unknown(name) => {
-                            return Err(anyhow::anyhow!(
-                                "Unknown bind group requirement '{}' in shader",
-                                name
-                            ));
-                        }
-                    };
-                    layouts.push(layout);
-                }
-            }
-
-            // Create render pipelines for all render modes
-            let surface_format = wgpu::TextureFormat::Bgra8UnormSrgb;
-            let render_pipeline_layout =
-                device
-                    .0
-                    .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                        label: Some(&format!("{} Pipeline Layout", registration.shader)),
-                        bind_group_layouts: &layouts,
-                        push_constant_ranges: &[],
-                    });
-
-            // Build list of render modes based on supported features
-            let mut render_modes = vec![RenderMode::filled()];
-
-            if supported_features.polygon_mode_line {
-                render_modes.push(RenderMode::wireframe());
-            }
-
-            if supported_features.polygon_mode_point {
-                render_modes.push(RenderMode {
-                    polygon_mode: wgpu::PolygonMode::Point,
-                });
-            }
-
-            // Clone bind_group_requirements once for all pipelines
-            let bind_group_requirements_clone = bind_group_requirements.clone();
-
-            // Clone device for later use (to avoid borrow issues)
-            let device_clone = device.0.clone();
-
-            // Create all shader instances first
-            let mut instances: Vec<(RenderMode, ShaderInstance)> = Vec::new();
-
-            // Different vertex buffer layout for instanced shaders
-            let vertex_buffer_layout = Vertex::desc();
-            let instance_buffer_layout = InstanceData::desc();
-            
-            let vertex_buffers: &[wgpu::VertexBufferLayout] = if matches!(registration.shader, Shader::Instanced) {
-                &[vertex_buffer_layout, instance_buffer_layout]
-            } else {
-                &[vertex_buffer_layout]
-            };
-
-            for render_mode in &render_modes {
-                let render_pipeline =
-                    device_clone.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                        label: Some(&format!(
-                            "{} Pipeline {:?}",
-                            registration.shader, render_mode.polygon_mode
-                        )),
-                        layout: Some(&render_pipeline_layout),
-                        vertex: wgpu::VertexState {
-                            module: &shader,
-                            entry_point: Some("vertex"),
-                            buffers: vertex_buffers,
-                            compilation_options: wgpu::PipelineCompilationOptions::default(),
-                        },
-                        fragment: Some(wgpu::FragmentState {
-                            module: &shader,
-                            entry_point: Some("fragment"),
-                            targets: &[Some(wgpu::ColorTargetState {
-                                format: surface_format,
-                                blend: Some(wgpu::BlendState::REPLACE),
-                                write_mask: wgpu::ColorWrites::ALL,
-                            })],
-                            compilation_options: wgpu::PipelineCompilationOptions::default(),
-                        }),
-                        primitive: wgpu::PrimitiveState {
-                            topology: wgpu::PrimitiveTopology::TriangleList,
-                            strip_index_format: None,
-                            front_face: wgpu::FrontFace::Ccw,
-                            cull_mode: Some(wgpu::Face::Back),
-                            polygon_mode: render_mode.polygon_mode,
-                            unclipped_depth: false,
-                            conservative: false,
-                        },
-                        depth_stencil: Some(wgpu::DepthStencilState {
-                            format: wgpu::TextureFormat::Depth32Float,
-                            depth_write_enabled: true,
-                            depth_compare: wgpu::CompareFunction::Less,
-                            stencil: wgpu::StencilState::default(),
-                            bias: wgpu::DepthBiasState::default(),
-                        }),
-                        multisample: wgpu::MultisampleState {
-                            count: 1,
-                            mask: !0,
-                            alpha_to_coverage_enabled: false,
-                        },
-                        multiview: None,
-                        cache: None,
-                    });
-
-                let shader_instance = ShaderInstance {
-                    module: shader.clone(),
-                    pipeline: render_pipeline,
-                    bind_group_requirements: bind_group_requirements_clone.clone(),
-                };
-
-                instances.push((*render_mode, shader_instance));
-            }
-
-            // Register all instances with shader cache
+            // Pipelines are created lazily by the cache for each render state they are drawn with
             let mut shader_cache = world.get_resource_mut::<ShaderCache>().ok_or_else(|| {
                 anyhow::anyhow!(
                     "ShaderCache resource not found - make sure RenderLayer is added before registering shaders"
                 )
             })?;
 
-            let mut shader_loader_opt = Some(shader_loader);
-
-            for (i, (render_mode, shader_instance)) in instances.into_iter().enumerate() {
-                // Only register shader loader once (for the first render mode)
-                let loader: Box<dyn ShaderLoader> = if i == 0 {
-                    shader_loader_opt.take().unwrap()
-                } else {
-                    // For other render modes, use dummy loader - only the first one is actually used for hot reloading
-                    Box::new(StaticShaderLoader::new("", ""))
-                };
-
-                shader_cache.register_shader(
-                    registration.shader.clone(),
-                    render_mode,
-                    loader,
-                    shader_instance,
-                );
-            }
+            shader_cache
+                .register_shader(registration.shader.clone(), shader_loader)
+                .map_err(|e| {
+                    anyhow::anyhow!("Failed to register shader '{}': {}", registration.shader, e)
+                })?;
         }
 
         Ok(())
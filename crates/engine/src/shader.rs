@@ -9,6 +9,27 @@ use std::sync::{
     mpsc::{Receiver, channel},
 };
 
+/// Struct layouts of every WGSL file in the crate, generated by `build.rs`.
+///
+/// Each shader gets a module named after its file, with one module per struct holding
+/// `SIZE`, `ALIGN` and per-member `_OFFSET`/`_SIZE` (and `_LOCATION` for vertex inputs)
+/// constants. Assert Rust-side types against these so layout mismatches fail the build.
+pub mod layouts {
+    include!(concat!(env!("OUT_DIR"), "/wgsl_layouts.rs"));
+}
+
+/// Compile-time check that a vertex attribute matches a Rust field and a shader input
+pub const fn vertex_attribute_matches(
+    attribute: &wgpu::VertexAttribute,
+    field_offset: usize,
+    location: u32,
+    size: usize,
+) -> bool {
+    attribute.offset == field_offset as u64
+        && attribute.shader_location == location
+        && attribute.format.size() == size as u64
+}
+
 /// Validates WGSL shader source using naga
 pub fn validate_wgsl(source: &str, shader_name: &str) -> Result<(), String> {
     let module = naga::front::wgsl::parse_str(source)
@@ -370,66 +391,314 @@ pub struct ShaderInstance {
     pub bind_group_requirements: Vec<Option<BindGroupRequirement>>,
 }
 
-/// Cache key that combines shader and render mode
+/// Vertex buffers a pipeline reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexLayout {
+    /// Slot 0 = `Vertex`
+    Standard,
+    /// Slot 0 = `Vertex`, slot 1 = per-instance `InstanceData`
+    Instanced,
+}
+
+impl VertexLayout {
+    pub fn buffers(&self) -> Vec<wgpu::VertexBufferLayout<'static>> {
+        match self {
+            VertexLayout::Standard => vec![Vertex::desc()],
+            VertexLayout::Instanced => vec![Vertex::desc(), InstanceData::desc()],
+        }
+    }
+}
+
+/// Cache key covering everything that goes into a render pipeline
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct ShaderKey {
+pub struct PipelineKey {
     pub shader: Shader,
     pub render_mode: RenderMode,
+    pub vertex_layout: VertexLayout,
+    pub color_format: wgpu::TextureFormat,
+    pub depth_format: Option<wgpu::TextureFormat>,
+    pub sample_count: u32,
+    pub depth_mode: DepthMode,
+    /// Ignored by shaders without a `DEBUG_VIEW` override constant
+    pub debug_view: DebugView,
 }
 
-impl ShaderKey {
-    pub fn new(shader: Shader, render_mode: RenderMode) -> Self {
+impl PipelineKey {
+    /// Key for drawing `material` into the given color and depth targets
+    pub fn for_targets(
+        material: &Material,
+        vertex_layout: VertexLayout,
+        color_target: &wgpu::Texture,
+        depth_target: Option<&wgpu::Texture>,
+    ) -> Self {
         Self {
-            shader,
-            render_mode,
+            shader: material.shader.clone(),
+            render_mode: material.render_mode,
+            vertex_layout,
+            color_format: color_target.format(),
+            depth_format: depth_target.map(|t| t.format()),
+            sample_count: color_target.sample_count(),
+            depth_mode: DepthMode::Standard,
+            debug_view: DebugView::Lit,
         }
     }
 }
 
-/// Central cache for managing shaders and their hot-reload state
+/// Bind group layouts a material shader can ask for through its variable names
+#[derive(Clone)]
+pub struct MaterialBindGroupLayouts {
+    pub texture: wgpu::BindGroupLayout,
+    pub camera: wgpu::BindGroupLayout,
+    pub transform: wgpu::BindGroupLayout,
+    pub shadow: wgpu::BindGroupLayout,
+}
+
+impl MaterialBindGroupLayouts {
+    pub fn get(
+        &self,
+        requirement: &BindGroupRequirement,
+    ) -> Result<&wgpu::BindGroupLayout, String> {
+        match requirement {
+            BindGroupRequirement::Texture => Ok(&self.texture),
+            BindGroupRequirement::Camera => Ok(&self.camera),
+            BindGroupRequirement::Transform => Ok(&self.transform),
+            BindGroupRequirement::Shadow => Ok(&self.shadow),
+            BindGroupRequirement::Unknown(name) => Err(format!(
+                "Unknown bind group requirement '{}' in shader",
+                name
+            )),
+        }
+    }
+}
+
+/// Compiled module and pipeline layout shared by every pipeline of one shader
+struct ShaderProgram {
+    module: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    bind_group_requirements: Vec<Option<BindGroupRequirement>>,
+    /// Declares `override DEBUG_VIEW`, see `DebugView`
+    has_debug_view: bool,
+    /// Declares `override ALPHA_MODE`, see `RenderMode::alpha_mode_index`
+    has_alpha_mode: bool,
+}
+
+/// Central cache for managing shaders, their pipelines and their hot-reload state
+///
+/// Pipelines are created lazily the first time a `PipelineKey` is requested and
+/// are dropped whenever their shader is reloaded.
 #[derive(Resource)]
 pub struct ShaderCache {
-    shaders: HashMap<ShaderKey, Arc<ShaderInstance>>,
+    device: wgpu::Device,
+    bind_group_layouts: MaterialBindGroupLayouts,
+    supported_features: SupportedFeatures,
+    programs: HashMap<Shader, ShaderProgram>,
+    /// Modules of `Shader::PostEffect` shaders, which bind a layout of their own
+    post_effects: HashMap<Shader, wgpu::ShaderModule>,
+    // `None` marks a key whose pipeline failed to build, so it isn't retried every frame
+    pipelines: HashMap<PipelineKey, Option<Arc<ShaderInstance>>>,
     loaders: HashMap<Shader, Box<dyn ShaderLoader>>,
     sources: HashMap<Shader, String>,
 }
 
 impl ShaderCache {
-    pub fn new() -> Self {
+    pub fn new(
+        device: wgpu::Device,
+        bind_group_layouts: MaterialBindGroupLayouts,
+        supported_features: SupportedFeatures,
+    ) -> Self {
         Self {
-            shaders: HashMap::new(),
+            device,
+            bind_group_layouts,
+            supported_features,
+            programs: HashMap::new(),
+            post_effects: HashMap::new(),
+            pipelines: HashMap::new(),
             loaders: HashMap::new(),
             sources: HashMap::new(),
         }
     }
 
-    /// Register a shader with the cache for a specific render mode
+    /// Register a shader with the cache, pipelines are built on first use
     pub fn register_shader(
         &mut self,
         shader: Shader,
-        render_mode: RenderMode,
         loader: Box<dyn ShaderLoader>,
-        instance: ShaderInstance,
-    ) {
-        let key = ShaderKey::new(shader.clone(), render_mode);
+    ) -> Result<(), String> {
         let source = loader.get_source();
-        self.shaders.insert(key, Arc::new(instance));
+        let module = loader.get_shader(&self.device);
+        self.load_program(&shader, module, &source)?;
+
         self.sources.insert(shader.clone(), source);
+        self.loaders.insert(shader, loader);
+        Ok(())
+    }
+
+    fn load_program(
+        &mut self,
+        shader: &Shader,
+        module: wgpu::ShaderModule,
+        source: &str,
+    ) -> Result<(), String> {
+        if let Shader::PostEffect(_) = shader {
+            log::info!("Loaded post effect shader '{}'", shader);
+            self.post_effects.insert(shader.clone(), module);
+            return Ok(());
+        }
+
+        let bind_group_requirements = BindGroupRequirement::parse_from_shader(source);
+        log::info!(
+            "Loaded shader '{}' with bind groups: {:?}",
+            shader,
+            bind_group_requirements
+        );
+
+        let mut layouts = Vec::new();
+        for requirement in bind_group_requirements.iter().flatten() {
+            layouts.push(self.bind_group_layouts.get(requirement)?);
+        }
 
-        // Only store one loader per shader (loaders are shared across render modes)
-        if !self.loaders.contains_key(&shader) {
-            self.loaders.insert(shader, loader);
+        let layout = self
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(&format!("{} Pipeline Layout", shader)),
+                bind_group_layouts: &layouts,
+                push_constant_ranges: &[],
+            });
+
+        self.programs.insert(
+            shader.clone(),
+            ShaderProgram {
+                module,
+                layout,
+                bind_group_requirements,
+                has_debug_view: source.contains("override DEBUG_VIEW"),
+                has_alpha_mode: source.contains("override ALPHA_MODE"),
+            },
+        );
+        self.pipelines.retain(|key, _| key.shader != *shader);
+        Ok(())
+    }
+
+    /// Get the pipeline for a key, creating it if it hasn't been built yet
+    pub fn get_or_create_pipeline(&mut self, key: &PipelineKey) -> Option<Arc<ShaderInstance>> {
+        if let Some(pipeline) = self.pipelines.get(key) {
+            return pipeline.clone();
         }
+
+        let pipeline = match self.create_pipeline(key) {
+            Ok(instance) => Some(Arc::new(instance)),
+            Err(e) => {
+                log::error!("Failed to create pipeline for {:?}: {}", key, e);
+                None
+            }
+        };
+        self.pipelines.insert(key.clone(), pipeline.clone());
+        pipeline
     }
 
-    /// Get a shader instance by name and render mode
-    pub fn get_shader(
-        &self,
-        shader: &Shader,
-        render_mode: &RenderMode,
-    ) -> Option<Arc<ShaderInstance>> {
-        let key = ShaderKey::new(shader.clone(), *render_mode);
-        self.shaders.get(&key).cloned()
+    /// Get an already created pipeline
+    pub fn get_pipeline(&self, key: &PipelineKey) -> Option<Arc<ShaderInstance>> {
+        self.pipelines.get(key).cloned().flatten()
+    }
+
+    fn create_pipeline(&self, key: &PipelineKey) -> Result<ShaderInstance, String> {
+        let program = self
+            .programs
+            .get(&key.shader)
+            .ok_or_else(|| format!("Shader '{}' has not been registered", key.shader))?;
+
+        if !self
+            .supported_features
+            .supports_polygon_mode(key.render_mode.polygon_mode)
+        {
+            return Err(format!(
+                "Polygon mode {:?} is not supported by this device",
+                key.render_mode.polygon_mode
+            ));
+        }
+
+        let vertex_buffers = key.vertex_layout.buffers();
+
+        let mut render_mode = key.render_mode;
+        let mut constants: Vec<(&str, f64)> = Vec::new();
+        if program.has_debug_view {
+            constants.push(("DEBUG_VIEW", key.debug_view.shader_index() as f64));
+            // Every fragment adds to the heat, hidden ones included
+            if key.debug_view == DebugView::Overdraw {
+                render_mode.blend = BlendMode::Additive;
+                render_mode.depth_test = false;
+                render_mode.depth_write = false;
+                render_mode.alpha_to_coverage = false;
+            }
+        }
+        if program.has_alpha_mode {
+            let alpha_mode = render_mode.alpha_mode_index(key.sample_count);
+            constants.push(("ALPHA_MODE", alpha_mode as f64));
+        }
+        let compilation_options = wgpu::PipelineCompilationOptions {
+            constants: &constants,
+            ..Default::default()
+        };
+
+        // Catch validation errors so a bad combination doesn't bring the app down
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let pipeline = self
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!(
+                    "{} Pipeline {:?} {:?}",
+                    key.shader, key.render_mode.polygon_mode, key.render_mode.blend
+                )),
+                layout: Some(&program.layout),
+                vertex: wgpu::VertexState {
+                    module: &program.module,
+                    entry_point: Some("vertex"),
+                    buffers: &vertex_buffers,
+                    compilation_options: compilation_options.clone(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &program.module,
+                    entry_point: Some("fragment"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: key.color_format,
+                        blend: Some(render_mode.blend.blend_state()),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options,
+                }),
+                primitive: render_mode.primitive_state(),
+                depth_stencil: key.depth_format.map(|format| {
+                    let mut state = render_mode.depth_stencil_state(format);
+                    state.depth_compare = key.depth_mode.compare(state.depth_compare);
+                    state
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: key.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: render_mode.alpha_to_coverage
+                        && key.sample_count > 1,
+                },
+                multiview: None,
+                cache: None,
+            });
+
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(error.to_string());
+        }
+
+        Ok(ShaderInstance {
+            module: program.module.clone(),
+            pipeline,
+            bind_group_requirements: program.bind_group_requirements.clone(),
+        })
+    }
+
+    /// Get the current module of a post effect shader
+    ///
+    /// A new module is returned after every successful hot reload.
+    pub fn get_post_effect_module(&self, shader: &Shader) -> Option<&wgpu::ShaderModule> {
+        self.post_effects.get(shader)
     }
 
     /// Get the shader source by name
@@ -437,35 +706,30 @@ impl ShaderCache {
         self.sources.get(name).map(|s| s.as_str())
     }
 
-    /// Check all shaders for hot-reload and return updated shaders
-    pub fn check_hot_reload(
-        &mut self,
-        device: &wgpu::Device,
-    ) -> Vec<(Shader, Result<(wgpu::ShaderModule, String), String>)> {
+    /// Check all shaders for hot-reload, rebuilding the ones that changed
+    ///
+    /// Returns the shaders that were reloaded along with the outcome, the pipelines
+    /// of successfully reloaded shaders are rebuilt lazily on their next use.
+    pub fn check_hot_reload(&mut self) -> Vec<(Shader, Result<(), String>)> {
         let mut reloaded = Vec::new();
 
         for (name, loader) in &mut self.loaders {
-            if let Some(reload_result) = loader.check_reload(device) {
-                // If reload was successful, update stored source
-                if let Ok((_, ref new_source)) = reload_result {
-                    self.sources.insert(name.clone(), new_source.clone());
-                }
+            if let Some(reload_result) = loader.check_reload(&self.device) {
                 reloaded.push((name.clone(), reload_result));
             }
         }
 
         reloaded
-    }
-
-    /// Update a shader instance after successful reload for a specific render mode
-    pub fn update_shader(
-        &mut self,
-        shader: &Shader,
-        render_mode: RenderMode,
-        instance: ShaderInstance,
-    ) {
-        let key = ShaderKey::new(shader.clone(), render_mode);
-        self.shaders.insert(key, Arc::new(instance));
+            .into_iter()
+            .map(|(name, reload_result)| {
+                let result = reload_result.and_then(|(module, source)| {
+                    self.load_program(&name, module, &source)?;
+                    self.sources.insert(name.clone(), source);
+                    Ok(())
+                });
+                (name, result)
+            })
+            .collect()
     }
 
     /// Get all shader names
@@ -479,19 +743,13 @@ impl ShaderCache {
 pub struct RaytracerShader {
     pub loader: Box<dyn ShaderLoader>,
     pub compute_pipeline: wgpu::ComputePipeline,
-    pub display_pipeline: wgpu::RenderPipeline,
 }
 
 impl RaytracerShader {
-    pub fn new(
-        loader: Box<dyn ShaderLoader>,
-        compute_pipeline: wgpu::ComputePipeline,
-        display_pipeline: wgpu::RenderPipeline,
-    ) -> Self {
+    pub fn new(loader: Box<dyn ShaderLoader>, compute_pipeline: wgpu::ComputePipeline) -> Self {
         Self {
             loader,
             compute_pipeline,
-            display_pipeline,
         }
     }
 
@@ -201,15 +201,23 @@ fn main() -> Result<()> {
             },
             Camera {
                 is_main: true,
-                fovy: 1.0,
-                target: Point3::new(0.0, 0.0, 0.0), // Looking straight down at origin
-                zfar: 100000.0,
-                znear: 0.1,
+                projection: Projection::Perspective {
+                    fovy: 1.0,
+                    znear: 0.1,
+                    zfar: 100000.0,
+                },
+                // Planet terrain thousands of units away z-fights with standard depth
+                depth_mode: DepthMode::ReverseZ,
                 aperture: 0.1,
                 focus_distance: 2000.0,
+                ..Default::default()
             },
             CameraController::default(),
             RenderTarget {},
+            Tonemapping::default(),
+            PostProcessStack::new()
+                .with(PostEffect::Bloom(Bloom::default()))
+                .with(PostEffect::Fxaa(Fxaa::default())),
         ),
     );
 
@@ -224,10 +232,11 @@ fn main() -> Result<()> {
     //         },
     //         Camera {
     //             is_main: true,
-    //             fovy: 1.0,
-    //             target: Point3::new(0.0, 0.0, 0.0),
-    //             zfar: 100.0,
-    //             znear: 0.01,
+    //             projection: Projection::Perspective {
+    //                 fovy: 1.0,
+    //                 znear: 0.01,
+    //                 zfar: 100.0,
+    //             },
     //             aperture: 0.1,
     //             focus_distance: 10.0,
     //         },
@@ -239,13 +248,14 @@ fn main() -> Result<()> {
     app.spawn(
         "Main Light",
         (
-            Light {
-                intensity: 1.0,
-                color: [1.0, 1.0, 1.0],
-            },
+            Light::directional(1.0, [1.0, 1.0, 1.0]),
             Transform {
                 position: Point3::new(5.0, 10.0, 5.0),
-                rotation: UnitQuaternion::identity(),
+                // Point the light's forward (-Z) axis at the origin
+                rotation: UnitQuaternion::face_towards(
+                    &Vector3::new(5.0, 10.0, 5.0),
+                    &Vector3::y(),
+                ),
                 scale: Vector3::new(1.0, 1.0, 1.0),
             },
         ),
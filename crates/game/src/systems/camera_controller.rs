@@ -4,11 +4,11 @@ use winit::keyboard::KeyCode;
 /// Camera controller system for WASD + mouse look
 /// Right-click to capture mouse, WASD to move, mouse to look around
 pub fn update_camera_controller(
-    mut camera_query: Query<(&mut Transform, &mut Camera, &mut CameraController)>,
+    mut camera_query: Query<(&mut Transform, &mut CameraController), With<Camera>>,
     input: Res<InputState>,
     time: Res<Time>,
 ) {
-    for (mut transform, mut camera, mut controller) in camera_query.iter_mut() {
+    for (mut transform, mut controller) in camera_query.iter_mut() {
         let dt = time.0.as_secs_f32();
         
         // Mouse look (only when captured)
@@ -60,10 +60,10 @@ pub fn update_camera_controller(
         // Normalize and apply speed
         if movement.magnitude() > 0.0 {
             movement = movement.normalize() * controller.move_speed * dt;
-            transform.position += movement;
+            transform.position += movement.cast();
         }
         
-        // Update camera target to look in forward direction
-        camera.target = transform.position + forward * 10.0;
+        // Face the camera's -Z axis forward
+        transform.rotation = UnitQuaternion::face_towards(&-forward, &up);
     }
 }
@@ -12,8 +12,6 @@ pub fn update_instanced_quad_lod(
         return;
     };
 
-    let camera_pos = camera_transform.position;
-
     for (mut instanced_mesh, lod_transform) in lod_query.iter_mut() {
         // Get parent entity to find config
         // For now, just use default config
@@ -24,14 +22,6 @@ pub fn update_instanced_quad_lod(
             .filter(|c| c.visible && c.children.is_none())
             .count();
         
-        // Compute entity's world transform matrix
-        let entity_matrix = {
-            let translation = Matrix4::new_translation(&lod_transform.position.coords);
-            let rotation = lod_transform.rotation.to_homogeneous();
-            let scale = Matrix4::new_nonuniform_scaling(&lod_transform.scale);
-            translation * rotation * scale
-        };
-        
         let mut needs_update = false;
 
         // Check all chunks for split/collapse
@@ -49,7 +39,7 @@ pub fn update_instanced_quad_lod(
                 continue;
             }
 
-            let distance = (camera_pos - chunk.center).magnitude();
+            let distance = (camera_transform.position - chunk.center).magnitude() as f32;
 
             // Check if should split
             if chunk.depth < config.max_depth as u32 {
@@ -81,18 +71,16 @@ pub fn update_instanced_quad_lod(
                             (cz_min + cz_max) / 2.0,
                         );
 
-                        // Transform for this chunk (scale and position) in local space
+                        // Transform for this chunk (scale and position) in local space, the
+                        // entity transform is applied when drawing
                         let size = cx_max - cx_min;
                         let local_transform = Matrix4::new_translation(&Vector3::new(*cx_min + size / 2.0, 0.0, *cz_min + size / 2.0))
                             * Matrix4::new_nonuniform_scaling(&Vector3::new(size / 2.0, 50.0, size / 2.0));
                         
-                        // Apply entity transform to get world space transform
-                        let world_transform = entity_matrix * local_transform;
-                        
                         // Transform center to world space for distance calculations
-                        let center_world = entity_matrix.transform_point(&center_local);
+                        let center_world = lod_transform.transform_point(&center_local);
 
-                        let child = LodChunk::new(*bounds, child_depth, center_world, world_transform);
+                        let child = LodChunk::new(*bounds, child_depth, center_world, local_transform);
                         
                         child_indices[i] = instanced_mesh.chunks.len();
                         instanced_mesh.chunks.push(child);
@@ -130,14 +118,6 @@ pub fn initialize_instanced_quad_lod(
 
         log::info!("Initializing instanced quad LOD");
 
-        // Compute entity's world transform matrix
-        let entity_matrix = {
-            let translation = Matrix4::new_translation(&lod_transform.position.coords);
-            let rotation = lod_transform.rotation.to_homogeneous();
-            let scale = Matrix4::new_nonuniform_scaling(&lod_transform.scale);
-            translation * rotation * scale
-        };
-
         // Create root chunk covering entire area
         let bounds = (-1000.0, 1000.0, -1000.0, 1000.0);
         let center_local = Point3::new(0.0, 0.0, 0.0);
@@ -145,13 +125,10 @@ pub fn initialize_instanced_quad_lod(
         // Root chunk transform in local space (covers -1000 to 1000)
         let local_transform = Matrix4::new_nonuniform_scaling(&Vector3::new(1000.0, 50.0, 1000.0));
         
-        // Apply entity transform to get world space transform
-        let world_transform = entity_matrix * local_transform;
-        
         // Transform center to world space
-        let center_world = entity_matrix.transform_point(&center_local);
+        let center_world = lod_transform.transform_point(&center_local);
 
-        let root_chunk = LodChunk::new(bounds, 0, center_world, world_transform);
+        let root_chunk = LodChunk::new(bounds, 0, center_world, local_transform);
         
         instanced_mesh.chunks.push(root_chunk);
         instanced_mesh.mark_dirty();
@@ -162,42 +139,23 @@ pub fn initialize_instanced_quad_lod(
     }
 }
 
-/// Update chunk transforms when entity Transform changes
+/// Update chunk centers when entity Transform changes
+///
+/// Chunk transforms are relative to the entity, so only the world space centers used for
+/// distance checks move with it.
 pub fn update_instanced_lod_transforms(
     mut lod_query: Query<(&mut InstancedLodMesh, &Transform), (With<QuadLodTest>, Changed<Transform>)>,
 ) {
     for (mut instanced_mesh, lod_transform) in lod_query.iter_mut() {
-        // Compute entity's world transform matrix
-        let entity_matrix = {
-            let translation = Matrix4::new_translation(&lod_transform.position.coords);
-            let rotation = lod_transform.rotation.to_homogeneous();
-            let scale = Matrix4::new_nonuniform_scaling(&lod_transform.scale);
-            translation * rotation * scale
-        };
+        log::info!("Transform changed - updating {} chunk centers", instanced_mesh.chunks.len());
         
-        log::info!("Transform changed - updating {} chunk transforms", instanced_mesh.chunks.len());
-        
-        // Rebuild transforms for all chunks
-        // Store original local transforms if not already stored
-        // For now, just rebuild from scratch based on bounds
         for chunk in instanced_mesh.chunks.iter_mut() {
             let (x_min, x_max, z_min, z_max) = chunk.bounds;
-            let size = x_max - x_min;
-            
-            // Reconstruct local transform from bounds
-            let local_transform = Matrix4::new_translation(&Vector3::new(x_min + size / 2.0, 0.0, z_min + size / 2.0))
-                * Matrix4::new_nonuniform_scaling(&Vector3::new(size / 2.0, 50.0, size / 2.0));
-            
-            // Apply entity transform
-            chunk.transform = entity_matrix * local_transform;
             
             // Update center position in world space
-            let center_local = Vector3::new((x_min + x_max) / 2.0, 0.0, (z_min + z_max) / 2.0);
-            let center_world = entity_matrix.transform_point(&Point3::from(center_local));
-            chunk.center = center_world;
+            let center_local = Point3::new((x_min + x_max) / 2.0, 0.0, (z_min + z_max) / 2.0);
+            chunk.center = lod_transform.transform_point(&center_local);
         }
-        
-        instanced_mesh.mark_dirty();
     }
 }
 
@@ -5,10 +5,10 @@ use noise::{NoiseFn, Perlin};
 /// Spawn 6 root chunks (one per cube face) when a PlanetLod is added
 pub fn initialize_planet_lod_chunks(
     mut commands: Commands,
-    planet_query: Query<(Entity, Option<&Material>, Option<&Texture>), (With<PlanetLod>, Without<PlanetChunk>)>,
+    planet_query: Query<(Entity, &Transform, Option<&Material>, Option<&Texture>), (With<PlanetLod>, Without<PlanetChunk>)>,
     chunk_query: Query<&PlanetChunk>,
 ) {
-    for (planet_entity, material, texture) in planet_query.iter() {
+    for (planet_entity, planet_transform, material, texture) in planet_query.iter() {
         // Check if this planet already has chunks (avoid respawning)
         let has_chunks = chunk_query
             .iter()
@@ -29,11 +29,12 @@ pub fn initialize_planet_lod_chunks(
         // Spawn 6 root chunks, one for each cube face
         for face in CubeFace::all() {
             let chunk = PlanetChunk::new_root(planet_entity, face);
+            let transform = chunk_transform(planet_transform, &chunk);
 
             let mut entity_commands = commands.spawn((
                 chunk,
                 ChunkParent { entity: planet_entity },
-                Transform::default(),
+                transform,
                 material.clone(),
             ));
 
@@ -49,21 +50,22 @@ pub fn initialize_planet_lod_chunks(
 pub fn update_planet_lod_raycast(
     camera_query: Query<(&Camera, &Transform), With<Camera>>,
     mut planet_query: Query<(&mut PlanetLod, &Transform)>,
+    mut gizmos: Gizmos,
 ) {
     // Find the main camera
     let main_camera = camera_query.iter().find(|(cam, _)| cam.is_main);
 
-    let Some((camera, camera_transform)) = main_camera else {
+    let Some((_, camera_transform)) = main_camera else {
         return; // No main camera found
     };
 
     // Generate ray from camera center
-    let ray = camera_center_ray(camera, camera_transform);
+    let ray = camera_center_ray(camera_transform);
 
     // Check intersection with each LOD planet
     for (mut planet_lod, planet_transform) in planet_query.iter_mut() {
         // Planet center is at its transform position
-        let planet_center = planet_transform.position;
+        let planet_center = planet_transform.position.cast();
 
         // For now, assume planet radius is 1.0 (we'll apply scale later if needed)
         // Since the planet is scaled via transform, we need to account for that
@@ -72,6 +74,12 @@ pub fn update_planet_lod_raycast(
         // Test intersection
         if let Some(intersection) = ray_sphere_intersection(&ray, planet_center, planet_radius) {
             planet_lod.raycast_hit = Some(intersection.point);
+            // Mark where the LOD is focused
+            gizmos.sphere(
+                intersection.point,
+                planet_radius * 0.02,
+                [1.0, 0.8, 0.0, 1.0],
+            );
         } else {
             planet_lod.raycast_hit = None;
         }
@@ -130,6 +138,8 @@ fn generate_chunk_mesh(
 ) -> Mesh {
     let noise = Perlin::new(seed);
     let (u_min, u_max, v_min, v_max) = uv_bounds;
+    // Vertices are relative to the chunk center so they keep their precision at any planet scale
+    let center = chunk_center(&face, uv_bounds);
 
     let mut vertices = Vec::new();
     let mut indices = Vec::new();
@@ -183,7 +193,7 @@ fn generate_chunk_mesh(
             }
 
             vertices.push(Vertex {
-                position: [position.x, position.y, position.z],
+                position: (position - center).into(),
                 uv: [u_local, v_local], // Use local UV for texturing
                 normal: [normal.x, normal.y, normal.z],
             });
@@ -211,6 +221,27 @@ fn generate_chunk_mesh(
     Mesh { vertices, indices }
 }
 
+/// Center of a chunk on the unit sphere, the origin of its mesh
+fn chunk_center(face: &CubeFace, uv_bounds: (f32, f32, f32, f32)) -> Vector3<f32> {
+    let (u_min, u_max, v_min, v_max) = uv_bounds;
+    cube_face_uv_to_xyz(face, (u_min + u_max) * 0.5, (v_min + v_max) * 0.5).normalize()
+}
+
+/// Transform placing a chunk's mesh on its planet
+///
+/// The offset to the chunk center is added in double precision, so chunks far from the planet
+/// center don't jitter.
+fn chunk_transform(planet_transform: &Transform, chunk: &PlanetChunk) -> Transform {
+    let center = chunk_center(&chunk.face, chunk.uv_bounds);
+    let offset = planet_transform.rotation * center.component_mul(&planet_transform.scale);
+
+    Transform {
+        position: planet_transform.position + offset.cast(),
+        rotation: planet_transform.rotation,
+        scale: planet_transform.scale,
+    }
+}
+
 /// Helper: Convert UV on cube face to 3D position
 fn cube_face_uv_to_xyz(face: &CubeFace, u: f32, v: f32) -> Vector3<f32> {
     let a = 2.0 * u - 1.0;
@@ -304,19 +335,14 @@ pub fn copy_texture_to_children(
 /// Children maintain their local position/rotation/scale relative to parent
 pub fn update_children_transforms(
     parent_query: Query<(Entity, &Transform), (With<CopyToChildren>, Changed<Transform>, Without<ChunkParent>)>,
-    mut children_query: Query<(&ChunkParent, &mut Transform), With<ChunkParent>>,
+    mut children_query: Query<(&ChunkParent, &PlanetChunk, &mut Transform), With<ChunkParent>>,
 ) {
     for (parent_entity, parent_transform) in parent_query.iter() {
-        // For now, children just inherit the parent's scale
-        // Position and rotation stay at their local values (usually default)
-        // This makes the chunks render at the planet's position/rotation/scale
-        for (chunk_parent, mut child_transform) in children_query.iter_mut() {
+        // Children inherit the parent's rotation and scale
+        // Their position is offset to their center, where their mesh is centered
+        for (chunk_parent, chunk, mut child_transform) in children_query.iter_mut() {
             if chunk_parent.entity == parent_entity {
-                // Inherit parent's position, rotation, and scale
-                // This makes chunks render in world space at the same transform as parent
-                child_transform.position = parent_transform.position;
-                child_transform.rotation = parent_transform.rotation;
-                child_transform.scale = parent_transform.scale;
+                *child_transform = chunk_transform(parent_transform, chunk);
             }
         }
     }
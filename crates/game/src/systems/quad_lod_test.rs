@@ -189,7 +189,7 @@ pub fn split_quad_chunks(
         return;
     };
 
-    let camera_pos = camera_transform.position;
+    let camera_pos: Point3<f32> = camera_transform.position.cast();
 
     // Collect chunks to split (to avoid borrow conflicts)
     let mut chunks_to_split = Vec::new();
@@ -276,7 +276,7 @@ pub fn collapse_quad_chunks(
         return;
     };
 
-    let camera_pos = camera_transform.position;
+    let camera_pos: Point3<f32> = camera_transform.position.cast();
 
     // Collect chunks to collapse
     let mut chunks_to_collapse = Vec::new();
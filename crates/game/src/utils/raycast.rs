@@ -1,26 +1,5 @@
 use crate::prelude::*;
 
-/// Represents a ray in 3D space
-pub struct Ray {
-    pub origin: Point3<f32>,
-    pub direction: Vector3<f32>, // Should be normalized
-}
-
-impl Ray {
-    /// Create a new ray with a normalized direction
-    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
-        Self {
-            origin,
-            direction: direction.normalize(),
-        }
-    }
-
-    /// Get a point along the ray at distance t
-    pub fn point_at(&self, t: f32) -> Point3<f32> {
-        self.origin + self.direction * t
-    }
-}
-
 /// Result of a ray-sphere intersection test
 pub struct SphereIntersection {
     /// Distance along the ray to the hit point
@@ -79,11 +58,11 @@ pub fn ray_sphere_intersection(
 
 /// Generate a ray from a camera through the viewport center
 /// This assumes we want to cast through the center of the screen
-pub fn camera_center_ray(camera: &Camera, transform: &Transform) -> Ray {
-    // Camera looks from its position toward the target
-    let direction = (camera.target - transform.position).normalize();
+pub fn camera_center_ray(transform: &Transform) -> Ray {
+    // Cameras look along their local -Z axis
+    let direction = transform.rotation * -Vector3::z();
 
-    Ray::new(transform.position, direction)
+    Ray::new(transform.position.cast(), direction)
 }
 
 #[cfg(test)]